@@ -4,17 +4,178 @@ use anyhow::{anyhow, Result};
 
 use sha3::{Digest, Keccak256};
 
-pub fn get_balance_hash(
-    address: &Vec<u8>,
+/// EIP-712 domain of the balance attestations. The domain has no verifying contract,
+/// the Solana program ID goes in the salt so signatures can't be replayed on another deployment.
+pub const EIP712_DOMAIN_TYPE: &[u8] =
+    b"EIP712Domain(string name,string version,uint256 chainId,bytes32 salt)";
+pub const EIP712_DOMAIN_NAME: &[u8] = b"vamp.fun";
+pub const EIP712_DOMAIN_VERSION: &[u8] = b"1";
+
+/// Balance entry of the snapshot, signed by the solver and the validators. The signer epoch is
+/// the one of the vamp's keys when signing, a key rotation revokes the entries signed before it.
+pub const BALANCE_CLAIM_TYPE: &[u8] =
+    b"BalanceClaim(address holder,uint256 amount,bytes intentId,uint32 signerEpoch)";
+
+/// Signed by the token holder to authorize a claim into a specific Solana wallet.
+/// The recipient is the owner of the destination token account, so the claim can't be redirected.
+pub const CLAIM_OWNERSHIP_TYPE: &[u8] =
+    b"ClaimOwnership(address holder,uint256 amount,bytes intentId,bytes32 recipient)";
+
+/// Root of the Merkle snapshot of an intent, signed by the solver and the validators so the
+/// root a vamp is created or topped up with is attested like the per-holder balances
+pub const SNAPSHOT_ROOT_TYPE: &[u8] =
+    b"SnapshotRoot(bytes32 root,bytes intentId,uint32 signerEpoch)";
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+fn encode_uint256(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn encode_address(address: &[u8]) -> Result<[u8; 32], Box<dyn Error>> {
+    if address.len() != 20 {
+        return Err(format!("Invalid Ethereum address length: {}", address.len()).into());
+    }
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(address);
+    Ok(word)
+}
+
+/// Domain separator for the source chain of the vamped token and the Solana program
+pub fn get_domain_separator(chain_id: u64, program_id: &[u8; 32]) -> [u8; 32] {
+    let mut hash_message = Keccak256::new();
+    hash_message.update(keccak256(EIP712_DOMAIN_TYPE));
+    hash_message.update(keccak256(EIP712_DOMAIN_NAME));
+    hash_message.update(keccak256(EIP712_DOMAIN_VERSION));
+    hash_message.update(encode_uint256(chain_id));
+    hash_message.update(program_id);
+    hash_message.finalize().into()
+}
+
+/// `"\x19\x01" || domainSeparator || hashStruct(message)`, the keccak of it is the signed digest
+fn encode_typed_data(domain_separator: &[u8; 32], struct_hash: &[u8; 32]) -> Vec<u8> {
+    let mut typed_data = Vec::with_capacity(2 + 32 + 32);
+    typed_data.extend_from_slice(b"\x19\x01");
+    typed_data.extend_from_slice(domain_separator);
+    typed_data.extend_from_slice(struct_hash);
+    typed_data
+}
+
+/// Encoded `BalanceClaim` typed data. The Secp256k1 program verifies signatures over its keccak,
+/// which is what `eth_signTypedData_v4` signs.
+pub fn get_balance_claim_typed_data(
+    address: &[u8],
     amount: u64,
-    intent_id: &Vec<u8>,
+    intent_id: &[u8],
+    signer_epoch: u32,
+    chain_id: u64,
+    program_id: &[u8; 32],
 ) -> Result<Vec<u8>, Box<dyn Error>> {
     let mut hash_message = Keccak256::new();
-    hash_message.update(address);
-    hash_message.update(&amount.to_le_bytes());
-    hash_message.update(intent_id);
-    let hash_message = hash_message.finalize();
-    Ok(hash_message.to_vec())
+    hash_message.update(keccak256(BALANCE_CLAIM_TYPE));
+    hash_message.update(encode_address(address)?);
+    hash_message.update(encode_uint256(amount));
+    hash_message.update(keccak256(intent_id));
+    hash_message.update(encode_uint256(signer_epoch.into()));
+    let struct_hash: [u8; 32] = hash_message.finalize().into();
+    Ok(encode_typed_data(
+        &get_domain_separator(chain_id, program_id),
+        &struct_hash,
+    ))
+}
+
+/// EIP-712 digest of the `BalanceClaim`, signed by the solver and the validators
+pub fn get_balance_claim_digest(
+    address: &[u8],
+    amount: u64,
+    intent_id: &[u8],
+    signer_epoch: u32,
+    chain_id: u64,
+    program_id: &[u8; 32],
+) -> Result<[u8; 32], Box<dyn Error>> {
+    let typed_data = get_balance_claim_typed_data(
+        address,
+        amount,
+        intent_id,
+        signer_epoch,
+        chain_id,
+        program_id,
+    )?;
+    Ok(keccak256(&typed_data))
+}
+
+/// Encoded `ClaimOwnership` typed data, see [`get_balance_claim_typed_data`]
+pub fn get_ownership_typed_data(
+    address: &[u8],
+    amount: u64,
+    intent_id: &[u8],
+    destination_owner: &[u8; 32],
+    chain_id: u64,
+    program_id: &[u8; 32],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut hash_message = Keccak256::new();
+    hash_message.update(keccak256(CLAIM_OWNERSHIP_TYPE));
+    hash_message.update(encode_address(address)?);
+    hash_message.update(encode_uint256(amount));
+    hash_message.update(keccak256(intent_id));
+    hash_message.update(destination_owner);
+    let struct_hash: [u8; 32] = hash_message.finalize().into();
+    Ok(encode_typed_data(
+        &get_domain_separator(chain_id, program_id),
+        &struct_hash,
+    ))
+}
+
+/// EIP-712 digest of the `ClaimOwnership`, signed by the token holder
+pub fn get_ownership_digest(
+    address: &[u8],
+    amount: u64,
+    intent_id: &[u8],
+    destination_owner: &[u8; 32],
+    chain_id: u64,
+    program_id: &[u8; 32],
+) -> Result<[u8; 32], Box<dyn Error>> {
+    let typed_data = get_ownership_typed_data(
+        address,
+        amount,
+        intent_id,
+        destination_owner,
+        chain_id,
+        program_id,
+    )?;
+    Ok(keccak256(&typed_data))
+}
+
+/// Encoded `SnapshotRoot` typed data, see [`get_balance_claim_typed_data`]
+pub fn get_snapshot_root_typed_data(
+    root: &[u8; 32],
+    intent_id: &[u8],
+    signer_epoch: u32,
+    chain_id: u64,
+    program_id: &[u8; 32],
+) -> Vec<u8> {
+    let mut hash_message = Keccak256::new();
+    hash_message.update(keccak256(SNAPSHOT_ROOT_TYPE));
+    hash_message.update(root);
+    hash_message.update(keccak256(intent_id));
+    hash_message.update(encode_uint256(signer_epoch.into()));
+    let struct_hash: [u8; 32] = hash_message.finalize().into();
+    encode_typed_data(&get_domain_separator(chain_id, program_id), &struct_hash)
+}
+
+/// EIP-712 digest of the `SnapshotRoot`, signed by the solver and the validators
+pub fn get_snapshot_root_digest(
+    root: &[u8; 32],
+    intent_id: &[u8],
+    signer_epoch: u32,
+    chain_id: u64,
+    program_id: &[u8; 32],
+) -> [u8; 32] {
+    keccak256(&get_snapshot_root_typed_data(root, intent_id, signer_epoch, chain_id, program_id))
 }
 
 pub fn convert_to_sol(src_amount: &U256) -> Result<(u64, u8)> {
@@ -92,21 +253,99 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_balance_hash() {
+    fn test_get_balance_claim_digest() {
         // Test setup
         let address = hex::decode("589A698b7b7dA0Bec545177D3963A2741105C7C9").unwrap();
         let amount = 1_000_000_000u64;
         let intent_id = hex::decode("1111111111111111111111111111111111111111111111111111111111111111").unwrap();
+        let program_id = [9u8; 32];
 
         // Test case: Valid inputs
-        let balance_hash = get_balance_hash(&address, amount, &intent_id);
-        assert!(balance_hash.is_ok());
+        let typed_data = get_balance_claim_typed_data(&address, amount, &intent_id, 0, 1, &program_id);
+        assert!(typed_data.is_ok());
+        let typed_data = typed_data.unwrap();
+        assert_eq!(&typed_data[..2], b"\x19\x01");
+        assert_eq!(&typed_data[2..34], &get_domain_separator(1, &program_id));
+
+        let digest = get_balance_claim_digest(&address, amount, &intent_id, 0, 1, &program_id);
+        assert!(digest.is_ok());
         assert_eq!(
-            balance_hash.unwrap(),
-            vec![
-                181, 209, 203, 211, 173, 67, 135, 228, 171, 113, 74, 177, 223, 120, 19, 120,
-                245, 152, 134, 189, 69, 93, 73, 168, 41, 70, 164, 38, 255, 208, 97, 141
-            ]
+            hex::encode(digest.unwrap()),
+            "ddcd6ccc5ef22a471beed674213b9110fb88b4463fbc0799afb4281e1a78efc4"
+        );
+
+        // Another source chain or deployment yields another digest
+        assert_ne!(
+            get_balance_claim_digest(&address, amount, &intent_id, 0, 10, &program_id).unwrap(),
+            get_balance_claim_digest(&address, amount, &intent_id, 0, 1, &program_id).unwrap()
+        );
+        assert_ne!(
+            get_balance_claim_digest(&address, amount, &intent_id, 0, 1, &[8u8; 32]).unwrap(),
+            get_balance_claim_digest(&address, amount, &intent_id, 0, 1, &program_id).unwrap()
+        );
+        // Entries signed before a key rotation don't pass for the new signer epoch
+        assert_ne!(
+            get_balance_claim_digest(&address, amount, &intent_id, 1, 1, &program_id).unwrap(),
+            get_balance_claim_digest(&address, amount, &intent_id, 0, 1, &program_id).unwrap()
+        );
+
+        // Test case: Invalid address
+        assert!(get_balance_claim_digest(&address[1..], amount, &intent_id, 0, 1, &program_id).is_err());
+    }
+
+    #[test]
+    fn test_get_ownership_digest() {
+        let address = hex::decode("589A698b7b7dA0Bec545177D3963A2741105C7C9").unwrap();
+        let amount = 1_000_000_000u64;
+        let intent_id = hex::decode("1111111111111111111111111111111111111111111111111111111111111111").unwrap();
+        let destination_owner = [7u8; 32];
+        let program_id = [9u8; 32];
+
+        let ownership_digest =
+            get_ownership_digest(&address, amount, &intent_id, &destination_owner, 1, &program_id);
+        assert!(ownership_digest.is_ok());
+        let ownership_digest = ownership_digest.unwrap();
+        assert_eq!(
+            hex::encode(ownership_digest),
+            "1641547755567964eb9b27e6f313f829b91f1105334a0a8a0a153c7ef26673ed"
+        );
+
+        // Same balance entry, different destination
+        let other_destination = [8u8; 32];
+        let other_digest =
+            get_ownership_digest(&address, amount, &intent_id, &other_destination, 1, &program_id)
+                .unwrap();
+        assert_ne!(ownership_digest, other_digest);
+        assert_ne!(
+            ownership_digest,
+            get_balance_claim_digest(&address, amount, &intent_id, 0, 1, &program_id).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_snapshot_root_digest() {
+        let intent_id = hex::decode("1111111111111111111111111111111111111111111111111111111111111111").unwrap();
+        let program_id = [9u8; 32];
+
+        let typed_data = get_snapshot_root_typed_data(&[5u8; 32], &intent_id, 0, 1, &program_id);
+        assert_eq!(&typed_data[2..34], &get_domain_separator(1, &program_id));
+        assert_eq!(
+            get_snapshot_root_digest(&[5u8; 32], &intent_id, 0, 1, &program_id),
+            keccak256(&typed_data)
+        );
+
+        // The root is bound to its intent
+        assert_ne!(
+            get_snapshot_root_digest(&[5u8; 32], &intent_id, 0, 1, &program_id),
+            get_snapshot_root_digest(&[5u8; 32], &intent_id[1..], 0, 1, &program_id)
+        );
+        assert_ne!(
+            get_snapshot_root_digest(&[5u8; 32], &intent_id, 0, 1, &program_id),
+            get_snapshot_root_digest(&[6u8; 32], &intent_id, 0, 1, &program_id)
+        );
+        assert_ne!(
+            get_snapshot_root_digest(&[5u8; 32], &intent_id, 0, 1, &program_id),
+            get_snapshot_root_digest(&[5u8; 32], &intent_id, 1, 1, &program_id)
         );
     }
 
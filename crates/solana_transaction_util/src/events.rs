@@ -0,0 +1,132 @@
+use anyhow::{Result, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::solana_vamp_program::{self, utils::Event};
+
+/// Prefix of the log lines written by `emit!`
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Parses the vamp program events from transaction logs, in emission order.
+/// Data logged by other programs, including the ones invoked by the vamp program, is skipped.
+pub fn parse_vamp_events(logs: &[String]) -> Result<Vec<Event>> {
+    let program_id = solana_vamp_program::ID.to_string();
+    // Whether each program on the invocation stack is the vamp program
+    let mut invocations: Vec<bool> = Vec::new();
+    let mut events = Vec::new();
+
+    for log in logs {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if invocations.last() != Some(&true) {
+                continue;
+            }
+            let bytes = STANDARD
+                .decode(data)
+                .map_err(|e| anyhow!("Failed to decode event data: {}", e))?;
+            // Events added to the program after the IDL was generated are not known here
+            if let Ok(event) = Event::try_from_bytes(&bytes) {
+                events.push(event);
+            }
+            continue;
+        }
+
+        let mut parts = log.split_whitespace();
+        if parts.next() != Some("Program") {
+            continue;
+        }
+        match (parts.next(), parts.next()) {
+            (Some(id), Some("invoke")) => invocations.push(id == program_id),
+            (Some(_), Some("success")) | (Some(_), Some("failed:")) => {
+                invocations.pop();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::Event as _;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::solana_vamp_program::events::{ReserveWithdrawn, TokensClaimed};
+
+    fn data_log(data: Vec<u8>) -> String {
+        format!("{}{}", PROGRAM_DATA_PREFIX, STANDARD.encode(data))
+    }
+
+    #[test]
+    fn test_parse_vamp_events() {
+        let program_id = solana_vamp_program::ID.to_string();
+        let token_program_id = spl_token::ID.to_string();
+        let claimed = TokensClaimed {
+            mint_account: Pubkey::new_unique(),
+            eth_address: [7; 20],
+            claimer: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            amount: 1_000_000_000,
+            sol_paid: 10_000_000,
+            total_claimed: 3_000_000_000,
+            protocol_fee: 250_000,
+            referral_fee: 100_000,
+            referrer: Some(Pubkey::new_unique()),
+        };
+        let withdrawn = ReserveWithdrawn {
+            mint_account: claimed.mint_account,
+            authority: Pubkey::new_unique(),
+            treasury: Pubkey::new_unique(),
+            amount: 5_000_000,
+            reserve_balance: 5_000_000,
+        };
+
+        let logs = vec![
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]".to_string(),
+            "Program ComputeBudget111111111111111111111111111111 success".to_string(),
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Instruction: Claim".to_string(),
+            format!("Program {} invoke [2]", token_program_id),
+            // Data logged by a CPI target doesn't belong to the vamp program
+            data_log(withdrawn.data()),
+            format!("Program {} success", token_program_id),
+            data_log(claimed.data()),
+            format!("Program {} consumed 42000 of 200000 compute units", program_id),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", program_id),
+            data_log(withdrawn.data()),
+            format!("Program {} success", program_id),
+        ];
+
+        let events = parse_vamp_events(&logs).unwrap();
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            Event::TokensClaimed(event) => {
+                assert_eq!(event.eth_address, claimed.eth_address);
+                assert_eq!(event.destination, claimed.destination);
+                assert_eq!(event.amount, claimed.amount);
+                assert_eq!(event.sol_paid, claimed.sol_paid);
+                assert_eq!(event.total_claimed, claimed.total_claimed);
+                assert_eq!(event.protocol_fee, claimed.protocol_fee);
+                assert_eq!(event.referrer, claimed.referrer);
+            }
+            _ => panic!("Expected TokensClaimed"),
+        }
+        match &events[1] {
+            Event::ReserveWithdrawn(event) => {
+                assert_eq!(event.treasury, withdrawn.treasury);
+                assert_eq!(event.amount, withdrawn.amount);
+            }
+            _ => panic!("Expected ReserveWithdrawn"),
+        }
+    }
+
+    #[test]
+    fn test_parse_vamp_events_invalid_data() {
+        let logs = vec![
+            format!("Program {} invoke [1]", solana_vamp_program::ID),
+            format!("{}not base64!", PROGRAM_DATA_PREFIX),
+        ];
+        assert!(parse_vamp_events(&logs).is_err());
+    }
+}
@@ -1,21 +1,137 @@
 use std::sync::Arc;
 
 use anchor_client::Program;
-use anchor_lang::{InstructionData, ToAccountMetas, declare_program};
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas, declare_program};
 use anyhow::{Result, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD};
 
 use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
 use solana_commitment_config::CommitmentConfig;
 use solana_sdk::hash::Hash;
+use solana_sdk::packet::PACKET_DATA_SIZE;
 use solana_sdk::signature::Signature;
 use solana_sdk::{
-    compute_budget::ComputeBudgetInstruction, pubkey::Pubkey, signature::Keypair,
-    signer::Signer as SolanaSigner, transaction::Transaction,
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+    signature::Keypair, signer::Signer as SolanaSigner, transaction::Transaction,
 };
+use solana_transaction_status_client_types::{UiTransactionEncoding, option_serializer::OptionSerializer};
+use spl_token::solana_program::program_pack::Pack;
 use tracing::info;
 
+pub mod events;
+pub mod secp256k1;
+
 declare_program!(solana_vamp_program);
 
+/// Address of the account marking a holder's claim in the given snapshot epoch of a vamp.
+/// The initial epoch has no epoch seed, later ones append it in little endian.
+pub fn claim_state_address(vamp_state: &Pubkey, eth_address: &[u8; 20], snapshot_epoch: u32) -> Pubkey {
+    find_claim_state_address(vamp_state, eth_address, snapshot_epoch).0
+}
+
+/// Same as `claim_state_address` along with the bump, which `claim_batch` takes with the claim
+pub fn find_claim_state_address(vamp_state: &Pubkey, eth_address: &[u8; 20], snapshot_epoch: u32) -> (Pubkey, u8) {
+    find_epoch_account_address(b"claim", vamp_state, eth_address, snapshot_epoch)
+}
+
+/// Address of the vesting account created by a holder's claim in the given snapshot epoch
+pub fn vesting_state_address(vamp_state: &Pubkey, eth_address: &[u8; 20], snapshot_epoch: u32) -> Pubkey {
+    find_vesting_state_address(vamp_state, eth_address, snapshot_epoch).0
+}
+
+/// Same as `vesting_state_address` along with the bump
+pub fn find_vesting_state_address(vamp_state: &Pubkey, eth_address: &[u8; 20], snapshot_epoch: u32) -> (Pubkey, u8) {
+    find_epoch_account_address(b"vesting", vamp_state, eth_address, snapshot_epoch)
+}
+
+/// Holders tracked by one claim bitmap page of vamps with bitmap claim tracking
+pub const CLAIM_BITMAP_BITS: u64 = 65_536;
+
+/// Address of the claim bitmap page of the given snapshot epoch, page `n` tracks the Merkle
+/// leaves from `n * CLAIM_BITMAP_BITS`
+pub fn claim_bitmap_address(vamp_state: &Pubkey, page: u32, snapshot_epoch: u32) -> Pubkey {
+    find_epoch_account_address(b"claim_bitmap", vamp_state, &page.to_le_bytes(), snapshot_epoch).0
+}
+
+/// Address of the archive of a snapshot epoch, written by the top-up closing the epoch
+pub fn epoch_snapshot_address(vamp_state: &Pubkey, snapshot_epoch: u32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"epoch_snapshot", vamp_state.as_ref(), &snapshot_epoch.to_le_bytes()],
+        &solana_vamp_program::ID,
+    )
+    .0
+}
+
+fn find_epoch_account_address(
+    prefix: &[u8],
+    vamp_state: &Pubkey,
+    key: &[u8],
+    snapshot_epoch: u32,
+) -> (Pubkey, u8) {
+    let epoch_seed = if snapshot_epoch == 0 {
+        vec![]
+    } else {
+        snapshot_epoch.to_le_bytes().to_vec()
+    };
+    Pubkey::find_program_address(
+        &[prefix, vamp_state.as_ref(), key, &epoch_seed],
+        &solana_vamp_program::ID,
+    )
+}
+
+/// Signs a transaction running the program instruction after a Secp256k1 program instruction
+/// verifying the signatures. A compute unit limit adds a compute budget instruction in front.
+pub fn transaction_with_signatures<TransactionAccounts, TransactionArgs>(
+    payer_keypair: &Keypair,
+    program: &Program<Arc<Keypair>>,
+    signatures: &[secp256k1::EthSignature],
+    transaction_accounts: TransactionAccounts,
+    transaction_args: TransactionArgs,
+    compute_unit_limit: Option<u32>,
+    recent_blockhash: Hash,
+) -> Result<Transaction>
+where
+    TransactionAccounts: ToAccountMetas,
+    TransactionArgs: InstructionData,
+{
+    let program_instructions = program
+        .request()
+        .accounts(transaction_accounts)
+        .args(transaction_args)
+        .instructions()?;
+
+    let mut all_instructions: Vec<Instruction> = compute_unit_limit
+        .map(ComputeBudgetInstruction::set_compute_unit_limit)
+        .into_iter()
+        .collect();
+    // The Secp256k1 instruction references its own position for the signature data
+    let secp256k1_index = all_instructions.len() as u8;
+    all_instructions.push(secp256k1::new_secp256k1_instruction(secp256k1_index, signatures)?);
+    all_instructions.extend(program_instructions);
+
+    Ok(Transaction::new_signed_with_payer(
+        &all_instructions,
+        Some(&payer_keypair.pubkey()),
+        &[payer_keypair],
+        recent_blockhash,
+    ))
+}
+
+/// Whether the serialized transaction fits the packet size limit of the cluster
+pub fn fits_in_packet(transaction: &Transaction) -> Result<bool> {
+    let size = bincode::serialized_size(transaction)
+        .map_err(|e| anyhow!("Failed to size transaction: {}", e))?;
+    Ok(size as usize <= PACKET_DATA_SIZE)
+}
+
+/// A program instruction along with the signatures the Secp256k1 program verifies for it
+pub struct AttestedInstruction<'a, TransactionAccounts, TransactionArgs> {
+    pub signatures: &'a [secp256k1::EthSignature],
+    pub accounts: TransactionAccounts,
+    pub args: TransactionArgs,
+}
+
 pub struct SolanaTransaction {
     client: RpcClient,
 }
@@ -65,6 +181,67 @@ impl SolanaTransaction {
         Ok((tx, mint_account, vamp_state))
     }
 
+    /// Builds a transaction whose first instruction has the Secp256k1 program verify the signatures
+    /// the vamp program instruction relies on. Signature checks don't run in the program, so the
+    /// default compute budget is enough and no limit is requested.
+    pub async fn prepare_with_signatures<TransactionAccounts, TransactionArgs>(
+        &self,
+        payer_keypair: Arc<Keypair>,
+        program: Arc<Program<Arc<Keypair>>>,
+        signatures: &[secp256k1::EthSignature],
+        transaction_accounts: TransactionAccounts,
+        transaction_args: TransactionArgs,
+    ) -> Result<Transaction>
+    where
+        TransactionAccounts: ToAccountMetas,
+        TransactionArgs: InstructionData,
+    {
+        let recent_blockhash = self.get_latest_block_hash().await?;
+        transaction_with_signatures(
+            &payer_keypair,
+            &program,
+            signatures,
+            transaction_accounts,
+            transaction_args,
+            None,
+            recent_blockhash,
+        )
+    }
+
+    /// Same as `prepare` for instructions that also rely on signatures verified by a preceding
+    /// Secp256k1 program instruction, like a vamp created with an attested snapshot root.
+    /// Without signatures the transaction is the one `prepare` builds.
+    pub async fn prepare_attested<TransactionAccounts, TransactionArgs>(
+        &self,
+        payer_keypair: Arc<Keypair>,
+        program: Arc<Program<Arc<Keypair>>>,
+        mint_account: Pubkey,
+        vamp_state: Pubkey,
+        instruction: AttestedInstruction<'_, TransactionAccounts, TransactionArgs>,
+    ) -> Result<(Transaction, Pubkey, Pubkey)>
+    where
+        TransactionAccounts: ToAccountMetas,
+        TransactionArgs: InstructionData,
+    {
+        let AttestedInstruction { signatures, accounts, args } = instruction;
+        if signatures.is_empty() {
+            return self
+                .prepare(payer_keypair, program, mint_account, vamp_state, accounts, args)
+                .await;
+        }
+        let recent_blockhash = self.get_latest_block_hash().await?;
+        let transaction = transaction_with_signatures(
+            &payer_keypair,
+            &program,
+            signatures,
+            accounts,
+            args,
+            Some(2_000_000),
+            recent_blockhash,
+        )?;
+        Ok((transaction, mint_account, vamp_state))
+    }
+
     pub async fn submit_transaction(&self, transaction: Transaction) -> Result<Signature> {
         let tx_sig = self.client
             .send_and_confirm_transaction(&transaction)
@@ -74,6 +251,181 @@ impl SolanaTransaction {
         Ok(tx_sig)
     }
 
+    /// Returns the wallet that owns the given SPL token account
+    pub async fn get_token_account_owner(&self, token_account: &Pubkey) -> Result<Pubkey> {
+        let account = self.client
+            .get_account(token_account)
+            .map_err(|e| anyhow!("Failed to get token account {}: {}", token_account, e))?;
+        // Token-2022 accounts append their extensions after the classic layout
+        let base_state = account
+            .data
+            .get(..spl_token::state::Account::LEN)
+            .ok_or_else(|| anyhow!("Token account {} is too short", token_account))?;
+        let token_account = spl_token::state::Account::unpack(base_state)
+            .map_err(|e| anyhow!("Failed to parse token account: {}", e))?;
+        Ok(token_account.owner)
+    }
+
+    /// Returns the token program of a mint, the classic SPL Token or Token-2022
+    pub async fn get_mint_token_program(&self, mint_account: &Pubkey) -> Result<Pubkey> {
+        let account = self.client
+            .get_account(mint_account)
+            .map_err(|e| anyhow!("Failed to get mint {}: {}", mint_account, e))?;
+        if account.owner != spl_token::ID && account.owner != spl_token_2022::ID {
+            return Err(anyhow!("Mint {} is owned by {}, not a token program", mint_account, account.owner));
+        }
+        Ok(account.owner)
+    }
+
+    /// Returns the decimals of a classic SPL Token or Token-2022 mint
+    pub async fn get_mint_decimals(&self, mint_account: &Pubkey) -> Result<u8> {
+        let account = self.client
+            .get_account(mint_account)
+            .map_err(|e| anyhow!("Failed to get mint {}: {}", mint_account, e))?;
+        // Token-2022 mints append their extensions after the classic layout
+        let base_state = account
+            .data
+            .get(..spl_token::state::Mint::LEN)
+            .ok_or_else(|| anyhow!("Mint {} is too short", mint_account))?;
+        let mint = spl_token::state::Mint::unpack(base_state)
+            .map_err(|e| anyhow!("Failed to parse mint: {}", e))?;
+        Ok(mint.decimals)
+    }
+
+    /// Fetches and decodes a vamp state account
+    pub async fn get_vamp_state(
+        &self,
+        vamp_state: &Pubkey,
+    ) -> Result<solana_vamp_program::accounts::VampState> {
+        let account = self.client
+            .get_account(vamp_state)
+            .map_err(|e| anyhow!("Failed to get vamp state {}: {}", vamp_state, e))?;
+        solana_vamp_program::accounts::VampState::try_deserialize(&mut account.data.as_slice())
+            .map_err(|e| anyhow!("Failed to parse vamp state: {}", e))
+    }
+
+    /// Finds the closed epoch of a vamp attested for `intent_id`, searching from the latest one.
+    /// Returns the address of its archive and its epoch.
+    pub async fn find_epoch_snapshot(
+        &self,
+        vamp_state: &Pubkey,
+        current_epoch: u32,
+        intent_id: &[u8],
+    ) -> Result<Option<(Pubkey, u32)>> {
+        for snapshot_epoch in (0..current_epoch).rev() {
+            let address = epoch_snapshot_address(vamp_state, snapshot_epoch);
+            let account = self.client
+                .get_account(&address)
+                .map_err(|e| anyhow!("Failed to get epoch snapshot {}: {}", address, e))?;
+            let epoch_snapshot =
+                solana_vamp_program::accounts::EpochSnapshot::try_deserialize(&mut account.data.as_slice())
+                    .map_err(|e| anyhow!("Failed to parse epoch snapshot: {}", e))?;
+            if epoch_snapshot.intent_id == intent_id {
+                return Ok(Some((address, snapshot_epoch)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Fetches and decodes the program-wide config
+    pub async fn get_global_config(&self) -> Result<solana_vamp_program::accounts::GlobalConfig> {
+        let (global_config, _) =
+            Pubkey::find_program_address(&[b"config"], &solana_vamp_program::ID);
+        let account = self.client
+            .get_account(&global_config)
+            .map_err(|e| anyhow!("Failed to get global config {}: {}", global_config, e))?;
+        solana_vamp_program::accounts::GlobalConfig::try_deserialize(&mut account.data.as_slice())
+            .map_err(|e| anyhow!("Failed to parse global config: {}", e))
+    }
+
+    /// Fetches a confirmed transaction and decodes the vamp program events from its logs
+    pub async fn get_transaction_events(
+        &self,
+        signature: &Signature,
+    ) -> Result<Vec<solana_vamp_program::utils::Event>> {
+        let transaction = self.client
+            .get_transaction(signature, UiTransactionEncoding::Json)
+            .map_err(|e| anyhow!("Failed to get transaction {}: {}", signature, e))?;
+        let logs = match transaction.transaction.meta.map(|meta| meta.log_messages) {
+            Some(OptionSerializer::Some(logs)) => logs,
+            _ => return Err(anyhow!("Transaction {} has no logs", signature)),
+        };
+        events::parse_vamp_events(&logs)
+    }
+
+    /// Asks the program for the exact lamports a claim of `balance` base units would cost right now,
+    /// fees included. The `payer` only has to exist, the transaction is simulated and never signed.
+    pub async fn quote_claim(
+        &self,
+        payer: &Pubkey,
+        mint_account: &Pubkey,
+        balance: u64,
+        referred: bool,
+    ) -> Result<u64> {
+        let (vamp_state, _) = Pubkey::find_program_address(
+            &[b"vamp", mint_account.as_ref()],
+            &solana_vamp_program::ID,
+        );
+        let (global_config, _) = Pubkey::find_program_address(&[b"config"], &solana_vamp_program::ID);
+        let instruction = Instruction {
+            program_id: solana_vamp_program::ID,
+            accounts: solana_vamp_program::client::accounts::QuoteClaim {
+                vamp_state,
+                global_config,
+                mint_account: *mint_account,
+            }
+            .to_account_metas(None),
+            data: solana_vamp_program::client::args::QuoteClaim { balance, referred }.data(),
+        };
+
+        let mut return_data = self.simulate_return_data(payer, &[instruction]).await?;
+        if return_data.len() > 8 {
+            return Err(anyhow!("Unexpected quote length {}", return_data.len()));
+        }
+        return_data.resize(8, 0);
+        let mut cost = [0u8; 8];
+        cost.copy_from_slice(&return_data);
+        Ok(u64::from_le_bytes(cost))
+    }
+
+    /// Simulates the instructions and returns the data set by the vamp program with `set_return_data`.
+    /// The runtime trims trailing zero bytes, callers pad the result to the size of the expected type.
+    pub async fn simulate_return_data(
+        &self,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+    ) -> Result<Vec<u8>> {
+        let transaction = Transaction::new_with_payer(instructions, Some(payer));
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            ..Default::default()
+        };
+        let result = self.client
+            .simulate_transaction_with_config(&transaction, config)
+            .map_err(|e| anyhow!("Failed to simulate transaction: {}", e))?
+            .value;
+        if let Some(err) = result.err {
+            return Err(anyhow!(
+                "Simulation failed: {}, logs: {:?}",
+                err,
+                result.logs.unwrap_or_default()
+            ));
+        }
+
+        // A missing return data means the program returned only zero bytes
+        let Some(return_data) = result.return_data else {
+            return Ok(Vec::new());
+        };
+        if return_data.program_id != solana_vamp_program::ID.to_string() {
+            return Err(anyhow!("Return data was set by {}", return_data.program_id));
+        }
+        let (data, _encoding) = return_data.data;
+        STANDARD
+            .decode(data)
+            .map_err(|e| anyhow!("Failed to decode return data: {}", e))
+    }
+
     async fn get_latest_block_hash(&self) -> Result<Hash> {
         // TODO: Add the chain selection logic here
         Ok(self.client
@@ -81,3 +433,182 @@ impl SolanaTransaction {
             .map_err(|e| anyhow!("Failed to get latest blockhash: {}", e))?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::{Client, Cluster};
+    use solana_sdk::instruction::AccountMeta;
+
+    #[test]
+    fn test_claim_state_address_epochs() {
+        let vamp_state = Pubkey::new_unique();
+        let eth_address = [7u8; 20];
+
+        // The initial epoch keeps the addresses of the claims made before top-ups
+        let (initial, _) = Pubkey::find_program_address(
+            &[b"claim", vamp_state.as_ref(), &eth_address],
+            &solana_vamp_program::ID,
+        );
+        assert_eq!(claim_state_address(&vamp_state, &eth_address, 0), initial);
+
+        let (topped_up, _) = Pubkey::find_program_address(
+            &[b"claim", vamp_state.as_ref(), &eth_address, &1u32.to_le_bytes()],
+            &solana_vamp_program::ID,
+        );
+        assert_eq!(claim_state_address(&vamp_state, &eth_address, 1), topped_up);
+        assert_ne!(vesting_state_address(&vamp_state, &eth_address, 1), topped_up);
+
+        // Archives are keyed by their epoch, the initial one included
+        let (initial_archive, _) = Pubkey::find_program_address(
+            &[b"epoch_snapshot", vamp_state.as_ref(), &0u32.to_le_bytes()],
+            &solana_vamp_program::ID,
+        );
+        assert_eq!(epoch_snapshot_address(&vamp_state, 0), initial_archive);
+    }
+
+    #[test]
+    fn test_claim_bitmap_address() {
+        let vamp_state = Pubkey::new_unique();
+
+        let (first_page, _) = Pubkey::find_program_address(
+            &[b"claim_bitmap", vamp_state.as_ref(), &0u32.to_le_bytes()],
+            &solana_vamp_program::ID,
+        );
+        assert_eq!(claim_bitmap_address(&vamp_state, 0, 0), first_page);
+
+        let (topped_up, _) = Pubkey::find_program_address(
+            &[b"claim_bitmap", vamp_state.as_ref(), &2u32.to_le_bytes(), &1u32.to_le_bytes()],
+            &solana_vamp_program::ID,
+        );
+        assert_eq!(claim_bitmap_address(&vamp_state, 2, 1), topped_up);
+    }
+
+    #[test]
+    fn test_claim_with_max_validators_fits_in_packet() {
+        use solana_vamp_program::constants::{CLAIM_TRANSACTION_BASE_SIZE, MAX_VALIDATORS};
+
+        let payer = Arc::new(Keypair::new());
+        let program = Client::new(Cluster::Debug, payer.clone())
+            .program(solana_vamp_program::ID)
+            .unwrap();
+        // The largest signed claim, every optional account is passed
+        let claim_transaction = |validators: u8| {
+            let transaction_accounts = solana_vamp_program::client::accounts::Claim {
+                authority: payer.pubkey(),
+                vamp_state: Pubkey::new_unique(),
+                global_config: Pubkey::new_unique(),
+                claim_state: Some(Pubkey::new_unique()),
+                vault: Pubkey::new_unique(),
+                sol_vault: Pubkey::new_unique(),
+                claimer_token_account: Pubkey::new_unique(),
+                mint_account: Pubkey::new_unique(),
+                token_program: spl_token::ID,
+                system_program: Pubkey::default(),
+                instructions_sysvar: solana_sdk::sysvar::instructions::ID,
+                epoch_snapshot: Some(Pubkey::new_unique()),
+                vesting_state: Some(Pubkey::new_unique()),
+                claim_bitmap: None,
+                protocol_treasury: Some(Pubkey::new_unique()),
+                referrer: Some(Pubkey::new_unique()),
+            };
+            // The holder signs the ownership message, the solver and the validators the balance one
+            let signatures: Vec<_> = (0..validators + 2)
+                .map(|signer| secp256k1::EthSignature {
+                    eth_address: [signer; 20],
+                    signature: [27; 65],
+                    message: vec![(signer == 0) as u8; 66],
+                })
+                .collect();
+            transaction_with_signatures(
+                &payer,
+                &program,
+                &signatures,
+                transaction_accounts,
+                solana_vamp_program::client::args::Claim {
+                    eth_address: [0; 20],
+                    balance: 1_000_000_000,
+                    max_total_cost: Some(1_000_000_000),
+                },
+                None,
+                Hash::default(),
+            )
+            .unwrap()
+        };
+
+        let size = |transaction: &Transaction| bincode::serialized_size(transaction).unwrap() as usize;
+        assert_eq!(size(&claim_transaction(0)), CLAIM_TRANSACTION_BASE_SIZE as usize);
+        assert!(fits_in_packet(&claim_transaction(MAX_VALIDATORS)).unwrap());
+        assert!(!fits_in_packet(&claim_transaction(MAX_VALIDATORS + 1)).unwrap());
+    }
+
+    #[test]
+    fn test_fits_in_packet() {
+        let payer = Arc::new(Keypair::new());
+        let program = Client::new(Cluster::Debug, payer.clone())
+            .program(solana_vamp_program::ID)
+            .unwrap();
+        let batch = |claims: u8| {
+            let mut transaction_accounts = solana_vamp_program::client::accounts::ClaimBatch {
+                authority: payer.pubkey(),
+                vamp_state: Pubkey::new_unique(),
+                global_config: Pubkey::new_unique(),
+                vault: Pubkey::new_unique(),
+                sol_vault: Pubkey::new_unique(),
+                mint_account: Pubkey::new_unique(),
+                token_program: spl_token::ID,
+                system_program: Pubkey::default(),
+                instructions_sysvar: solana_sdk::sysvar::instructions::ID,
+                protocol_treasury: None,
+            }
+            .to_account_metas(None);
+            let mut signatures = Vec::new();
+            let mut batch = Vec::new();
+            for claim in 0..claims {
+                // The owner signs its own message, the solver and a validator sign every balance
+                signatures.push(secp256k1::EthSignature {
+                    eth_address: [claim; 20],
+                    signature: [27; 65],
+                    message: vec![claim * 2; 66],
+                });
+                for signer in [u8::MAX, u8::MAX - 1] {
+                    signatures.push(secp256k1::EthSignature {
+                        eth_address: [signer; 20],
+                        signature: [27; 65],
+                        message: vec![claim * 2 + 1; 66],
+                    });
+                }
+                transaction_accounts.push(AccountMeta::new(Pubkey::new_unique(), false));
+                transaction_accounts.push(AccountMeta::new(Pubkey::new_unique(), false));
+                batch.push(solana_vamp_program::types::BatchClaim {
+                    eth_address: [claim; 20],
+                    balance: 1_000_000_000,
+                    max_total_cost: Some(1_000_000_000),
+                    claim_state_bump: 255,
+                    vesting_state_bump: None,
+                });
+            }
+            (
+                signatures,
+                transaction_accounts,
+                solana_vamp_program::client::args::ClaimBatch { claims: batch },
+            )
+        };
+        let legacy_transaction = |claims: u8| {
+            let (signatures, transaction_accounts, transaction_args) = batch(claims);
+            transaction_with_signatures(
+                &payer,
+                &program,
+                &signatures,
+                transaction_accounts,
+                transaction_args,
+                Some(400_000),
+                Hash::default(),
+            )
+            .unwrap()
+        };
+        assert!(fits_in_packet(&legacy_transaction(1)).unwrap());
+        // Signature data and account addresses leave no room for a second claim
+        assert!(!fits_in_packet(&legacy_transaction(2)).unwrap());
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{Result, anyhow};
+use solana_sdk::instruction::Instruction;
+
+const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 11;
+const ETH_ADDRESS_SERIALIZED_SIZE: usize = 20;
+const SIGNATURE_SERIALIZED_SIZE: usize = 65;
+
+/// An EIP-712 signature to be verified by the Secp256k1 program
+#[derive(Debug, Clone)]
+pub struct EthSignature {
+    pub eth_address: [u8; 20],
+    /// 64-byte signature followed by `v`, either 27/28 or the raw recovery id
+    pub signature: [u8; 65],
+    /// The encoded typed data, the Secp256k1 program verifies the signature over its keccak
+    pub message: Vec<u8>,
+}
+
+/// Builds a single Secp256k1 program instruction verifying all the signatures.
+/// Signatures over the same message or by the same signer share those bytes, so a claim with
+/// several validators, or a batch of claims attested by the same signers, stays small.
+/// `instruction_index` is the position of the instruction in the transaction, the vamp program
+/// only accepts signature data carried by the Secp256k1 instruction itself.
+pub fn new_secp256k1_instruction(
+    instruction_index: u8,
+    signatures: &[EthSignature],
+) -> Result<Instruction> {
+    let count = u8::try_from(signatures.len())
+        .map_err(|_| anyhow!("Too many signatures: {}", signatures.len()))?;
+
+    let mut eth_addresses: Vec<&[u8; 20]> = Vec::new();
+    let mut messages: Vec<&[u8]> = Vec::new();
+    for signature in signatures {
+        if !eth_addresses.contains(&&signature.eth_address) {
+            eth_addresses.push(&signature.eth_address);
+        }
+        if !messages.contains(&signature.message.as_slice()) {
+            messages.push(&signature.message);
+        }
+    }
+
+    let signatures_start = 1 + signatures.len() * SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+    let eth_addresses_start = signatures_start + signatures.len() * SIGNATURE_SERIALIZED_SIZE;
+    let messages_start = eth_addresses_start + eth_addresses.len() * ETH_ADDRESS_SERIALIZED_SIZE;
+    let mut message_offsets = Vec::with_capacity(messages.len());
+    let mut offset = messages_start;
+    for message in &messages {
+        message_offsets.push(offset);
+        offset += message.len();
+    }
+    let to_u16 = |value: usize| {
+        u16::try_from(value).map_err(|_| anyhow!("Secp256k1 instruction data is too large"))
+    };
+
+    let mut offsets_data = vec![count];
+    let mut signatures_data = Vec::new();
+    for (i, signature) in signatures.iter().enumerate() {
+        let signature_offset = signatures_start + i * SIGNATURE_SERIALIZED_SIZE;
+        let eth_address_index = eth_addresses
+            .iter()
+            .position(|eth_address| **eth_address == signature.eth_address)
+            .expect("every eth address was collected");
+        let eth_address_offset = eth_addresses_start + eth_address_index * ETH_ADDRESS_SERIALIZED_SIZE;
+        let message = signature.message.as_slice();
+        let message_index = messages
+            .iter()
+            .position(|m| *m == message)
+            .expect("every message was collected");
+
+        offsets_data.extend_from_slice(&to_u16(signature_offset)?.to_le_bytes());
+        offsets_data.push(instruction_index);
+        offsets_data.extend_from_slice(&to_u16(eth_address_offset)?.to_le_bytes());
+        offsets_data.push(instruction_index);
+        offsets_data.extend_from_slice(&to_u16(message_offsets[message_index])?.to_le_bytes());
+        offsets_data.extend_from_slice(&to_u16(message.len())?.to_le_bytes());
+        offsets_data.push(instruction_index);
+
+        // The precompile expects the raw recovery id instead of Ethereum's 27/28
+        let recovery_id = match signature.signature[64] {
+            v @ (27 | 28) => v - 27,
+            v @ (0 | 1) => v,
+            v => return Err(anyhow!("Invalid signature recovery id {}", v)),
+        };
+        signatures_data.extend_from_slice(&signature.signature[..64]);
+        signatures_data.push(recovery_id);
+    }
+
+    let mut data = offsets_data;
+    data.extend(signatures_data);
+    for eth_address in eth_addresses {
+        data.extend_from_slice(eth_address);
+    }
+    for message in messages {
+        data.extend_from_slice(message);
+    }
+
+    Ok(Instruction {
+        program_id: solana_sdk::secp256k1_program::ID,
+        accounts: vec![],
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(private_key: [u8; 32], message: &[u8]) -> EthSignature {
+        let secret_key = libsecp256k1::SecretKey::parse(&private_key).unwrap();
+        let public_key = libsecp256k1::PublicKey::from_secret_key(&secret_key);
+        let eth_address = solana_secp256k1_program::eth_address_from_pubkey(
+            &public_key.serialize()[1..].try_into().unwrap(),
+        );
+        let (signature, recovery_id) =
+            solana_secp256k1_program::sign_message(&private_key, message)
+                .unwrap();
+
+        let mut eth_signature = [0u8; 65];
+        eth_signature[..64].copy_from_slice(&signature);
+        eth_signature[64] = recovery_id + 27;
+        EthSignature {
+            eth_address,
+            signature: eth_signature,
+            message: message.to_vec(),
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_new_secp256k1_instruction_verifies() {
+        let balance_claim = [7u8; 66];
+        let ownership = [8u8; 66];
+        let signatures = vec![
+            signed([1; 32], &balance_claim),
+            signed([2; 32], &balance_claim),
+            signed([3; 32], &ownership),
+        ];
+
+        let instruction = new_secp256k1_instruction(0, &signatures).unwrap();
+        assert_eq!(instruction.program_id, solana_sdk::secp256k1_program::ID);
+        solana_secp256k1_program::verify(
+            &instruction.data,
+            &[&instruction.data],
+            &solana_feature_set::FeatureSet::all_enabled(),
+        )
+        .unwrap();
+
+        // The shared balance message is stored once
+        assert_eq!(
+            instruction.data.len(),
+            1 + 3 * (SIGNATURE_OFFSETS_SERIALIZED_SIZE + 20 + 65) + 2 * balance_claim.len()
+        );
+
+        // A signer attesting several claims has its address stored once
+        let attestations = vec![signed([1; 32], &balance_claim), signed([1; 32], &ownership)];
+        let instruction = new_secp256k1_instruction(0, &attestations).unwrap();
+        solana_secp256k1_program::verify(
+            &instruction.data,
+            &[&instruction.data],
+            &solana_feature_set::FeatureSet::all_enabled(),
+        )
+        .unwrap();
+        assert_eq!(
+            instruction.data.len(),
+            1 + 2 * (SIGNATURE_OFFSETS_SERIALIZED_SIZE + 65) + 20 + 2 * balance_claim.len()
+        );
+
+        // A signature attributed to the wrong address is rejected
+        let mut forged = signatures.clone();
+        forged[0].eth_address = forged[1].eth_address;
+        let instruction = new_secp256k1_instruction(0, &forged).unwrap();
+        assert!(
+            solana_secp256k1_program::verify(
+                &instruction.data,
+                &[&instruction.data],
+                &solana_feature_set::FeatureSet::all_enabled(),
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,551 @@
+//! In-process SVM harness for the vamp program. Runs the program built by `anchor build` next to the
+//! Metaplex token metadata program, which has to be dumped from mainnet first, see the README.
+
+use std::path::Path;
+
+use anchor_lang::{AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas};
+use anchor_spl::metadata::mpl_token_metadata;
+use balance_util::{
+    get_balance_claim_typed_data, get_ownership_typed_data, get_snapshot_root_typed_data,
+};
+use litesvm::{types::TransactionResult, LiteSVM};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_util::secp256k1::{new_secp256k1_instruction, EthSignature};
+use solana_vamp_program::{
+    accounts,
+    event::ErrorCode,
+    instruction,
+    state::{
+        global_config::GlobalConfig,
+        vamp_state::{ClaimState, ClaimTracking, CurveKind, VampState},
+    },
+    BatchClaim, VampingParams,
+};
+use spl_associated_token_account_client::{
+    address::get_associated_token_address, instruction::create_associated_token_account,
+};
+
+const PROGRAM_PATH: &str = "../target/deploy/solana_vamp_program.so";
+const TOKEN_METADATA_PROGRAM_PATH: &str = "fixtures/mpl_token_metadata.so";
+
+pub const DECIMALS: u8 = 9;
+pub const SOURCE_CHAIN_ID: u64 = 1;
+pub const SIGNER_EPOCH: u32 = 0; // The test vamps keep the keys they were created with
+pub const VAMP_AMOUNT: u64 = 1_000_000_000_000_000;
+pub const FLAT_PRICE: u64 = 10_000_000; // 0.01 SOL per whole token
+pub const ONE_TOKEN: u64 = 1_000_000_000;
+
+/// Ethereum key of the solver, a validator or a holder
+pub struct EthKey {
+    secret: [u8; 32],
+    pub address: [u8; 20],
+}
+
+impl EthKey {
+    pub fn new(secret: [u8; 32]) -> Self {
+        let secret_key = libsecp256k1::SecretKey::parse(&secret).expect("valid secret key");
+        let public_key = libsecp256k1::PublicKey::from_secret_key(&secret_key);
+        let address = solana_secp256k1_program::eth_address_from_pubkey(
+            &public_key.serialize()[1..]
+                .try_into()
+                .expect("64-byte public key"),
+        );
+        Self { secret, address }
+    }
+
+    /// Signs the encoded typed data, the Secp256k1 program verifies over its keccak
+    pub fn sign(&self, message: Vec<u8>) -> EthSignature {
+        let (signature, recovery_id) =
+            solana_secp256k1_program::sign_message(&self.secret, &message).expect("signed message");
+        let mut eth_signature = [0u8; 65];
+        eth_signature[..64].copy_from_slice(&signature);
+        eth_signature[64] = recovery_id + 27;
+        EthSignature {
+            eth_address: self.address,
+            signature: eth_signature,
+            message,
+        }
+    }
+}
+
+/// Addresses of a vamp created by `authority`
+pub struct Vamp {
+    pub mint: Pubkey,
+    pub metadata: Pubkey,
+    pub vamp_state: Pubkey,
+    pub vault: Pubkey,
+    pub sol_vault: Pubkey,
+}
+
+impl Vamp {
+    pub fn new(authority: &Pubkey, vamp_identifier: u64) -> Self {
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &solana_vamp_program::ID).0;
+        let mint = pda(&[b"mint", authority.as_ref(), &vamp_identifier.to_le_bytes()]);
+        let (metadata, _) = Pubkey::find_program_address(
+            &[b"metadata", mpl_token_metadata::ID.as_ref(), mint.as_ref()],
+            &mpl_token_metadata::ID,
+        );
+        Self {
+            mint,
+            metadata,
+            vamp_state: pda(&[b"vamp", mint.as_ref()]),
+            vault: pda(&[b"vault", mint.as_ref()]),
+            sol_vault: pda(&[b"sol_vault", mint.as_ref()]),
+        }
+    }
+
+    /// Claim state of the holder for the initial snapshot
+    pub fn claim_state(&self, eth_address: &[u8; 20]) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"claim", self.vamp_state.as_ref(), eth_address],
+            &solana_vamp_program::ID,
+        )
+        .0
+    }
+
+    pub fn vesting_state(&self, eth_address: &[u8; 20]) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"vesting", self.vamp_state.as_ref(), eth_address],
+            &solana_vamp_program::ID,
+        )
+        .0
+    }
+}
+
+pub struct VampTest {
+    pub svm: LiteSVM,
+    /// Creates the vamps and administers the global config
+    pub authority: Keypair,
+    /// Pays for the claims and owns the destination token accounts
+    pub claimer: Keypair,
+    pub solver: EthKey,
+    pub validators: Vec<EthKey>,
+    pub holder: EthKey,
+}
+
+impl Default for VampTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VampTest {
+    pub fn new() -> Self {
+        let mut svm = LiteSVM::new();
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        svm.add_program_from_file(solana_vamp_program::ID, manifest_dir.join(PROGRAM_PATH))
+            .unwrap_or_else(|err| panic!("{PROGRAM_PATH} not found, run `anchor build`: {err:?}"));
+        svm.add_program_from_file(
+            mpl_token_metadata::ID,
+            manifest_dir.join(TOKEN_METADATA_PROGRAM_PATH),
+        )
+        .unwrap_or_else(|err| {
+            panic!("{TOKEN_METADATA_PROGRAM_PATH} not found, dump it from mainnet: {err:?}")
+        });
+
+        let authority = Keypair::new();
+        let claimer = Keypair::new();
+        for payer in [&authority, &claimer] {
+            svm.airdrop(&payer.pubkey(), 1_000 * LAMPORTS_PER_SOL)
+                .expect("airdrop");
+        }
+
+        let mut test = Self {
+            svm,
+            authority,
+            claimer,
+            solver: EthKey::new([1; 32]),
+            validators: vec![EthKey::new([2; 32]), EthKey::new([3; 32])],
+            holder: EthKey::new([4; 32]),
+        };
+        test.set_global_config(test.global_config_fixture());
+        test
+    }
+
+    /// The config `initialize_global_config` creates. The instruction checks the upgrade authority
+    /// of the program data, which the SVM doesn't have, so the account is written directly.
+    pub fn global_config_fixture(&self) -> GlobalConfig {
+        GlobalConfig {
+            bump: Pubkey::find_program_address(&[b"config"], &solana_vamp_program::ID).1,
+            admin: self.authority.pubkey(),
+            emergency_freeze: false,
+            protocol_treasury: self.authority.pubkey(),
+            protocol_fee_bps: 0,
+            referral_fee_bps: 0,
+        }
+    }
+
+    pub fn global_config_address(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"config"], &solana_vamp_program::ID).0
+    }
+
+    pub fn set_global_config(&mut self, global_config: GlobalConfig) {
+        let mut data = Vec::new();
+        global_config
+            .try_serialize(&mut data)
+            .expect("serialized global config");
+        let account = Account {
+            lamports: self.svm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: solana_vamp_program::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let address = self.global_config_address();
+        self.svm
+            .set_account(address, account)
+            .expect("global config account");
+    }
+
+    /// Free flat price vamp, either validator can approve the claims
+    pub fn create_token_mint_args(&self, vamp_identifier: u64) -> instruction::CreateTokenMint {
+        instruction::CreateTokenMint {
+            vamp_identifier,
+            token_decimals: DECIMALS,
+            token_name: "My Memetoken".to_string(),
+            token_symbol: "MEME".to_string(),
+            token_erc20_address: vec![0x0a; 20],
+            token_uri: "https://example.com/token/1".to_string(),
+            amount: VAMP_AMOUNT,
+            solver_public_key: self.solver.address.to_vec(),
+            validator_set: self
+                .validators
+                .iter()
+                .map(|validator| validator.address)
+                .collect(),
+            validator_threshold: 1,
+            intent_id: b"intent".to_vec(),
+            source_chain_id: SOURCE_CHAIN_ID,
+            merkle_root: None,
+            claim_deadline: None,
+            vesting: None,
+            claim_tracking: ClaimTracking::Accounts,
+            vamping_params: Some(VampingParams {
+                paid_claiming_enabled: false,
+                use_bonding_curve: false,
+                curve_slope: 0,
+                base_price: 0,
+                max_price: None,
+                flat_price_per_token: FLAT_PRICE,
+                curve_kind: CurveKind::Linear,
+                curve_exponent: 2,
+                curve_step_size: 0,
+                redeem_spread_bps: 0,
+            }),
+        }
+    }
+
+    /// Creates the vamp, a snapshot root is attested by the solver and every validator
+    pub fn create_token_mint(&mut self, args: instruction::CreateTokenMint) -> TransactionResult {
+        let signatures = args
+            .merkle_root
+            .map(|root| self.snapshot_root_signatures(&root, &args.intent_id))
+            .unwrap_or_default();
+        self.create_token_mint_with_signatures(args, &signatures)
+    }
+
+    pub fn create_token_mint_with_signatures(
+        &mut self,
+        args: instruction::CreateTokenMint,
+        signatures: &[EthSignature],
+    ) -> TransactionResult {
+        let vamp = Vamp::new(&self.authority.pubkey(), args.vamp_identifier);
+        let create_token_mint = vamp_instruction(
+            accounts::Initialize {
+                authority: self.authority.pubkey(),
+                mint_account: vamp.mint,
+                metadata_account: vamp.metadata,
+                vamp_state: vamp.vamp_state,
+                vault: vamp.vault,
+                sol_vault: vamp.sol_vault,
+                token_program: anchor_spl::token::ID,
+                token_metadata_program: mpl_token_metadata::ID,
+                system_program: anchor_lang::system_program::ID,
+                associated_token_program: anchor_spl::associated_token::ID,
+                rent: sysvar::rent::ID,
+                instructions_sysvar: sysvar::instructions::ID,
+            },
+            args,
+        );
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(1_400_000)];
+        if !signatures.is_empty() {
+            instructions.push(new_secp256k1_instruction(1, signatures).expect("secp256k1 instruction"));
+        }
+        instructions.push(create_token_mint);
+        self.send(instructions, &self.authority.insecure_clone())
+    }
+
+    /// The snapshot root of the intent signed by the solver and every validator
+    pub fn snapshot_root_signatures(&self, root: &[u8; 32], intent_id: &[u8]) -> Vec<EthSignature> {
+        let snapshot_root = get_snapshot_root_typed_data(
+            root,
+            intent_id,
+            SIGNER_EPOCH,
+            SOURCE_CHAIN_ID,
+            &solana_vamp_program::ID.to_bytes(),
+        );
+        std::iter::once(&self.solver)
+            .chain(&self.validators)
+            .map(|signer| signer.sign(snapshot_root.clone()))
+            .collect()
+    }
+
+    /// Creates the associated token account of `owner` for the vamp mint, paid by the claimer
+    pub fn create_token_account(&mut self, vamp: &Vamp, owner: &Pubkey) -> Pubkey {
+        self.send(
+            vec![create_associated_token_account(
+                &self.claimer.pubkey(),
+                owner,
+                &vamp.mint,
+                &anchor_spl::token::ID,
+            )],
+            &self.claimer.insecure_clone(),
+        )
+        .expect("token account");
+        get_associated_token_address(owner, &vamp.mint)
+    }
+
+    /// Accounts of a claim into `claimer_token_account`, tracked with a claim state account
+    pub fn claim_accounts(&self, vamp: &Vamp, claimer_token_account: Pubkey) -> accounts::Claim {
+        accounts::Claim {
+            authority: self.claimer.pubkey(),
+            vamp_state: vamp.vamp_state,
+            global_config: self.global_config_address(),
+            epoch_snapshot: None,
+            claim_state: Some(vamp.claim_state(&self.holder.address)),
+            vault: vamp.vault,
+            sol_vault: vamp.sol_vault,
+            claimer_token_account,
+            mint_account: vamp.mint,
+            token_program: anchor_spl::token::ID,
+            system_program: anchor_lang::system_program::ID,
+            instructions_sysvar: sysvar::instructions::ID,
+            vesting_state: None,
+            claim_bitmap: None,
+            protocol_treasury: None,
+            referrer: None,
+        }
+    }
+
+    /// The holder's ownership signature for the claimer wallet and the balance claim
+    /// signed by the solver and every validator
+    pub fn claim_signatures(&self, intent_id: &[u8], balance: u64) -> Vec<EthSignature> {
+        let program_id = solana_vamp_program::ID.to_bytes();
+        let balance_claim = get_balance_claim_typed_data(
+            &self.holder.address,
+            balance,
+            intent_id,
+            SIGNER_EPOCH,
+            SOURCE_CHAIN_ID,
+            &program_id,
+        )
+        .expect("balance claim typed data");
+        let ownership = get_ownership_typed_data(
+            &self.holder.address,
+            balance,
+            intent_id,
+            &self.claimer.pubkey().to_bytes(),
+            SOURCE_CHAIN_ID,
+            &program_id,
+        )
+        .expect("ownership typed data");
+
+        let mut signatures = vec![
+            self.holder.sign(ownership),
+            self.solver.sign(balance_claim.clone()),
+        ];
+        signatures.extend(
+            self.validators
+                .iter()
+                .map(|validator| validator.sign(balance_claim.clone())),
+        );
+        signatures
+    }
+
+    pub fn claim_instruction(
+        &self,
+        accounts: accounts::Claim,
+        balance: u64,
+        max_total_cost: Option<u64>,
+    ) -> Instruction {
+        vamp_instruction(
+            accounts,
+            instruction::Claim {
+                eth_address: self.holder.address,
+                balance,
+                max_total_cost,
+            },
+        )
+    }
+
+    /// Sends `claim` after the Secp256k1 instruction verifying the signatures
+    pub fn claim(
+        &mut self,
+        accounts: accounts::Claim,
+        balance: u64,
+        max_total_cost: Option<u64>,
+        signatures: &[EthSignature],
+    ) -> TransactionResult {
+        let secp256k1_instruction =
+            new_secp256k1_instruction(0, signatures).expect("secp256k1 instruction");
+        let claim = self.claim_instruction(accounts, balance, max_total_cost);
+        self.send(
+            vec![secp256k1_instruction, claim],
+            &self.claimer.insecure_clone(),
+        )
+    }
+
+    /// Claims for the holder with `claim_batch`, relayed by the claimer. The batch creates the
+    /// claim state itself, so it reports a holder who already claimed instead of failing in `init`.
+    pub fn claim_batch(
+        &mut self,
+        vamp: &Vamp,
+        claimer_token_account: Pubkey,
+        balance: u64,
+        signatures: &[EthSignature],
+    ) -> TransactionResult {
+        let (claim_state, claim_state_bump) = Pubkey::find_program_address(
+            &[b"claim", vamp.vamp_state.as_ref(), &self.holder.address],
+            &solana_vamp_program::ID,
+        );
+        let mut claim_batch = vamp_instruction(
+            accounts::ClaimBatch {
+                authority: self.claimer.pubkey(),
+                vamp_state: vamp.vamp_state,
+                global_config: self.global_config_address(),
+                vault: vamp.vault,
+                sol_vault: vamp.sol_vault,
+                mint_account: vamp.mint,
+                token_program: anchor_spl::token::ID,
+                system_program: anchor_lang::system_program::ID,
+                instructions_sysvar: sysvar::instructions::ID,
+                protocol_treasury: None,
+            },
+            instruction::ClaimBatch {
+                claims: vec![BatchClaim {
+                    eth_address: self.holder.address,
+                    balance,
+                    max_total_cost: None,
+                    claim_state_bump,
+                    vesting_state_bump: None,
+                }],
+            },
+        );
+        claim_batch.accounts.extend([
+            AccountMeta::new(claim_state, false),
+            AccountMeta::new(claimer_token_account, false),
+        ]);
+        let secp256k1_instruction =
+            new_secp256k1_instruction(0, signatures).expect("secp256k1 instruction");
+        self.send(
+            vec![secp256k1_instruction, claim_batch],
+            &self.claimer.insecure_clone(),
+        )
+    }
+
+    /// Sends the instructions paid and signed by `payer`. The blockhash is expired afterwards,
+    /// so repeating a transaction isn't rejected as already processed.
+    pub fn send(&mut self, instructions: Vec<Instruction>, payer: &Keypair) -> TransactionResult {
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            self.svm.latest_blockhash(),
+        );
+        let result = self.svm.send_transaction(transaction);
+        self.svm.expire_blockhash();
+        result
+    }
+
+    /// Sends a vamp program instruction signed by the vamp authority
+    pub fn send_as_authority(
+        &mut self,
+        accounts: impl ToAccountMetas,
+        data: impl InstructionData,
+    ) -> TransactionResult {
+        let instruction = vamp_instruction(accounts, data);
+        self.send(vec![instruction], &self.authority.insecure_clone())
+    }
+
+    pub fn warp_to(&mut self, unix_timestamp: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = unix_timestamp;
+        self.svm.set_sysvar(&clock);
+    }
+
+    pub fn account<T: AccountDeserialize>(&self, address: &Pubkey) -> Option<T> {
+        let account = self.svm.get_account(address)?;
+        Some(T::try_deserialize(&mut account.data.as_slice()).expect("deserialized account"))
+    }
+
+    pub fn vamp_state(&self, vamp: &Vamp) -> VampState {
+        self.account(&vamp.vamp_state).expect("vamp state")
+    }
+
+    pub fn claim_state(&self, vamp: &Vamp) -> Option<ClaimState> {
+        self.account(&vamp.claim_state(&self.holder.address))
+    }
+
+    pub fn token_balance(&self, token_account: &Pubkey) -> u64 {
+        self.account::<anchor_spl::token::TokenAccount>(token_account)
+            .expect("token account")
+            .amount
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm
+            .get_account(address)
+            .map_or(0, |account| account.lamports)
+    }
+}
+
+pub fn vamp_instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: solana_vamp_program::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Pricing of the vamp created with `args`, the test vamps are created with explicit params
+pub fn vamping_params(args: &mut instruction::CreateTokenMint) -> &mut VampingParams {
+    args.vamping_params
+        .as_mut()
+        .expect("test vamps are created with vamping params")
+}
+
+/// Asserts that the transaction failed with the program error, or with an Anchor error
+pub fn assert_error(result: TransactionResult, error: impl Into<u32> + std::fmt::Debug) {
+    let error_name = format!("{error:?}");
+    assert_failed(result, &error_name, InstructionError::Custom(error.into()));
+}
+
+/// Asserts that the transaction failed with a runtime error, or with the error of another program
+pub fn assert_instruction_error(result: TransactionResult, error: InstructionError) {
+    let error_name = format!("{error:?}");
+    assert_failed(result, &error_name, error);
+}
+
+fn assert_failed(result: TransactionResult, error_name: &str, expected: InstructionError) {
+    let failed = match result {
+        Ok(_) => panic!("transaction succeeded, expected {error_name}"),
+        Err(failed) => failed,
+    };
+    match failed.err {
+        TransactionError::InstructionError(_, err) if err == expected => {}
+        err => panic!(
+            "expected {error_name}, got {err:?}, logs: {:#?}",
+            failed.meta.logs
+        ),
+    }
+}
@@ -0,0 +1,567 @@
+use anchor_spl::token::spl_token::error::TokenError;
+use solana_sdk::{
+    instruction::InstructionError,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::error::SystemError;
+use solana_transaction_util::secp256k1::new_secp256k1_instruction;
+use solana_vamp_program::{
+    accounts,
+    event::ErrorCode,
+    instruction::{self, CreateTokenMint},
+    state::{
+        global_config::GlobalConfig,
+        vamp_state::{GraduationConfig, VestingSchedule},
+    },
+};
+use solana_vamp_program_tests::{
+    assert_error, assert_instruction_error, vamp_instruction, vamping_params, Vamp, VampTest,
+    FLAT_PRICE, ONE_TOKEN, VAMP_AMOUNT,
+};
+
+const BALANCE: u64 = 5 * ONE_TOKEN;
+
+/// Creates a vamp and the claimer's token account, `configure` adjusts the vamp settings
+fn setup(configure: impl FnOnce(&mut CreateTokenMint)) -> (VampTest, Vamp, Pubkey) {
+    let mut test = VampTest::new();
+    let mut args = test.create_token_mint_args(1);
+    configure(&mut args);
+    test.create_token_mint(args).unwrap();
+
+    let vamp = Vamp::new(&test.authority.pubkey(), 1);
+    let claimer = test.claimer.pubkey();
+    let claimer_token_account = test.create_token_account(&vamp, &claimer);
+    (test, vamp, claimer_token_account)
+}
+
+fn paid(args: &mut CreateTokenMint) {
+    vamping_params(args).paid_claiming_enabled = true;
+}
+
+#[test]
+fn test_claim() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    let sol_vault_lamports = test.lamports(&vamp.sol_vault);
+
+    assert!(test.claim_state(&vamp).is_none());
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    test.claim(accounts, BALANCE, None, &signatures).unwrap();
+
+    assert_eq!(test.token_balance(&claimer_token_account), BALANCE);
+    assert_eq!(test.token_balance(&vamp.vault), VAMP_AMOUNT - BALANCE);
+    assert!(test.claim_state(&vamp).unwrap().is_claimed);
+    let vamp_state = test.vamp_state(&vamp);
+    assert_eq!(vamp_state.total_claimed, BALANCE);
+    assert_eq!(vamp_state.reserve_balance, 0);
+    assert_eq!(test.lamports(&vamp.sol_vault), sol_vault_lamports);
+
+    // The claim state account exists once the holder claimed, so `init` rejects the second
+    // claim before the program could report TokensAlreadyClaimed. The batch creates the claim
+    // state itself and reports it.
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_instruction_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        InstructionError::Custom(SystemError::AccountAlreadyInUse as u32),
+    );
+    assert_error(
+        test.claim_batch(&vamp, claimer_token_account, BALANCE, &signatures),
+        ErrorCode::TokensAlreadyClaimed,
+    );
+    assert_eq!(test.token_balance(&claimer_token_account), BALANCE);
+    assert_eq!(test.vamp_state(&vamp).total_claimed, BALANCE);
+}
+
+#[test]
+fn test_claim_paid() {
+    let (mut test, vamp, claimer_token_account) = setup(paid);
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    let claim_cost = 5 * FLAT_PRICE;
+    let sol_vault_lamports = test.lamports(&vamp.sol_vault);
+
+    // The claimer's slippage limit is below the cost
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, Some(claim_cost - 1), &signatures),
+        ErrorCode::SlippageExceeded,
+    );
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    test.claim(accounts, BALANCE, Some(claim_cost), &signatures)
+        .unwrap();
+
+    assert_eq!(test.token_balance(&claimer_token_account), BALANCE);
+    assert_eq!(test.vamp_state(&vamp).reserve_balance, claim_cost);
+    assert_eq!(
+        test.lamports(&vamp.sol_vault),
+        sol_vault_lamports + claim_cost
+    );
+}
+
+#[test]
+fn test_claim_batch() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    test.claim_batch(&vamp, claimer_token_account, BALANCE, &signatures)
+        .unwrap();
+    assert_eq!(test.token_balance(&claimer_token_account), BALANCE);
+    assert!(test.claim_state(&vamp).unwrap().is_claimed);
+
+    assert_error(
+        test.claim_batch(&vamp, claimer_token_account, BALANCE, &signatures),
+        ErrorCode::TokensAlreadyClaimed,
+    );
+    assert_eq!(test.vamp_state(&vamp).total_claimed, BALANCE);
+}
+
+#[test]
+fn test_claim_exceeding_vault() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    // The attested balance is more than the vault holds
+    let balance = VAMP_AMOUNT + ONE_TOKEN;
+    let signatures = test.claim_signatures(b"intent", balance);
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_instruction_error(
+        test.claim(accounts, balance, None, &signatures),
+        InstructionError::Custom(TokenError::InsufficientFunds as u32),
+    );
+    assert_eq!(test.token_balance(&vamp.vault), VAMP_AMOUNT);
+}
+
+#[test]
+fn test_claim_cost_overflow() {
+    let (mut test, vamp, claimer_token_account) = setup(|args| {
+        paid(args);
+        vamping_params(args).flat_price_per_token = i64::MAX as u64;
+    });
+
+    // Five tokens at the highest price cost more lamports than a u64 holds
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_instruction_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        InstructionError::ArithmeticOverflow,
+    );
+
+    // Two tokens still fit, the protocol fee on top of them doesn't
+    let global_config = GlobalConfig {
+        protocol_fee_bps: 250,
+        ..test.global_config_fixture()
+    };
+    test.set_global_config(global_config.clone());
+    let balance = 2 * ONE_TOKEN;
+    let signatures = test.claim_signatures(b"intent", balance);
+    let mut accounts = test.claim_accounts(&vamp, claimer_token_account);
+    accounts.protocol_treasury = Some(global_config.protocol_treasury);
+    assert_error(
+        test.claim(accounts, balance, Some(u64::MAX), &signatures),
+        ErrorCode::ArithmeticOverflow,
+    );
+}
+
+#[test]
+fn test_claim_price_cap() {
+    let (mut test, vamp, claimer_token_account) = setup(|args| {
+        paid(args);
+        // The curve starts at the cap and rises above it within the claim
+        let params = vamping_params(args);
+        params.use_bonding_curve = true;
+        params.base_price = FLAT_PRICE;
+        params.curve_slope = 1_000;
+        params.max_price = Some(FLAT_PRICE);
+    });
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::PriceTooHigh,
+    );
+}
+
+#[test]
+fn test_claim_snapshot_vamp() {
+    let (mut test, vamp, claimer_token_account) = setup(|args| args.merkle_root = Some([1; 32]));
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    // Holders of snapshot vamps claim with their Merkle proof
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidClaimMode,
+    );
+}
+
+#[test]
+fn test_claim_signatures() {
+    let (mut test, vamp, claimer_token_account) = setup(|args| args.validator_threshold = 2);
+    // Ownership, solver and one signature per validator
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    let without_solver: Vec<_> = signatures
+        .iter()
+        .filter(|signature| signature.eth_address != test.solver.address)
+        .cloned()
+        .collect();
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &without_solver),
+        ErrorCode::InvalidSolverSignature,
+    );
+
+    // The balance claim was attested for another intent
+    let other_intent = test.claim_signatures(b"other intent", BALANCE);
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &other_intent),
+        ErrorCode::InvalidSolverSignature,
+    );
+
+    // One validator approval, two are required. A repeated signature counts once.
+    let mut one_validator = signatures[..3].to_vec();
+    one_validator.push(signatures[2].clone());
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &one_validator),
+        ErrorCode::ValidatorThresholdNotMet,
+    );
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures[1..]),
+        ErrorCode::InvalidOwnerSignature,
+    );
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    test.claim(accounts, BALANCE, None, &signatures).unwrap();
+    assert_eq!(test.token_balance(&claimer_token_account), BALANCE);
+}
+
+#[test]
+fn test_claim_to_another_wallet() {
+    let (mut test, vamp, _) = setup(|_| {});
+    // The holder signed for the claimer's wallet
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    let other_owner = Keypair::new().pubkey();
+    let other_token_account = test.create_token_account(&vamp, &other_owner);
+
+    let accounts = test.claim_accounts(&vamp, other_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidOwnerSignature,
+    );
+}
+
+#[test]
+fn test_claim_signature_instruction() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    // The second Secp256k1 instruction verifies the data of the first one, which the runtime
+    // accepts. The program only trusts signature data carried by the instruction itself.
+    let verified = new_secp256k1_instruction(0, &signatures).unwrap();
+    let borrowed = verified.clone();
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    let claim = test.claim_instruction(accounts, BALANCE, None);
+    let claimer = test.claimer.insecure_clone();
+    assert_error(
+        test.send(vec![verified, borrowed, claim], &claimer),
+        ErrorCode::InvalidSignatureInstruction,
+    );
+}
+
+#[test]
+fn test_claim_frozen() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    test.set_global_config(GlobalConfig {
+        emergency_freeze: true,
+        ..test.global_config_fixture()
+    });
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::ClaimsFrozen,
+    );
+}
+
+#[test]
+fn test_claim_paused() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    let authority = test.authority.pubkey();
+    let pause_accounts = || accounts::PauseVamp {
+        authority,
+        vamp_state: vamp.vamp_state,
+        mint_account: vamp.mint,
+    };
+
+    let pause = pause_accounts();
+    test.send_as_authority(pause, instruction::Pause {})
+        .unwrap();
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::VampPaused,
+    );
+
+    let unpause = pause_accounts();
+    test.send_as_authority(unpause, instruction::Unpause {})
+        .unwrap();
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    test.claim(accounts, BALANCE, None, &signatures).unwrap();
+}
+
+#[test]
+fn test_claim_deadline() {
+    let (mut test, vamp, claimer_token_account) = setup(|args| args.claim_deadline = Some(100));
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    test.warp_to(101);
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::ClaimWindowClosed,
+    );
+
+    test.warp_to(100);
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    test.claim(accounts, BALANCE, None, &signatures).unwrap();
+}
+
+#[test]
+fn test_claim_tracking_accounts() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    let mut accounts = test.claim_accounts(&vamp, claimer_token_account);
+    accounts.claim_state = None;
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidClaimTracking,
+    );
+}
+
+#[test]
+fn test_claim_vesting() {
+    let (mut test, vamp, claimer_token_account) = setup(|args| {
+        args.vesting = Some(VestingSchedule {
+            start_time: 0,
+            cliff_duration: 0,
+            duration: 100,
+        });
+    });
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidVestingAccount,
+    );
+
+    // A fifth of the schedule has passed
+    test.warp_to(20);
+    let mut accounts = test.claim_accounts(&vamp, claimer_token_account);
+    accounts.vesting_state = Some(vamp.vesting_state(&test.holder.address));
+    test.claim(accounts, BALANCE, None, &signatures).unwrap();
+
+    assert_eq!(test.token_balance(&claimer_token_account), BALANCE / 5);
+    assert_eq!(test.token_balance(&vamp.vault), VAMP_AMOUNT - BALANCE / 5);
+    let vamp_state = test.vamp_state(&vamp);
+    assert_eq!(vamp_state.total_claimed, BALANCE);
+    assert_eq!(vamp_state.vesting_locked, BALANCE - BALANCE / 5);
+}
+
+#[test]
+fn test_claim_fees() {
+    let (mut test, vamp, claimer_token_account) = setup(paid);
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    let protocol_treasury = Keypair::new().pubkey();
+    let referrer = Keypair::new().pubkey();
+    // Fee recipients have to be rent exempt to receive small shares
+    test.svm
+        .airdrop(&protocol_treasury, LAMPORTS_PER_SOL)
+        .unwrap();
+    test.svm.airdrop(&referrer, LAMPORTS_PER_SOL).unwrap();
+
+    // Fees above the payment can't be set through set_protocol_fees
+    test.set_global_config(GlobalConfig {
+        protocol_treasury,
+        protocol_fee_bps: 10_000,
+        referral_fee_bps: 1,
+        ..test.global_config_fixture()
+    });
+    let mut accounts = test.claim_accounts(&vamp, claimer_token_account);
+    accounts.protocol_treasury = Some(protocol_treasury);
+    accounts.referrer = Some(referrer);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidFeeConfig,
+    );
+
+    // 2.5% to the protocol and 1% to the referrer
+    test.set_global_config(GlobalConfig {
+        protocol_treasury,
+        protocol_fee_bps: 250,
+        referral_fee_bps: 100,
+        ..test.global_config_fixture()
+    });
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidProtocolTreasury,
+    );
+
+    let mut accounts = test.claim_accounts(&vamp, claimer_token_account);
+    accounts.protocol_treasury = Some(referrer);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidProtocolTreasury,
+    );
+
+    let mut accounts = test.claim_accounts(&vamp, claimer_token_account);
+    accounts.protocol_treasury = Some(protocol_treasury);
+    accounts.referrer = Some(test.claimer.pubkey());
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::InvalidReferrer,
+    );
+
+    let mut accounts = test.claim_accounts(&vamp, claimer_token_account);
+    accounts.protocol_treasury = Some(protocol_treasury);
+    accounts.referrer = Some(referrer);
+    test.claim(accounts, BALANCE, None, &signatures).unwrap();
+
+    let claim_cost = 5 * FLAT_PRICE;
+    let protocol_fee = claim_cost * 250 / 10_000;
+    let referral_fee = claim_cost * 100 / 10_000;
+    assert_eq!(
+        test.lamports(&protocol_treasury),
+        LAMPORTS_PER_SOL + protocol_fee
+    );
+    assert_eq!(test.lamports(&referrer), LAMPORTS_PER_SOL + referral_fee);
+    // The fees come on top, the reserve keeps the whole curve cost
+    let vamp_state = test.vamp_state(&vamp);
+    assert_eq!(vamp_state.reserve_balance, claim_cost);
+    assert_eq!(vamp_state.protocol_fees_paid, protocol_fee);
+    assert_eq!(vamp_state.referral_fees_paid, referral_fee);
+}
+
+/// Graduates the vamp right away with a zero reserve threshold, returns the LP vault
+fn graduate(test: &mut VampTest, vamp: &Vamp) -> Pubkey {
+    let set_graduation = accounts::SetGraduation {
+        authority: test.authority.pubkey(),
+        vamp_state: vamp.vamp_state,
+        mint_account: vamp.mint,
+    };
+    let graduation = GraduationConfig {
+        reserve_threshold: Some(0),
+        claimed_threshold: None,
+        lp_allocation: ONE_TOKEN,
+    };
+    test.send_as_authority(
+        set_graduation,
+        instruction::SetGraduation {
+            graduation: Some(graduation),
+        },
+    )
+    .unwrap();
+    let lp_vault = lp_vault(vamp);
+    let graduate = accounts::Graduate {
+        payer: test.authority.pubkey(),
+        vamp_state: vamp.vamp_state,
+        mint_account: vamp.mint,
+        lp_vault,
+        token_program: anchor_spl::token::ID,
+        system_program: anchor_lang::system_program::ID,
+    };
+    test.send_as_authority(graduate, instruction::Graduate {})
+        .unwrap();
+    lp_vault
+}
+
+fn lp_vault(vamp: &Vamp) -> Pubkey {
+    Pubkey::find_program_address(&[b"lp_vault", vamp.mint.as_ref()], &solana_vamp_program::ID).0
+}
+
+#[test]
+fn test_claim_graduated() {
+    let (mut test, vamp, claimer_token_account) = setup(|_| {});
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    graduate(&mut test, &vamp);
+
+    let accounts = test.claim_accounts(&vamp, claimer_token_account);
+    assert_error(
+        test.claim(accounts, BALANCE, None, &signatures),
+        ErrorCode::VampGraduated,
+    );
+}
+
+#[test]
+fn test_withdraw_graduation_liquidity() {
+    let (mut test, vamp, claimer_token_account) = setup(paid);
+    let signatures = test.claim_signatures(b"intent", BALANCE);
+    let claim_accounts = test.claim_accounts(&vamp, claimer_token_account);
+    test.claim(claim_accounts, BALANCE, None, &signatures).unwrap();
+    let reserve = 5 * FLAT_PRICE;
+    assert_eq!(test.vamp_state(&vamp).reserve_balance, reserve);
+
+    let treasury = Keypair::new().pubkey();
+    test.svm.airdrop(&treasury, LAMPORTS_PER_SOL).unwrap();
+    let set_treasury = accounts::SetTreasury {
+        authority: test.authority.pubkey(),
+        vamp_state: vamp.vamp_state,
+        mint_account: vamp.mint,
+    };
+    test.send_as_authority(set_treasury, instruction::SetTreasury { treasury })
+        .unwrap();
+    let treasury_token_account = test.create_token_account(&vamp, &treasury);
+    let withdraw = |authority: Pubkey| accounts::WithdrawGraduationLiquidity {
+        authority,
+        vamp_state: vamp.vamp_state,
+        sol_vault: vamp.sol_vault,
+        lp_vault: lp_vault(&vamp),
+        treasury,
+        treasury_token_account,
+        mint_account: vamp.mint,
+        token_program: anchor_spl::token::ID,
+    };
+
+    // Before graduating the reserve backs redeems, there is no LP vault to empty
+    let authority = test.authority.pubkey();
+    assert_error(
+        test.send_as_authority(withdraw(authority), instruction::WithdrawGraduationLiquidity {}),
+        anchor_lang::error::ErrorCode::AccountNotInitialized,
+    );
+
+    graduate(&mut test, &vamp);
+
+    let claimer = test.claimer.insecure_clone();
+    assert_error(
+        test.send(
+            vec![vamp_instruction(
+                withdraw(claimer.pubkey()),
+                instruction::WithdrawGraduationLiquidity {},
+            )],
+            &claimer,
+        ),
+        ErrorCode::Unauthorized,
+    );
+
+    let sol_vault_lamports = test.lamports(&vamp.sol_vault);
+    let treasury_lamports = test.lamports(&treasury);
+    test.send_as_authority(withdraw(authority), instruction::WithdrawGraduationLiquidity {})
+        .unwrap();
+
+    assert_eq!(test.lamports(&vamp.sol_vault), sol_vault_lamports - reserve);
+    assert_eq!(test.lamports(&treasury), treasury_lamports + reserve);
+    assert_eq!(test.token_balance(&treasury_token_account), ONE_TOKEN);
+    assert_eq!(test.token_balance(&lp_vault(&vamp)), 0);
+    assert_eq!(test.vamp_state(&vamp).reserve_balance, 0);
+}
@@ -0,0 +1,170 @@
+use anchor_lang::solana_program::program_option::COption;
+use anchor_spl::token::Mint;
+use solana_sdk::{instruction::InstructionError, signature::Signer};
+use solana_system_interface::error::SystemError;
+use solana_vamp_program::{
+    event::ErrorCode,
+    state::vamp_state::{ClaimTracking, CurveKind, VampStatus, VestingSchedule},
+};
+use solana_vamp_program_tests::{
+    assert_error, assert_instruction_error, vamping_params, Vamp, VampTest, DECIMALS, FLAT_PRICE,
+    VAMP_AMOUNT,
+};
+
+#[test]
+fn test_create_token_mint() {
+    let mut test = VampTest::new();
+    let args = test.create_token_mint_args(1);
+    test.create_token_mint(args).unwrap();
+
+    let vamp = Vamp::new(&test.authority.pubkey(), 1);
+    assert_eq!(test.token_balance(&vamp.vault), VAMP_AMOUNT);
+
+    // The mint signs for itself, only the program can mint more
+    let mint: Mint = test.account(&vamp.mint).unwrap();
+    assert_eq!(mint.supply, VAMP_AMOUNT);
+    assert_eq!(mint.decimals, DECIMALS);
+    assert_eq!(mint.mint_authority, COption::Some(vamp.mint));
+
+    let vamp_state = test.vamp_state(&vamp);
+    assert_eq!(vamp_state.mint, vamp.mint);
+    assert_eq!(vamp_state.authority, test.authority.pubkey());
+    assert_eq!(vamp_state.treasury, test.authority.pubkey());
+    assert_eq!(vamp_state.sol_vault, vamp.sol_vault);
+    assert_eq!(vamp_state.token_supply, VAMP_AMOUNT);
+    assert_eq!(vamp_state.solver_public_key, test.solver.address.to_vec());
+    assert_eq!(vamp_state.validator_set.len(), 2);
+    assert_eq!(vamp_state.validator_threshold, 1);
+    assert_eq!(vamp_state.total_claimed, 0);
+    assert_eq!(vamp_state.reserve_balance, 0);
+    assert_eq!(vamp_state.claim_tracking, ClaimTracking::Accounts);
+    assert_eq!(vamp_state.status, VampStatus::Active);
+
+    // Vamp identifiers are unique per authority, the mint of the identifier already exists
+    let args = test.create_token_mint_args(1);
+    assert_instruction_error(
+        test.create_token_mint(args),
+        InstructionError::Custom(SystemError::AccountAlreadyInUse as u32),
+    );
+}
+
+#[test]
+fn test_create_token_mint_claim_deadline() {
+    let mut test = VampTest::new();
+    test.warp_to(1_000);
+
+    let mut args = test.create_token_mint_args(1);
+    args.claim_deadline = Some(1_000);
+    assert_error(
+        test.create_token_mint(args),
+        ErrorCode::InvalidClaimDeadline,
+    );
+
+    let mut args = test.create_token_mint_args(1);
+    args.claim_deadline = Some(1_001);
+    test.create_token_mint(args).unwrap();
+}
+
+#[test]
+fn test_create_token_mint_validator_set() {
+    let mut test = VampTest::new();
+    let validator = test.validators[0].address;
+
+    let invalid_sets = [
+        (vec![], 1),
+        (vec![validator], 0),
+        (vec![validator], 2),
+        (vec![validator, validator], 1),
+        (vec![[9; 20]; 6], 1),
+    ];
+    for (validator_set, validator_threshold) in invalid_sets {
+        let mut args = test.create_token_mint_args(1);
+        args.validator_set = validator_set;
+        args.validator_threshold = validator_threshold;
+        assert_error(test.create_token_mint(args), ErrorCode::InvalidValidatorSet);
+    }
+}
+
+#[test]
+fn test_create_token_mint_claim_tracking() {
+    let mut test = VampTest::new();
+
+    // Bitmap pages are indexed by the Merkle leaf
+    let mut args = test.create_token_mint_args(1);
+    args.claim_tracking = ClaimTracking::Bitmap;
+    assert_error(test.create_token_mint(args), ErrorCode::InvalidClaimMode);
+
+    let mut args = test.create_token_mint_args(1);
+    args.claim_tracking = ClaimTracking::Bitmap;
+    args.merkle_root = Some([1; 32]);
+    test.create_token_mint(args).unwrap();
+}
+
+#[test]
+fn test_create_token_mint_snapshot_root_attestation() {
+    let mut test = VampTest::new();
+    let root = [1; 32];
+    let signatures = test.snapshot_root_signatures(&root, b"intent");
+
+    // The solver alone can't commit a root
+    let mut args = test.create_token_mint_args(1);
+    args.merkle_root = Some(root);
+    assert_error(
+        test.create_token_mint_with_signatures(args, &signatures[..1]),
+        ErrorCode::ValidatorThresholdNotMet,
+    );
+
+    // Nor can the validators without the solver
+    let mut args = test.create_token_mint_args(1);
+    args.merkle_root = Some(root);
+    assert_error(
+        test.create_token_mint_with_signatures(args, &signatures[1..]),
+        ErrorCode::InvalidSolverSignature,
+    );
+
+    // The attestation is bound to the root
+    let mut args = test.create_token_mint_args(1);
+    args.merkle_root = Some([2; 32]);
+    assert_error(
+        test.create_token_mint_with_signatures(args, &signatures),
+        ErrorCode::InvalidSolverSignature,
+    );
+
+    let mut args = test.create_token_mint_args(1);
+    args.merkle_root = Some(root);
+    test.create_token_mint_with_signatures(args, &signatures).unwrap();
+}
+
+#[test]
+fn test_create_token_mint_vamping_params() {
+    let mut test = VampTest::new();
+
+    // A cap below the flat price would reject every paid claim
+    let mut args = test.create_token_mint_args(1);
+    vamping_params(&mut args).paid_claiming_enabled = true;
+    vamping_params(&mut args).max_price = Some(FLAT_PRICE - 1);
+    assert_error(test.create_token_mint(args), ErrorCode::InvalidVampingParams);
+
+    let mut args = test.create_token_mint_args(1);
+    let params = vamping_params(&mut args);
+    params.use_bonding_curve = true;
+    params.curve_kind = CurveKind::Power;
+    params.curve_exponent = 10;
+    assert_error(test.create_token_mint(args), ErrorCode::InvalidVampingParams);
+}
+
+#[test]
+fn test_create_token_mint_vesting_schedule() {
+    let mut test = VampTest::new();
+
+    let mut args = test.create_token_mint_args(1);
+    args.vesting = Some(VestingSchedule {
+        start_time: 0,
+        cliff_duration: 200,
+        duration: 100,
+    });
+    assert_error(
+        test.create_token_mint(args),
+        ErrorCode::InvalidVestingSchedule,
+    );
+}
@@ -0,0 +1,108 @@
+use anchor_spl::metadata::{mpl_token_metadata, MetadataAccount};
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+use solana_vamp_program::{accounts, event::ErrorCode, instruction};
+use solana_vamp_program_tests::{assert_error, vamp_instruction, Vamp, VampTest};
+
+fn setup() -> (VampTest, Vamp) {
+    let mut test = VampTest::new();
+    let args = test.create_token_mint_args(1);
+    test.create_token_mint(args).unwrap();
+    let vamp = Vamp::new(&test.authority.pubkey(), 1);
+    (test, vamp)
+}
+
+/// The accounts of both metadata instructions for a classic mint, signed by `authority`
+fn metadata_accounts(vamp: &Vamp, authority: Pubkey) -> accounts::UpdateTokenMetadata {
+    accounts::UpdateTokenMetadata {
+        authority,
+        vamp_state: vamp.vamp_state,
+        mint_account: vamp.mint,
+        metadata_account: Some(vamp.metadata),
+        token_metadata_program: Some(mpl_token_metadata::ID),
+        token_program: anchor_spl::token::ID,
+        system_program: anchor_lang::system_program::ID,
+    }
+}
+
+fn update_uri(uri: &str) -> instruction::UpdateTokenMetadata {
+    instruction::UpdateTokenMetadata {
+        name: None,
+        symbol: None,
+        uri: Some(uri.to_string()),
+    }
+}
+
+#[test]
+fn test_update_token_metadata() {
+    let (mut test, vamp) = setup();
+    let authority = test.authority.pubkey();
+    test.send_as_authority(
+        metadata_accounts(&vamp, authority),
+        update_uri("https://vamp.fun/updated.json"),
+    )
+    .unwrap();
+
+    // The fields that aren't given are kept
+    let metadata: MetadataAccount = test.account(&vamp.metadata).unwrap();
+    assert_eq!(
+        metadata.uri.trim_end_matches('\0'),
+        "https://vamp.fun/updated.json"
+    );
+    assert_eq!(metadata.update_authority, vamp.mint);
+    let args = test.create_token_mint_args(1);
+    assert_eq!(metadata.name.trim_end_matches('\0'), args.token_name);
+}
+
+#[test]
+fn test_update_token_metadata_unauthorized() {
+    let (mut test, vamp) = setup();
+    let claimer = test.claimer.insecure_clone();
+    let update = vamp_instruction(
+        metadata_accounts(&vamp, claimer.pubkey()),
+        update_uri("https://vamp.fun/hijacked.json"),
+    );
+    assert_error(test.send(vec![update], &claimer), ErrorCode::Unauthorized);
+
+    let transfer = vamp_instruction(
+        metadata_accounts(&vamp, claimer.pubkey()),
+        instruction::TransferMetadataAuthority {
+            new_authority: Some(claimer.pubkey()),
+        },
+    );
+    assert_error(test.send(vec![transfer], &claimer), ErrorCode::Unauthorized);
+}
+
+#[test]
+fn test_update_token_metadata_after_transfer() {
+    let (mut test, vamp) = setup();
+    let authority = test.authority.pubkey();
+    let new_authority = Pubkey::new_unique();
+    test.send_as_authority(
+        metadata_accounts(&vamp, authority),
+        instruction::TransferMetadataAuthority {
+            new_authority: Some(new_authority),
+        },
+    )
+    .unwrap();
+
+    let metadata: MetadataAccount = test.account(&vamp.metadata).unwrap();
+    assert_eq!(metadata.update_authority, new_authority);
+
+    // The vamp no longer controls the metadata, neither for updates nor for another transfer
+    assert_error(
+        test.send_as_authority(
+            metadata_accounts(&vamp, authority),
+            update_uri("https://vamp.fun/late.json"),
+        ),
+        ErrorCode::MetadataAuthorityTransferred,
+    );
+    assert_error(
+        test.send_as_authority(
+            metadata_accounts(&vamp, authority),
+            instruction::TransferMetadataAuthority {
+                new_authority: Some(authority),
+            },
+        ),
+        ErrorCode::MetadataAuthorityTransferred,
+    );
+}
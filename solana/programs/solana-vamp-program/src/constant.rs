@@ -1 +1,20 @@
-pub const ANCHOR_DISCRIMINATOR: usize = 8;
\ No newline at end of file
+use anchor_lang::prelude::*;
+
+pub const ANCHOR_DISCRIMINATOR: usize = 8;
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Largest serialized transaction the cluster accepts
+pub const PACKET_DATA_SIZE: usize = 1232;
+/// A signed claim with every optional account and no validator signature: the fee payer
+/// signature, message header, 19 account keys, blockhash, the claim instruction and the
+/// Secp256k1 instruction with the solver and holder signatures over their two messages
+#[constant]
+pub const CLAIM_TRANSACTION_BASE_SIZE: u16 = 1039;
+/// A validator signature shares the solver's message, it only adds its offsets, address and signature
+pub const VALIDATOR_SIGNATURE_SIZE: usize = 11 + 20 + 65;
+/// Every validator of the set may have to sign, so the whole set has to fit a claim transaction.
+/// Next to the solver and holder signatures that leaves room for 2 validators, so vamps are
+/// approved 1-of-1, 1-of-2 or 2-of-2.
+#[constant]
+pub const MAX_VALIDATORS: u8 =
+    ((PACKET_DATA_SIZE - CLAIM_TRANSACTION_BASE_SIZE as usize) / VALIDATOR_SIGNATURE_SIZE) as u8;
@@ -1,4 +1,8 @@
 use anchor_lang::prelude::*;
+
+use crate::instructions::initialize::VampingParams;
+use crate::state::vamp_state::GraduationConfig;
+
 // Errors
 #[error_code]
 pub enum ErrorCode {
@@ -26,6 +30,94 @@ pub enum ErrorCode {
     ArithmeticOverflow,
     #[msg("Cost per token exceeds the maximum allowed")]
     PriceTooHigh,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Withdrawal amount exceeds the reserve balance")]
+    InsufficientReserve,
+    #[msg("Treasury account mismatch")]
+    InvalidTreasury,
+    #[msg("Claim cost exceeds the maximum total cost provided")]
+    SlippageExceeded,
+    #[msg("Claim instruction doesn't match the vamp claim mode")]
+    InvalidClaimMode,
+    #[msg("Claim deadline must be in the future")]
+    InvalidClaimDeadline,
+    #[msg("Claim window is closed")]
+    ClaimWindowClosed,
+    #[msg("Claim window is still open")]
+    ClaimWindowOpen,
+    #[msg("Sweep destination is missing or not owned by the treasury")]
+    InvalidSweepDestination,
+    #[msg("Vamping parameters are out of bounds")]
+    InvalidVampingParams,
+    #[msg("Vamping parameters are locked after claiming has started")]
+    VampingParamsLocked,
+    #[msg("Redeeming requires paid claiming")]
+    RedeemDisabled,
+    #[msg("Redeem amount exceeds the claimed supply")]
+    InsufficientClaimedSupply,
+    #[msg("Redeem payout is below the minimum payout provided")]
+    PayoutTooLow,
+    #[msg("Claims on this vamp are paused")]
+    VampPaused,
+    #[msg("Claims are frozen program-wide")]
+    ClaimsFrozen,
+    #[msg("Signer keys must be 20 byte Ethereum addresses")]
+    InvalidSignerKey,
+    #[msg("Signer grace period is out of bounds")]
+    InvalidGracePeriod,
+    #[msg("Validator set is empty, has more than 2 validators, has duplicates or the threshold is out of range")]
+    InvalidValidatorSet,
+    #[msg("Not enough distinct validator signatures")]
+    ValidatorThresholdNotMet,
+    #[msg("Secp256k1 instruction data is malformed or references another instruction")]
+    InvalidSignatureInstruction,
+    #[msg("Vesting schedule needs a positive duration and a cliff within it")]
+    InvalidVestingSchedule,
+    #[msg("Vesting account is missing, or provided for a vamp without vesting")]
+    InvalidVestingAccount,
+    #[msg("No vested tokens to release yet")]
+    NothingToRelease,
+    #[msg("Token account is not the vesting beneficiary")]
+    InvalidBeneficiary,
+    #[msg("Top-up needs a positive amount and a new intent")]
+    InvalidTopUp,
+    #[msg("Claim tracking accounts don't match the vamp claim tracking")]
+    InvalidClaimTracking,
+    #[msg("Holder index is outside the claim bitmap")]
+    InvalidClaimIndex,
+    #[msg("Batch claim accounts don't match the claims")]
+    InvalidBatchAccounts,
+    #[msg("Protocol and referral fees exceed the claim payment")]
+    InvalidFeeConfig,
+    #[msg("Protocol treasury account is missing or doesn't match the global config")]
+    InvalidProtocolTreasury,
+    #[msg("Claimer can't refer their own claim")]
+    InvalidReferrer,
+    #[msg("Vamp has graduated")]
+    VampGraduated,
+    #[msg("Graduation needs at least one threshold")]
+    InvalidGraduationConfig,
+    #[msg("Graduation thresholds are not reached")]
+    GraduationNotReached,
+    #[msg("Metaplex metadata accounts are required for this mint")]
+    InvalidMetadataAccounts,
+    #[msg("Metadata update has no field to change")]
+    InvalidMetadataUpdate,
+    #[msg("Metadata authority was transferred away from the vamp")]
+    MetadataAuthorityTransferred,
+    #[msg("Rotation delay can only be raised, up to 30 days")]
+    InvalidRotationDelay,
+    #[msg("No signer rotation is pending")]
+    NoPendingRotation,
+    #[msg("Signer rotation is still time locked")]
+    RotationTimeLocked,
+    #[msg("Epoch snapshot isn't an archive of this vamp")]
+    InvalidSnapshotEpoch,
+    #[msg("Vamp hasn't graduated")]
+    VampNotGraduated,
+    #[msg("Unclaimed tokens of the snapshot epoch were swept")]
+    EpochSwept,
 }
 
 #[event]
@@ -35,4 +127,197 @@ pub struct TokenMintCreated {
     pub token_symbol: String,
     pub token_erc20_address: String,
     pub amount: u64,
+    pub vamp_state: Pubkey,
+    pub authority: Pubkey,
+    pub intent_id: Vec<u8>,
+    pub merkle_root: Option<[u8; 32]>,
+    pub claim_deadline: Option<i64>,
+    pub token_decimals: u8,
+}
+
+#[event]
+pub struct TokensClaimed {
+    pub mint_account: Pubkey,
+    pub eth_address: [u8; 20],
+    pub claimer: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub sol_paid: u64,
+    pub total_claimed: u64,
+    pub protocol_fee: u64,
+    pub referral_fee: u64,
+    pub referrer: Option<Pubkey>,
+}
+
+#[event]
+pub struct ReserveWithdrawn {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    pub amount: u64,
+    pub reserve_balance: u64,
+}
+
+#[event]
+pub struct TreasuryUpdated {
+    pub mint_account: Pubkey,
+    pub old_treasury: Pubkey,
+    pub new_treasury: Pubkey,
+}
+
+#[event]
+pub struct UnclaimedSwept {
+    pub mint_account: Pubkey,
+    pub destination: Option<Pubkey>,
+    pub burned: bool,
+    pub total_claimed: u64,
+    pub swept: u64,
+    pub snapshot_epoch: u32,
+}
+
+#[event]
+pub struct VampingParamsUpdated {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub old_params: VampingParams,
+    pub new_params: VampingParams,
+    pub params_locked: bool,
+}
+
+#[event]
+pub struct TokensRedeemed {
+    pub mint_account: Pubkey,
+    pub redeemer: Pubkey,
+    pub amount: u64,
+    pub payout: u64,
+    pub spread: u64,
+    pub total_claimed: u64,
+    pub reserve_balance: u64,
+}
+
+#[event]
+pub struct VampPauseUpdated {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub paused: bool,
+}
+
+#[event]
+pub struct EmergencyFreezeUpdated {
+    pub admin: Pubkey,
+    pub emergency_freeze: bool,
+}
+
+#[event]
+pub struct ProtocolFeesUpdated {
+    pub admin: Pubkey,
+    pub protocol_treasury: Pubkey,
+    pub protocol_fee_bps: u16,
+    pub referral_fee_bps: u16,
+}
+
+#[event]
+pub struct SignersRotated {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub signer_epoch: u32,
+    pub solver_public_key: Vec<u8>,
+    pub validator_set: Vec<[u8; 20]>,
+    pub validator_threshold: u8,
+    pub previous_signers_valid_until: Option<i64>,
+}
+
+#[event]
+pub struct TokensReleased {
+    pub mint_account: Pubkey,
+    pub eth_address: [u8; 20],
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub released_amount: u64,
+    pub total_amount: u64,
+}
+
+#[event]
+pub struct VampToppedUp {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub token_supply: u64,
+    pub snapshot_epoch: u32,
+    pub intent_id: Vec<u8>,
+    pub merkle_root: Option<[u8; 32]>,
+    pub claim_deadline: Option<i64>,
+}
+
+#[event]
+pub struct ClaimBitmapCreated {
+    pub mint_account: Pubkey,
+    pub claim_bitmap: Pubkey,
+    pub snapshot_epoch: u32,
+    pub page: u32,
+    pub holders: u32,
+}
+
+#[event]
+pub struct GraduationUpdated {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub graduation: Option<GraduationConfig>,
+}
+
+#[event]
+pub struct VampGraduated {
+    pub mint_account: Pubkey,
+    pub lp_vault: Pubkey,
+    pub lp_allocation: u64,
+    pub reserve_balance: u64,
+    pub total_claimed: u64,
+}
+
+#[event]
+pub struct TokenMetadataUpdated {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+#[event]
+pub struct MetadataAuthorityTransferred {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub new_authority: Option<Pubkey>,
+}
+
+#[event]
+pub struct SignerRotationQueued {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub solver_public_key: Vec<u8>,
+    pub validator_set: Vec<[u8; 20]>,
+    pub validator_threshold: u8,
+    pub executable_at: i64,
+}
+
+#[event]
+pub struct SignerRotationCancelled {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct RotationDelayUpdated {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub rotation_delay: i64,
+}
+
+#[event]
+pub struct GraduationLiquidityWithdrawn {
+    pub mint_account: Pubkey,
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
 }
\ No newline at end of file
@@ -53,6 +53,160 @@ pub fn calculate_claim_cost_bonding_curve(
     Ok(base_cost.checked_add(curve_cost).ok_or(ProgramError::ArithmeticOverflow)?)
 }
 
+/// Library function to calculate cost for claiming tokens on the power curve
+/// price(x) = base_price + curve_slope * x^exponent, integrated over [total_claimed, total_claimed + token_amount]
+pub fn calculate_claim_cost_power_curve(
+    token_amount: Decimal,
+    total_claimed: Decimal,
+    base_price: Decimal,
+    curve_slope: Decimal,
+    exponent: u64,
+) -> Result<Decimal> {
+    if token_amount == Decimal::zero() {
+        return Ok(Decimal::zero());
+    }
+
+    let base_cost = token_amount
+        .checked_mul(base_price)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    // Antiderivative of x^k is x^(k+1) / (k+1)
+    let power = exponent.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+    let mut start = total_claimed;
+    let mut end = total_claimed
+        .checked_add(token_amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // x^(k+1) of a large supply leaves the Decimal range long before the cost does. The curve
+    // is integrated over x / 10^n, keeping x^(k+1) below 10^28, and the result scaled back.
+    let max_base = Decimal::from(10u64.pow(28 / power.min(28) as u32));
+    let mut scale_digits = 0u64;
+    while end > max_base {
+        start = start.checked_div(Decimal::TEN).ok_or(ProgramError::ArithmeticOverflow)?;
+        end = end.checked_div(Decimal::TEN).ok_or(ProgramError::ArithmeticOverflow)?;
+        scale_digits += 1;
+    }
+
+    let mut curve_cost = end
+        .checked_powu(power)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_sub(
+            start
+                .checked_powu(power)
+                .ok_or(ProgramError::ArithmeticOverflow)?,
+        )
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_div(Decimal::from(power))
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_mul(curve_slope)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    for _ in 0..scale_digits * power {
+        curve_cost = curve_cost
+            .checked_mul(Decimal::TEN)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+    }
+
+    Ok(base_cost.checked_add(curve_cost).ok_or(ProgramError::ArithmeticOverflow)?)
+}
+
+/// Library function to calculate cost for claiming tokens on the exponential curve
+/// price(x) = base_price * e^(growth_rate * x), integrated over [total_claimed, total_claimed + token_amount]
+pub fn calculate_claim_cost_exponential_curve(
+    token_amount: Decimal,
+    total_claimed: Decimal,
+    base_price: Decimal,
+    growth_rate: Decimal,
+) -> Result<Decimal> {
+    if token_amount == Decimal::zero() {
+        return Ok(Decimal::zero());
+    }
+    // The curve degenerates into the flat base price
+    if growth_rate == Decimal::zero() {
+        return calculate_claim_cost_fixed_price(token_amount, base_price);
+    }
+
+    let exp_at = |x: Decimal| -> Result<Decimal> {
+        Ok(growth_rate
+            .checked_mul(x)
+            .ok_or(ProgramError::ArithmeticOverflow)?
+            .checked_exp()
+            .ok_or(ProgramError::ArithmeticOverflow)?)
+    };
+    let end = total_claimed
+        .checked_add(token_amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    Ok(exp_at(end)?
+        .checked_sub(exp_at(total_claimed)?)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_mul(base_price)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_div(growth_rate)
+        .ok_or(ProgramError::ArithmeticOverflow)?)
+}
+
+/// Library function to calculate cost for claiming tokens on the piecewise step curve
+/// price(x) = base_price + step_increment * floor(x / step_size), integrated over [total_claimed, total_claimed + token_amount]
+pub fn calculate_claim_cost_step_curve(
+    token_amount: Decimal,
+    total_claimed: Decimal,
+    base_price: Decimal,
+    step_increment: Decimal,
+    step_size: Decimal,
+) -> Result<Decimal> {
+    if token_amount == Decimal::zero() {
+        return Ok(Decimal::zero());
+    }
+    if step_size <= Decimal::zero() {
+        return Err(ProgramError::InvalidArgument.into());
+    }
+
+    let end = total_claimed
+        .checked_add(token_amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    Ok(step_curve_area(end, base_price, step_increment, step_size)?
+        .checked_sub(step_curve_area(total_claimed, base_price, step_increment, step_size)?)
+        .ok_or(ProgramError::ArithmeticOverflow)?)
+}
+
+/// Area under the step curve over [0, x]: the completed steps plus the partial current one
+fn step_curve_area(
+    x: Decimal,
+    base_price: Decimal,
+    step_increment: Decimal,
+    step_size: Decimal,
+) -> Result<Decimal> {
+    let steps = x
+        .checked_div(step_size)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .floor();
+    let step_start = steps
+        .checked_mul(step_size)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    // step_size * (0 + 1 + ... + (steps - 1)) for the completed steps
+    let completed_steps = steps
+        .checked_mul(steps.checked_sub(Decimal::ONE).ok_or(ProgramError::ArithmeticOverflow)?)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_div(Decimal::TWO)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_mul(step_size)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let current_step = steps
+        .checked_mul(x.checked_sub(step_start).ok_or(ProgramError::ArithmeticOverflow)?)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    Ok(x
+        .checked_mul(base_price)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        .checked_add(
+            completed_steps
+                .checked_add(current_step)
+                .ok_or(ProgramError::ArithmeticOverflow)?
+                .checked_mul(step_increment)
+                .ok_or(ProgramError::ArithmeticOverflow)?,
+        )
+        .ok_or(ProgramError::ArithmeticOverflow)?)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -105,4 +259,160 @@ mod test {
         );
         assert_eq!(claim_cost, Ok(Decimal::new(406, 8)));
     }
+
+    /// Sums the price at the midpoint of `steps` equal slices of [start, start + amount]
+    fn brute_force_cost(
+        price: impl Fn(Decimal) -> Decimal,
+        start: Decimal,
+        amount: Decimal,
+        steps: u32,
+    ) -> Decimal {
+        let dx = amount / Decimal::from(steps);
+        (0..steps)
+            .map(|i| price(start + dx * (Decimal::from(i) + Decimal::new(5, 1))) * dx)
+            .sum()
+    }
+
+    fn assert_close(actual: Decimal, expected: Decimal, relative_tolerance: Decimal) {
+        let difference = (actual - expected).abs();
+        assert!(
+            difference <= expected.abs() * relative_tolerance,
+            "{} differs from {} by {}",
+            actual,
+            expected,
+            difference
+        );
+    }
+
+    #[test]
+    fn test_calculate_claim_cost_bonding_curve_matches_sum() {
+        let base_price = Decimal::new(1, 2);
+        let curve_slope = Decimal::new(1, 6);
+        let total_claimed = Decimal::from(1_000);
+        let token_amount = Decimal::from(250);
+        // The linear curve charges every whole token at the price of its position
+        let expected: Decimal = (0..250)
+            .map(|i| base_price + curve_slope * (total_claimed + Decimal::from(i)))
+            .sum();
+        let claim_cost =
+            calculate_claim_cost_bonding_curve(token_amount, total_claimed, base_price, curve_slope);
+        assert_eq!(claim_cost, Ok(expected));
+    }
+
+    #[test]
+    fn test_calculate_claim_cost_power_curve_matches_sum() {
+        let base_price = Decimal::new(1, 2);
+        let curve_slope = Decimal::new(1, 9);
+        for exponent in 1..=4u64 {
+            let claim_cost = calculate_claim_cost_power_curve(
+                Decimal::from(50),
+                Decimal::from(100),
+                base_price,
+                curve_slope,
+                exponent,
+            )
+            .unwrap();
+            let expected = brute_force_cost(
+                |x| base_price + curve_slope * x.powu(exponent),
+                Decimal::from(100),
+                Decimal::from(50),
+                10_000,
+            );
+            assert_close(claim_cost, expected, Decimal::new(1, 8));
+        }
+    }
+
+    #[test]
+    fn test_calculate_claim_cost_power_curve_splits() {
+        // Claiming in two parts costs the same as claiming at once
+        let base_price = Decimal::new(1, 2);
+        let curve_slope = Decimal::new(1, 9);
+        let once = calculate_claim_cost_power_curve(
+            Decimal::from(30),
+            Decimal::from(10),
+            base_price,
+            curve_slope,
+            3,
+        )
+        .unwrap();
+        let first = calculate_claim_cost_power_curve(
+            Decimal::from(12),
+            Decimal::from(10),
+            base_price,
+            curve_slope,
+            3,
+        )
+        .unwrap();
+        let second = calculate_claim_cost_power_curve(
+            Decimal::from(18),
+            Decimal::from(22),
+            base_price,
+            curve_slope,
+            3,
+        )
+        .unwrap();
+        assert_eq!(once, first + second);
+    }
+
+    #[test]
+    fn test_calculate_claim_cost_exponential_curve_matches_sum() {
+        let base_price = Decimal::new(1, 2);
+        let growth_rate = Decimal::new(1, 3);
+        let claim_cost = calculate_claim_cost_exponential_curve(
+            Decimal::from(500),
+            Decimal::from(1_000),
+            base_price,
+            growth_rate,
+        )
+        .unwrap();
+        let expected = brute_force_cost(
+            |x| base_price * (growth_rate * x).exp(),
+            Decimal::from(1_000),
+            Decimal::from(500),
+            10_000,
+        );
+        assert_close(claim_cost, expected, Decimal::new(1, 8));
+
+        // Without growth the curve is the flat base price
+        let claim_cost = calculate_claim_cost_exponential_curve(
+            Decimal::from(500),
+            Decimal::from(1_000),
+            base_price,
+            Decimal::zero(),
+        );
+        assert_eq!(claim_cost, Ok(Decimal::from(5)));
+    }
+
+    #[test]
+    fn test_calculate_claim_cost_step_curve_matches_sum() {
+        let base_price = Decimal::new(1, 2);
+        let step_increment = Decimal::new(5, 3);
+        let step_size = Decimal::from(100);
+        // Starts and ends in the middle of a step
+        let claim_cost = calculate_claim_cost_step_curve(
+            Decimal::from(330),
+            Decimal::from(50),
+            base_price,
+            step_increment,
+            step_size,
+        )
+        .unwrap();
+        // Slice boundaries fall on the step edges, so the midpoint sum is exact
+        let expected = brute_force_cost(
+            |x| base_price + step_increment * (x / step_size).floor(),
+            Decimal::from(50),
+            Decimal::from(330),
+            330,
+        );
+        assert_eq!(claim_cost, expected);
+
+        let claim_cost = calculate_claim_cost_step_curve(
+            Decimal::from(1),
+            Decimal::zero(),
+            base_price,
+            step_increment,
+            Decimal::zero(),
+        );
+        assert!(claim_cost.is_err());
+    }
 }
@@ -1,20 +1,32 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{
-    account_info::AccountInfo, keccak::hash, program_error::ProgramError, pubkey::Pubkey,
-    secp256k1_recover::secp256k1_recover,
+use anchor_lang::solana_program::{program_error::ProgramError, sysvar};
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
 };
-use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
-use balance_util::get_balance_hash;
-use libsecp256k1::Signature;
+use balance_util::{get_balance_claim_typed_data, get_ownership_typed_data};
 use rust_decimal::Decimal;
 
+use crate::instructions::secp256k1_signatures::{load_verified_signatures, VerifiedSignature};
 use crate::instructions::calculate_claim_cost::{
-    calculate_claim_cost_bonding_curve, calculate_claim_cost_fixed_price,
+    calculate_claim_cost_bonding_curve, calculate_claim_cost_exponential_curve,
+    calculate_claim_cost_fixed_price, calculate_claim_cost_power_curve,
+    calculate_claim_cost_step_curve,
 };
 use crate::{
-    event::ErrorCode,
-    state::vamp_state::{ClaimState, VampState},
+    constant::BPS_DENOMINATOR,
+    event::{ErrorCode, TokensClaimed},
+    state::{
+        global_config::GlobalConfig,
+        claim_bitmap::ClaimBitmap,
+        epoch_snapshot::EpochSnapshot,
+        vamp_state::{
+            snapshot_epoch_seed, ClaimState, ClaimTracking, CurveKind, VampState, VampStatus,
+        },
+        vesting_state::VestingState,
+    },
 };
+use crate::instructions::release::unlocked_amount;
 
 #[derive(Accounts)]
 #[instruction(eth_address: [u8; 20])]
@@ -29,14 +41,28 @@ pub struct Claim<'info> {
     )]
     pub vamp_state: Account<'info, VampState>,
 
+    #[account(
+        seeds = [b"config"],
+        bump = global_config.bump,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    /// Archive of an epoch closed by a top-up, the claim is made for that epoch when passed
+    /// and for the current one otherwise
+    #[account(
+        constraint = epoch_snapshot.vamp_state == vamp_state.key() @ ErrorCode::InvalidSnapshotEpoch,
+    )]
+    pub epoch_snapshot: Option<Account<'info, EpochSnapshot>>,
+
+    /// Marks the holder's claim when the vamp tracks claims with accounts
     #[account(
         init,
         payer = authority,
-        seeds = [b"claim", vamp_state.key().as_ref(), &eth_address],
+        seeds = [b"claim", vamp_state.key().as_ref(), &eth_address, &snapshot_epoch_seed(claimed_epoch(&vamp_state, epoch_snapshot.as_deref()))],
         bump,
         space = 8 + ClaimState::INIT_SPACE,
     )]
-    pub claim_state: Account<'info, ClaimState>,
+    pub claim_state: Option<Account<'info, ClaimState>>,
 
     #[account(
         mut,
@@ -45,7 +71,7 @@ pub struct Claim<'info> {
         token::mint = mint_account,
         token::authority = vamp_state,
     )]
-    pub vault: Account<'info, TokenAccount>,
+    pub vault: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: This is the SOL vault PDA
     #[account(
@@ -55,202 +81,998 @@ pub struct Claim<'info> {
     )]
     pub sol_vault: UncheckedAccount<'info>,
 
-    #[account(mut)]
-    pub claimer_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint_account,
+    )]
+    pub claimer_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub mint_account: Account<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+    pub mint_account: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+
+    /// CHECK: The instructions sysvar, read to find the signatures verified by the Secp256k1 program
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    /// Required when the vamp has a vesting schedule, holds the claimed tokens still locked
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"vesting", vamp_state.key().as_ref(), &eth_address, &snapshot_epoch_seed(claimed_epoch(&vamp_state, epoch_snapshot.as_deref()))],
+        bump,
+        space = 8 + VestingState::INIT_SPACE,
+    )]
+    pub vesting_state: Option<Account<'info, VestingState>>,
+
+    /// Page holding the holder's bit when the vamp tracks claims with bitmaps
+    #[account(mut)]
+    pub claim_bitmap: Option<AccountLoader<'info, ClaimBitmap>>,
+
+    /// CHECK: Receives the protocol share of the claim payment, required while the protocol fee is set
+    #[account(
+        mut,
+        address = global_config.protocol_treasury @ ErrorCode::InvalidProtocolTreasury,
+    )]
+    pub protocol_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Wallet that referred the claimer, receives the referral share of the claim payment
+    #[account(mut)]
+    pub referrer: Option<UncheckedAccount<'info>>,
 }
 
-fn verify_ethereum_signature(
-    message: &Vec<u8>,
-    signature: [u8; 65],
-    expected_address: &Vec<u8>,
+/// Epoch a claim is made for, the archived one when the claim passes its epoch snapshot
+pub(crate) fn claimed_epoch(vamp_state: &VampState, epoch_snapshot: Option<&EpochSnapshot>) -> u32 {
+    epoch_snapshot.map_or(vamp_state.snapshot_epoch, |epoch_snapshot| epoch_snapshot.snapshot_epoch)
+}
+
+/// An attestation as signed in the vamp's current signer epoch, and as the rotated out keys
+/// signed it in the epoch before
+pub(crate) struct EpochMessages {
+    pub current: Vec<u8>,
+    pub previous: Vec<u8>,
+}
+
+impl EpochMessages {
+    /// Builds the message of both signer epochs, `message` encodes it for an epoch
+    pub(crate) fn new(vamp_state: &VampState, message: impl Fn(u32) -> Vec<u8>) -> Self {
+        Self {
+            current: message(vamp_state.signer_epoch),
+            // Before the first rotation there are no rotated out keys to sign it
+            previous: message(vamp_state.signer_epoch.saturating_sub(1)),
+        }
+    }
+}
+
+/// Checks that the current key, or the rotated out key while its grace period hasn't ended,
+/// signed the message of its epoch
+fn verify_signer_signature(
+    signatures: &[VerifiedSignature],
+    messages: &EpochMessages,
+    current_key: &[u8],
+    previous_key: &[u8],
+    previous_valid_until: Option<i64>,
+    now: i64,
     signature_type: ErrorCode,
 ) -> Result<()> {
-    const PREFIX: &str = "\x19Ethereum Signed Message:\n";
-    let len = message.len();
-    let len_string = len.to_string();
-
-    let mut eth_message = Vec::with_capacity(PREFIX.len() + len_string.len() + len);
-    eth_message.extend_from_slice(PREFIX.as_bytes());
-    eth_message.extend_from_slice(len_string.as_bytes());
-    eth_message.extend_from_slice(message);
-
-    let message_hash = hash(&eth_message).0;
-    {
-        let signature = Signature::parse_standard_slice(&signature[..64]).map_err(|e| {
-            msg!("Failed to parse signature: {:?}", e);
-            ProgramError::InvalidArgument
-        })?;
-
-        if signature.s.is_high() {
-            msg!("signature with high-s value");
-            return Err(ProgramError::InvalidArgument.into());
+    let signed_by = |key: &[u8], message: &[u8]| {
+        signatures
+            .iter()
+            .any(|signature| signature.eth_address.as_slice() == key && signature.message == message)
+    };
+    let previous_valid = matches!(previous_valid_until, Some(valid_until) if now <= valid_until);
+
+    let signed = signed_by(current_key, &messages.current)
+        || (previous_valid && signed_by(previous_key, &messages.previous));
+    if !signed {
+        return Err(signature_type.into());
+    }
+    Ok(())
+}
+
+/// Requires `validator_threshold` distinct members of the validator set to have signed the message.
+/// While its grace period hasn't ended, the rotated out set meeting its own threshold over the
+/// message of the previous epoch is accepted too. The sets are counted apart, members of one
+/// can't make up for missing members of the other.
+fn verify_validator_signatures(
+    signatures: &[VerifiedSignature],
+    messages: &EpochMessages,
+    vamp_state: &VampState,
+    now: i64,
+) -> Result<()> {
+    let previous_valid = matches!(
+        vamp_state.previous_signers_valid_until,
+        Some(valid_until) if now <= valid_until
+    );
+
+    let approved = validator_approvals(signatures, &messages.current, &vamp_state.validator_set)
+        >= vamp_state.validator_threshold as usize
+        || (previous_valid
+            && validator_approvals(signatures, &messages.previous, &vamp_state.previous_validator_set)
+                >= vamp_state.previous_validator_threshold as usize);
+    require!(approved, ErrorCode::ValidatorThresholdNotMet);
+    Ok(())
+}
+
+/// Counts the distinct members of `validator_set` that signed the message
+fn validator_approvals(signatures: &[VerifiedSignature], message: &[u8], validator_set: &[[u8; 20]]) -> usize {
+    let mut approvals: Vec<[u8; 20]> = Vec::with_capacity(signatures.len());
+    for signature in signatures.iter().filter(|signature| signature.message == message) {
+        let validator = signature.eth_address;
+        // Repeated signatures of one validator count once
+        if validator_set.contains(&validator) && !approvals.contains(&validator) {
+            approvals.push(validator);
         }
     }
+    approvals.len()
+}
+
+/// Calculates the SOL cost of `balance` base units on the vamp curve, starting `start` base units into the supply.
+/// Rounded up, so the reserve always covers refunding the claimed supply.
+pub(crate) fn calculate_curve_cost(
+    vamp_state: &VampState,
+    start: u64,
+    balance: u64,
+    decimals: u8,
+) -> Result<u64> {
+    curve_lamports(vamp_state, start, balance, decimals)?
+        .ceil()
+        .try_into()
+        .map_err(|_| ProgramError::ArithmeticOverflow.into())
+}
 
-    // Parse the signature
-    let recid = signature[64];
+/// Calculates the SOL refunded for `balance` base units on the vamp curve, rounded down
+pub(crate) fn calculate_curve_refund(
+    vamp_state: &VampState,
+    start: u64,
+    balance: u64,
+    decimals: u8,
+) -> Result<u64> {
+    curve_lamports(vamp_state, start, balance, decimals)?
+        .floor()
+        .try_into()
+        .map_err(|_| ProgramError::ArithmeticOverflow.into())
+}
 
-    let recovered_pubkey = secp256k1_recover(&message_hash, recid - 27, &signature[..64])
-        .map_err(|_| ProgramError::InvalidArgument)?;
+fn curve_lamports(
+    vamp_state: &VampState,
+    start: u64,
+    balance: u64,
+    decimals: u8,
+) -> Result<Decimal> {
+    // Calculate the SOL cost using the bonding curve on whole-token units
+    let decimals = decimals as u32;
+    let unit = 10u64.pow(decimals);
+    // Balances span the whole u64 range, prices were validated to fit an i64
+    let sol_balance = Decimal::from_i128_with_scale(balance as i128, decimals);
+    let sol_price = Decimal::new(vamp_state.flat_price_per_token as i64, decimals);
+    let curve_cost = if vamp_state.use_bonding_curve {
+        let total_claimed = Decimal::from_i128_with_scale(start as i128, decimals);
+        let base_price = Decimal::new(vamp_state.base_price as i64, decimals);
+        match vamp_state.curve_kind {
+            CurveKind::Linear => calculate_claim_cost_bonding_curve(
+                sol_balance,
+                total_claimed,
+                base_price,
+                Decimal::new(vamp_state.curve_slope as i64, 9))?,
+            CurveKind::Power => calculate_claim_cost_power_curve(
+                sol_balance,
+                total_claimed,
+                base_price,
+                Decimal::new(vamp_state.curve_slope as i64, 9),
+                vamp_state.curve_exponent)?,
+            CurveKind::Exponential => calculate_claim_cost_exponential_curve(
+                sol_balance,
+                total_claimed,
+                base_price,
+                Decimal::new(vamp_state.curve_slope as i64, 9))?,
+            CurveKind::Step => calculate_claim_cost_step_curve(
+                sol_balance,
+                total_claimed,
+                base_price,
+                Decimal::new(vamp_state.curve_slope as i64, decimals),
+                Decimal::new(vamp_state.curve_step_size as i64, decimals))?,
+        }
+    } else {
+        calculate_claim_cost_fixed_price(sol_balance, sol_price)?
+    }
+    .checked_mul(Decimal::new(unit as i64, 0))
+    .ok_or(ProgramError::ArithmeticOverflow)?;
 
-    let public_key_bytes = recovered_pubkey.0;
+    Ok(curve_cost)
+}
 
-    let recovered_address = &hash(&public_key_bytes).0[12..];
+/// Calculates the SOL cost of claiming `balance` base units and enforces the per-token price cap
+pub(crate) fn calculate_claim_cost(vamp_state: &VampState, balance: u64, decimals: u8) -> Result<u64> {
+    let claim_cost = calculate_curve_cost(vamp_state, vamp_state.total_claimed, balance, decimals)?;
+    let unit = 10u64.pow(decimals as u32);
 
-    // Verify the signature
-    if recovered_address != expected_address {
-        return Err(signature_type.into());
+    // The average cost per whole token must not exceed the cap:
+    // claim_cost / (balance / unit) <= max_price
+    if let Some(max_price) = vamp_state.max_price {
+        let cost_scaled = (claim_cost as u128)
+            .checked_mul(unit as u128)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let cap_scaled = (max_price as u128)
+            .checked_mul(balance as u128)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(cost_scaled <= cap_scaled, ErrorCode::PriceTooHigh);
     }
 
-    Ok(())
+    Ok(claim_cost)
 }
 
-pub fn buy_claim_tokens(
-    ctx: Context<Claim>,
+/// Verifies that the holder signed the claim of the `intent_id` snapshot for `destination_owner`,
+/// the wallet owning the destination token account
+pub(crate) fn verify_ownership_signature(
+    signatures: &[VerifiedSignature],
+    vamp_state: &VampState,
+    intent_id: &[u8],
     eth_address: [u8; 20],
     balance: u64,
-    solver_individual_balance_sig: [u8; 65],
-    validator_individual_balance_sig: [u8; 65],
-    ownership_sig: [u8; 65],
+    destination_owner: &Pubkey,
 ) -> Result<()> {
-    let message = get_balance_hash(
-        &eth_address.to_vec(),
+    // The owner signs over the destination wallet, so the entry can't be redirected
+    let ownership_message = get_ownership_typed_data(
+        &eth_address,
         balance,
-        &ctx.accounts.vamp_state.intent_id,
+        intent_id,
+        &destination_owner.to_bytes(),
+        vamp_state.source_chain_id,
+        &crate::ID.to_bytes(),
     )
     .expect("eth message hash error");
 
+    require!(
+        signatures.iter().any(|signature| {
+            signature.eth_address == eth_address && signature.message == ownership_message
+        }),
+        ErrorCode::InvalidOwnerSignature
+    );
+    Ok(())
+}
+
+/// Verifies the EIP-712 BalanceClaim of the holder in the `intent_id` snapshot, signed by the
+/// solver and the validators
+pub(crate) fn verify_balance_claim(
+    signatures: &[VerifiedSignature],
+    vamp_state: &VampState,
+    intent_id: &[u8],
+    eth_address: [u8; 20],
+    balance: u64,
+    now: i64,
+) -> Result<()> {
+    let messages = EpochMessages::new(vamp_state, |signer_epoch| {
+        get_balance_claim_typed_data(
+            &eth_address,
+            balance,
+            intent_id,
+            signer_epoch,
+            vamp_state.source_chain_id,
+            &crate::ID.to_bytes(),
+        )
+        .expect("eth message hash error")
+    });
+
+    verify_attestation(signatures, &messages, vamp_state, now)
+}
+
+/// Requires the solver and `validator_threshold` validators of the vamp to have signed the message
+pub(crate) fn verify_attestation(
+    signatures: &[VerifiedSignature],
+    messages: &EpochMessages,
+    vamp_state: &VampState,
+    now: i64,
+) -> Result<()> {
     // Verify the solver signature
-    verify_ethereum_signature(
-        &message,
-        solver_individual_balance_sig,
-        &ctx.accounts.vamp_state.solver_public_key,
+    verify_signer_signature(
+        signatures,
+        messages,
+        &vamp_state.solver_public_key,
+        &vamp_state.previous_solver_public_key,
+        vamp_state.previous_signers_valid_until,
+        now,
         ErrorCode::InvalidSolverSignature,
     )?;
 
-    // Verify the owner signature
-    verify_ethereum_signature(
-        &message,
-        ownership_sig,
-        &eth_address.to_vec(),
-        ErrorCode::InvalidOwnerSignature,
-    )?;
-
-    // Verify the validator signature
-    verify_ethereum_signature(
-        &message,
-        validator_individual_balance_sig,
-        &ctx.accounts.vamp_state.validator_public_key,
-        ErrorCode::InvalidValidatorSignature,
-    )?;
+    // Verify the validator signatures
+    verify_validator_signatures(signatures, messages, vamp_state, now)
+}
 
+/// Fails unless the vamp accepts claims right now
+pub(crate) fn check_claims_open(
+    global_config: &GlobalConfig,
+    vamp_state: &VampState,
+    epoch_snapshot: Option<&EpochSnapshot>,
+) -> Result<()> {
+    require!(!global_config.emergency_freeze, ErrorCode::ClaimsFrozen);
+    require!(!vamp_state.paused, ErrorCode::VampPaused);
     require!(
-        ctx.accounts.claim_state.is_claimed == false,
-        ErrorCode::TokensAlreadyClaimed
+        vamp_state.status == VampStatus::Active,
+        ErrorCode::VampGraduated
     );
 
-    // Calculate the SOL cost using the bonding curve on whole-token units
-    let decimals = ctx.accounts.mint_account.decimals as u32;
-    let unit = 10u64.pow(decimals);
-    let sol_balance = Decimal::new(balance as i64, decimals);
-    let sol_price = Decimal::new(
-        ctx.accounts.vamp_state.flat_price_per_token as i64,
-        decimals,
+    check_epoch_open(vamp_state, epoch_snapshot, Clock::get()?.unix_timestamp)
+}
+
+/// Fails once the deadline of the claimed epoch passed or its unclaimed tokens were swept.
+/// Archived epochs keep the deadline they had, a top-up only sets the one of the new epoch.
+fn check_epoch_open(
+    vamp_state: &VampState,
+    epoch_snapshot: Option<&EpochSnapshot>,
+    now: i64,
+) -> Result<()> {
+    let claim_deadline = epoch_snapshot.map_or(vamp_state.claim_deadline, |epoch_snapshot| {
+        epoch_snapshot.claim_deadline
+    });
+    if let Some(claim_deadline) = claim_deadline {
+        require!(now <= claim_deadline, ErrorCode::ClaimWindowClosed);
+    }
+
+    let snapshot_epoch = claimed_epoch(vamp_state, epoch_snapshot);
+    require!(
+        vamp_state
+            .swept_epoch
+            .is_none_or(|swept_epoch| snapshot_epoch > swept_epoch),
+        ErrorCode::EpochSwept
     );
-    let claim_cost = if ctx.accounts.vamp_state.use_bonding_curve {
-        calculate_claim_cost_bonding_curve(
-            sol_balance,
-            Decimal::new(ctx.accounts.vamp_state.total_claimed as i64, decimals),
-            Decimal::new(ctx.accounts.vamp_state.base_price as i64, decimals),
-            Decimal::new(ctx.accounts.vamp_state.curve_slope as i64, 9))?
+    Ok(())
+}
+
+/// The SOL charged for a claim of `balance` base units, the curve cost with the fees on top,
+/// bounded in total by the claimer's slippage limit
+pub(crate) fn charged_claim_payment(
+    global_config: &GlobalConfig,
+    vamp_state: &VampState,
+    balance: u64,
+    decimals: u8,
+    referred: bool,
+    max_total_cost: Option<u64>,
+) -> Result<ClaimPayment> {
+    let claim_cost = if vamp_state.paid_claiming_enabled {
+        calculate_claim_cost(vamp_state, balance, decimals)?
     } else {
-        calculate_claim_cost_fixed_price(sol_balance, sol_price)?
+        0
+    };
+    let payment = ClaimPayment::with_fees(global_config, claim_cost, referred)?;
+
+    if let Some(max_total_cost) = max_total_cost {
+        require!(payment.total()? <= max_total_cost, ErrorCode::SlippageExceeded);
     }
-    .checked_mul(Decimal::new(unit as i64, 0))
-    .ok_or_else(|| ProgramError::ArithmeticOverflow)?
-    .try_into()
-    .map_err(|_| ProgramError::ArithmeticOverflow)?;
-
-    // Transfer SOL from claimer to SOL vault using system program
-    let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
-        &ctx.accounts.authority.key(),
-        &ctx.accounts.sol_vault.key(),
-        claim_cost,
-    );
+    Ok(payment)
+}
 
-    anchor_lang::solana_program::program::invoke(
-        &transfer_ix,
-        &[
-            ctx.accounts.authority.to_account_info(),
-            ctx.accounts.sol_vault.to_account_info(),
-        ],
-    )?;
+/// A claim payment divided between the SOL vault and the fee recipients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ClaimPayment {
+    pub vault: u64,
+    pub protocol_fee: u64,
+    pub referral_fee: u64,
+}
+
+impl ClaimPayment {
+    /// Charges the fee shares of the global config on top of `claim_cost`, which goes to the
+    /// vault whole so the reserve keeps the curve value of the claimed supply. A claim without
+    /// a referrer pays no referral fee.
+    pub(crate) fn with_fees(global_config: &GlobalConfig, claim_cost: u64, referred: bool) -> Result<Self> {
+        require!(
+            global_config.protocol_fee_bps as u64 + global_config.referral_fee_bps as u64
+                <= BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeConfig
+        );
+        let share = |bps: u16| -> Result<u64> {
+            let share = (claim_cost as u128)
+                .checked_mul(bps as u128)
+                .ok_or(ErrorCode::ArithmeticOverflow)?
+                / BPS_DENOMINATOR as u128;
+            Ok(share as u64)
+        };
+        let protocol_fee = share(global_config.protocol_fee_bps)?;
+        let referral_fee = if referred {
+            share(global_config.referral_fee_bps)?
+        } else {
+            0
+        };
 
-    // Update bonding curve state
-    let vamp_state = &mut ctx.accounts.vamp_state;
+        Ok(Self {
+            vault: claim_cost,
+            protocol_fee,
+            referral_fee,
+        })
+    }
+
+    /// The SOL the claimer pays, the curve cost and the fees
+    pub(crate) fn total(&self) -> Result<u64> {
+        self.vault
+            .checked_add(self.protocol_fee)
+            .and_then(|total| total.checked_add(self.referral_fee))
+            .ok_or(ErrorCode::ArithmeticOverflow.into())
+    }
+
+    /// Transfers the payment from `payer`, a positive protocol fee needs the protocol treasury
+    pub(crate) fn pay<'info>(
+        &self,
+        payer: AccountInfo<'info>,
+        sol_vault: AccountInfo<'info>,
+        protocol_treasury: Option<AccountInfo<'info>>,
+        referrer: Option<AccountInfo<'info>>,
+        system_program: AccountInfo<'info>,
+    ) -> Result<()> {
+        require!(
+            self.protocol_fee == 0 || protocol_treasury.is_some(),
+            ErrorCode::InvalidProtocolTreasury
+        );
+
+        for (amount, recipient) in [
+            (self.vault, Some(sol_vault)),
+            (self.protocol_fee, protocol_treasury),
+            (self.referral_fee, referrer),
+        ] {
+            if let (true, Some(recipient)) = (amount > 0, recipient) {
+                transfer(
+                    CpiContext::new(
+                        system_program.clone(),
+                        Transfer {
+                            from: payer.clone(),
+                            to: recipient,
+                        },
+                    ),
+                    amount,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds a claim payment to the reserve and the fee counters of the vamp
+pub(crate) fn record_claim_payment(vamp_state: &mut VampState, payment: &ClaimPayment) -> Result<()> {
     vamp_state.reserve_balance = vamp_state
         .reserve_balance
-        .checked_add(claim_cost)
+        .checked_add(payment.vault)
         .ok_or(ErrorCode::ArithmeticOverflow)?;
+    vamp_state.protocol_fees_paid = vamp_state
+        .protocol_fees_paid
+        .checked_add(payment.protocol_fee)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    vamp_state.referral_fees_paid = vamp_state
+        .referral_fees_paid
+        .checked_add(payment.referral_fee)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    Ok(())
+}
 
-    let mint_key = ctx.accounts.mint_account.key();
-    let seeds = &[b"vamp".as_ref(), mint_key.as_ref(), &[vamp_state.bump]];
-    let signer_seeds = &[&seeds[..]];
-
-    // Transfer tokens from vault to claimer
-    anchor_spl::token::transfer(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.vault.to_account_info(),
-                to: ctx.accounts.claimer_token_account.to_account_info(),
-                authority: vamp_state.to_account_info(),
-            },
-            signer_seeds,
-        ),
-        balance,
-    )?;
+impl<'info> Claim<'info> {
+    /// Epoch of the claim, with the intent and snapshot root its balances were attested for.
+    /// Epochs closed by a top-up are read from their archive, so holders keep claiming them.
+    pub(crate) fn claimed_snapshot(&self) -> (u32, Vec<u8>, Option<[u8; 32]>) {
+        match &self.epoch_snapshot {
+            Some(epoch_snapshot) => (
+                epoch_snapshot.snapshot_epoch,
+                epoch_snapshot.intent_id.clone(),
+                epoch_snapshot.merkle_root,
+            ),
+            None => (
+                self.vamp_state.snapshot_epoch,
+                self.vamp_state.intent_id.clone(),
+                self.vamp_state.merkle_root,
+            ),
+        }
+    }
 
-    // Update the total claimed counter (in base units)
-    vamp_state.total_claimed = vamp_state
-        .total_claimed
-        .checked_add(balance)
-        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    /// Verifies that the holder signed the claim for the owner of the destination token account
+    pub(crate) fn verify_ownership(
+        &self,
+        intent_id: &[u8],
+        eth_address: [u8; 20],
+        balance: u64,
+        signatures: &[VerifiedSignature],
+    ) -> Result<()> {
+        verify_ownership_signature(
+            signatures,
+            &self.vamp_state,
+            intent_id,
+            eth_address,
+            balance,
+            &self.claimer_token_account.owner,
+        )
+    }
 
-    ctx.accounts.claim_state.is_claimed = true;
-    Ok(())
+    /// Records the holder as claimed in the epoch. Bitmap tracking needs the holder's Merkle leaf `index`.
+    fn record_claim(&mut self, snapshot_epoch: u32, index: Option<u64>) -> Result<()> {
+        match (
+            self.vamp_state.claim_tracking,
+            self.claim_state.as_mut(),
+            self.claim_bitmap.as_ref(),
+            index,
+        ) {
+            (ClaimTracking::Accounts, Some(claim_state), None, _) => {
+                require!(!claim_state.is_claimed, ErrorCode::TokensAlreadyClaimed);
+                claim_state.is_claimed = true;
+            }
+            (ClaimTracking::Bitmap, None, Some(claim_bitmap), Some(index)) => {
+                let (page, bit) = ClaimBitmap::position(index)?;
+                let mut claim_bitmap = claim_bitmap.load_mut()?;
+                require!(
+                    claim_bitmap.vamp_state == self.vamp_state.key()
+                        && claim_bitmap.snapshot_epoch == snapshot_epoch
+                        && claim_bitmap.page == page,
+                    ErrorCode::InvalidClaimTracking
+                );
+                claim_bitmap.set_claimed(bit)?;
+            }
+            _ => return Err(ErrorCode::InvalidClaimTracking.into()),
+        }
+        Ok(())
+    }
+
+    /// Charges the claim cost and transfers the verified balance from the vault.
+    /// With a vesting schedule only the unlocked part is transferred, the rest is left for `release`.
+    pub(crate) fn settle(
+        &mut self,
+        eth_address: [u8; 20],
+        snapshot_epoch: u32,
+        balance: u64,
+        index: Option<u64>,
+        max_total_cost: Option<u64>,
+        bumps: &ClaimBumps,
+    ) -> Result<()> {
+        check_claims_open(
+            &self.global_config,
+            &self.vamp_state,
+            self.epoch_snapshot.as_deref(),
+        )?;
+
+        self.record_claim(snapshot_epoch, index)?;
+
+        // Claimers can't route the referral share of their own payment back to themselves
+        let referrer = self.referrer.as_ref().map(|referrer| referrer.key());
+        require!(
+            referrer != Some(self.authority.key())
+                && referrer != Some(self.claimer_token_account.owner),
+            ErrorCode::InvalidReferrer
+        );
+
+        // The claimer pays the curve cost to the vault and the fees to their recipients
+        let payment = charged_claim_payment(
+            &self.global_config,
+            &self.vamp_state,
+            balance,
+            self.mint_account.decimals,
+            referrer.is_some(),
+            max_total_cost,
+        )?;
+        payment.pay(
+            self.authority.to_account_info(),
+            self.sol_vault.to_account_info(),
+            self.protocol_treasury.as_ref().map(|account| account.to_account_info()),
+            self.referrer.as_ref().map(|account| account.to_account_info()),
+            self.system_program.to_account_info(),
+        )?;
+
+        // Update bonding curve state
+        let vamp_state = &mut self.vamp_state;
+        record_claim_payment(vamp_state, &payment)?;
+
+        let unlocked = match (vamp_state.vesting, self.vesting_state.as_mut(), bumps.vesting_state) {
+            (None, None, _) => balance,
+            (Some(schedule), Some(vesting_state), Some(vesting_bump)) => {
+                let unlocked = unlocked_amount(&schedule, balance, Clock::get()?.unix_timestamp)?;
+                vesting_state.set_inner(VestingState {
+                    bump: vesting_bump,
+                    vamp_state: vamp_state.key(),
+                    eth_address,
+                    beneficiary: self.claimer_token_account.key(),
+                    total_amount: balance,
+                    released_amount: unlocked,
+                    snapshot_epoch,
+                });
+                vamp_state.vesting_locked = vamp_state
+                    .vesting_locked
+                    .checked_add(balance - unlocked)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                unlocked
+            }
+            _ => return Err(ErrorCode::InvalidVestingAccount.into()),
+        };
+
+        let mint_key = self.mint_account.key();
+        let seeds = &[b"vamp".as_ref(), mint_key.as_ref(), &[vamp_state.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        // Transfer tokens from vault to claimer
+        if unlocked > 0 {
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        mint: self.mint_account.to_account_info(),
+                        to: self.claimer_token_account.to_account_info(),
+                        authority: vamp_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                unlocked,
+                self.mint_account.decimals,
+            )?;
+        }
+
+        // Update the total claimed counter (in base units)
+        vamp_state.total_claimed = vamp_state
+            .total_claimed
+            .checked_add(balance)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(TokensClaimed {
+            mint_account: mint_key,
+            eth_address,
+            claimer: self.authority.key(),
+            destination: self.claimer_token_account.key(),
+            amount: balance,
+            sol_paid: payment.total()?,
+            total_claimed: vamp_state.total_claimed,
+            protocol_fee: payment.protocol_fee,
+            referral_fee: payment.referral_fee,
+            referrer,
+        });
+        Ok(())
+    }
+}
+
+pub fn buy_claim_tokens(
+    ctx: Context<Claim>,
+    eth_address: [u8; 20],
+    balance: u64,
+    max_total_cost: Option<u64>,
+) -> Result<()> {
+    // Vamps created with a snapshot root only accept Merkle proofs
+    let (snapshot_epoch, intent_id, merkle_root) = ctx.accounts.claimed_snapshot();
+    require!(merkle_root.is_none(), ErrorCode::InvalidClaimMode);
+
+    // The signatures were checked by the Secp256k1 program instructions of this transaction
+    let signatures = load_verified_signatures(&ctx.accounts.instructions_sysvar)?;
+    let now = Clock::get()?.unix_timestamp;
+
+    // EIP-712 BalanceClaim signed by the solver and the validators
+    verify_balance_claim(&signatures, &ctx.accounts.vamp_state, &intent_id, eth_address, balance, now)?;
+
+    // Verify the owner signature
+    ctx.accounts.verify_ownership(&intent_id, eth_address, balance, &signatures)?;
+
+    ctx.accounts.settle(eth_address, snapshot_epoch, balance, None, max_total_cost, &ctx.bumps)
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    fn verified(eth_address: [u8; 20], message: &[u8]) -> VerifiedSignature {
+        VerifiedSignature {
+            eth_address,
+            message: message.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_verify_signer_signature_grace_period() {
+        let messages = EpochMessages {
+            current: vec![7; 66],
+            previous: vec![6; 66],
+        };
+        // The signature was issued by the rotated out key, in the previous signer epoch
+        let previous_key = [2; 20];
+        let current_key = [1; 20];
+        let signatures = [verified(previous_key, &messages.previous)];
+        let verify = |valid_until: Option<i64>, now: i64| {
+            verify_signer_signature(
+                &signatures,
+                &messages,
+                &current_key,
+                &previous_key,
+                valid_until,
+                now,
+                ErrorCode::InvalidSolverSignature,
+            )
+        };
+
+        assert_eq!(Ok(()), verify(Some(100), 100));
+        assert_eq!(Err(ErrorCode::InvalidSolverSignature.into()), verify(Some(100), 101));
+        assert_eq!(Err(ErrorCode::InvalidSolverSignature.into()), verify(None, 0));
+
+        // A signature over another message doesn't count
+        let other_message = vec![8; 66];
+        assert_eq!(
+            Err(ErrorCode::InvalidSolverSignature.into()),
+            verify_signer_signature(
+                &[verified(current_key, &other_message)],
+                &messages,
+                &current_key,
+                &previous_key,
+                None,
+                0,
+                ErrorCode::InvalidSolverSignature,
+            )
+        );
+
+        // A key kept through the rotation only passes with its previous epoch signature during
+        // the grace period, the rotation revokes it like the replaced keys
+        let kept_key_signatures = [verified(current_key, &messages.previous)];
+        let verify_kept_key = |now: i64| {
+            verify_signer_signature(
+                &kept_key_signatures,
+                &messages,
+                &current_key,
+                &current_key,
+                Some(100),
+                now,
+                ErrorCode::InvalidSolverSignature,
+            )
+        };
+        assert_eq!(Ok(()), verify_kept_key(100));
+        assert_eq!(Err(ErrorCode::InvalidSolverSignature.into()), verify_kept_key(101));
+    }
+
+    #[test]
+    fn test_verify_validator_signatures_threshold() {
+        let message = vec![7; 66];
+        let messages = EpochMessages {
+            current: message.clone(),
+            previous: message.clone(),
+        };
+        let validator = [9; 20];
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.validator_set = vec![validator, [1; 20]];
+        vamp_state.validator_threshold = 1;
+        let signatures = [verified(validator, &message)];
+        assert_eq!(Ok(()), verify_validator_signatures(&signatures, &messages, &vamp_state, 0));
+
+        // A repeated signature doesn't count towards the threshold twice
+        vamp_state.validator_threshold = 2;
+        assert_eq!(
+            Err(ErrorCode::ValidatorThresholdNotMet.into()),
+            verify_validator_signatures(
+                &[verified(validator, &message), verified(validator, &message)],
+                &messages,
+                &vamp_state,
+                0
+            )
+        );
+        assert_eq!(
+            Ok(()),
+            verify_validator_signatures(
+                &[verified(validator, &message), verified([1; 20], &message)],
+                &messages,
+                &vamp_state,
+                0
+            )
+        );
+
+        // Signers outside the set don't count
+        vamp_state.validator_set = vec![[1; 20], [2; 20]];
+        vamp_state.validator_threshold = 1;
+        assert_eq!(
+            Err(ErrorCode::ValidatorThresholdNotMet.into()),
+            verify_validator_signatures(&signatures, &messages, &vamp_state, 0)
+        );
+
+        // Unless they belong to the rotated out set during its grace period
+        vamp_state.previous_validator_set = vec![validator];
+        vamp_state.previous_validator_threshold = 1;
+        vamp_state.previous_signers_valid_until = Some(100);
+        assert_eq!(Ok(()), verify_validator_signatures(&signatures, &messages, &vamp_state, 100));
+        assert_eq!(
+            Err(ErrorCode::ValidatorThresholdNotMet.into()),
+            verify_validator_signatures(&signatures, &messages, &vamp_state, 101)
+        );
+
+        // One member of each set doesn't make a 2 of 2 approval of either
+        vamp_state.validator_set = vec![[1; 20], [2; 20]];
+        vamp_state.validator_threshold = 2;
+        vamp_state.previous_validator_set = vec![validator, [3; 20]];
+        vamp_state.previous_validator_threshold = 2;
+        let mixed = [verified([1; 20], &message), verified(validator, &message)];
+        assert_eq!(
+            Err(ErrorCode::ValidatorThresholdNotMet.into()),
+            verify_validator_signatures(&mixed, &messages, &vamp_state, 100)
+        );
+        let previous = [verified([3; 20], &message), verified(validator, &message)];
+        assert_eq!(Ok(()), verify_validator_signatures(&previous, &messages, &vamp_state, 100));
+        let current = [verified([2; 20], &message), verified([1; 20], &message)];
+        assert_eq!(Ok(()), verify_validator_signatures(&current, &messages, &vamp_state, 101));
+
+        // The rotated out set counts with the message of the epoch it was rotated out of
+        let messages = EpochMessages {
+            current: message.clone(),
+            previous: vec![6; 66],
+        };
+        assert_eq!(
+            Err(ErrorCode::ValidatorThresholdNotMet.into()),
+            verify_validator_signatures(&previous, &messages, &vamp_state, 100)
+        );
+    }
+
+    const ONE_TOKEN: u64 = 1_000_000_000;
+
+    pub(crate) fn vamp_state_fixture() -> VampState {
+        VampState {
+            bump: 255,
+            mint: Pubkey::default(),
+            solver_public_key: vec![],
+            validator_set: vec![],
+            vamp_identifier: 0,
+            intent_id: vec![],
+            total_claimed: 0,
+            reserve_balance: 0,
+            token_supply: 0,
+            curve_exponent: 2,
+            sol_vault: Pubkey::default(),
+            curve_slope: 0,
+            base_price: 0,
+            max_price: None,
+            use_bonding_curve: false,
+            flat_price_per_token: 10_000_000,
+            paid_claiming_enabled: true,
+            authority: Pubkey::default(),
+            treasury: Pubkey::default(),
+            merkle_root: None,
+            claim_deadline: None,
+            params_locked: false,
+            curve_kind: CurveKind::Linear,
+            curve_step_size: 0,
+            redeem_spread_bps: 0,
+            paused: false,
+            signer_epoch: 0,
+            previous_solver_public_key: vec![],
+            previous_validator_set: vec![],
+            previous_signers_valid_until: None,
+            validator_threshold: 1,
+            source_chain_id: 1,
+            vesting: None,
+            vesting_locked: 0,
+            snapshot_epoch: 0,
+            claim_tracking: ClaimTracking::Accounts,
+            protocol_fees_paid: 0,
+            referral_fees_paid: 0,
+            graduation: None,
+            status: VampStatus::Active,
+            rotation_delay: 0,
+            pending_rotation: None,
+            previous_validator_threshold: 0,
+            swept_epoch: None,
+        }
+    }
+
+    #[test]
+    fn test_calculate_claim_cost_price_cap() {
+        let mut vamp_state = vamp_state_fixture();
+        // 2 tokens at 0.01 SOL each
+        assert_eq!(calculate_claim_cost(&vamp_state, 2_000_000_000, 9), Ok(20_000_000));
+
+        vamp_state.max_price = Some(10_000_000);
+        assert_eq!(calculate_claim_cost(&vamp_state, 2_000_000_000, 9), Ok(20_000_000));
+
+        vamp_state.max_price = Some(9_999_999);
+        assert_eq!(
+            calculate_claim_cost(&vamp_state, 2_000_000_000, 9),
+            Err(ErrorCode::PriceTooHigh.into())
+        );
+    }
+
+    #[test]
+    fn test_calculate_curve_cost_u64_supply() {
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.use_bonding_curve = true;
+        vamp_state.curve_kind = CurveKind::Power;
+        vamp_state.curve_exponent = 2;
+        vamp_state.curve_slope = 1;
+        vamp_state.base_price = 0;
+
+        // 10^10 tokens already claimed, past i64::MAX base units and 10^30 once cubed.
+        // A tenth of a token there costs 10^10 SOL: ((10^10 + 0.1)^3 - 10^30) / 3 * 10^-9
+        let start = 10_000_000_000 * 1_000_000_000;
+        assert!(start > i64::MAX as u64);
+        assert_eq!(
+            calculate_curve_cost(&vamp_state, start, 100_000_000, 9),
+            Ok(10_000_000_000_100_000_000)
+        );
+
+        // The largest supply at the cheapest slope, the cost itself leaves the u64 range
+        vamp_state.curve_exponent = 4;
+        assert_eq!(
+            calculate_curve_cost(&vamp_state, u64::MAX - ONE_TOKEN, ONE_TOKEN, 9),
+            Err(ProgramError::ArithmeticOverflow.into())
+        );
+        // Cheap enough at the start of the curve
+        assert_eq!(calculate_curve_cost(&vamp_state, 0, 10 * ONE_TOKEN, 9), Ok(20_000));
+    }
+
+    #[test]
+    fn test_claim_payment_with_fees() {
+        let mut global_config = GlobalConfig {
+            bump: 255,
+            admin: Pubkey::default(),
+            emergency_freeze: false,
+            protocol_treasury: Pubkey::default(),
+            protocol_fee_bps: 0,
+            referral_fee_bps: 0,
+        };
+        let with_fees = |global_config: &GlobalConfig, referred: bool| {
+            ClaimPayment::with_fees(global_config, 1_000_001, referred)
+        };
+        assert_eq!(
+            with_fees(&global_config, true),
+            Ok(ClaimPayment { vault: 1_000_001, protocol_fee: 0, referral_fee: 0 })
+        );
+
+        // Fees round down and come on top, the vault gets the whole curve cost
+        global_config.protocol_fee_bps = 250;
+        global_config.referral_fee_bps = 100;
+        let payment = with_fees(&global_config, true).unwrap();
+        assert_eq!(
+            payment,
+            ClaimPayment { vault: 1_000_001, protocol_fee: 25_000, referral_fee: 10_000 }
+        );
+        assert_eq!(payment.total(), Ok(1_035_001));
+        assert_eq!(
+            with_fees(&global_config, false),
+            Ok(ClaimPayment { vault: 1_000_001, protocol_fee: 25_000, referral_fee: 0 })
+        );
+
+        global_config.protocol_fee_bps = 9_000;
+        global_config.referral_fee_bps = 2_000;
+        assert_eq!(with_fees(&global_config, true), Err(ErrorCode::InvalidFeeConfig.into()));
+    }
+
     #[test]
-    fn test_verify_ethereum_signature() {
-        let message = vec![
-            83, 31, 60, 150, 106, 85, 124, 28, 140, 125, 105, 187, 151, 211, 104, 177, 20, 147, 53,
-            87, 63, 176, 26, 228, 4, 49, 136, 174, 166, 102, 114, 231,
-        ];
-        let signature = [
-            251, 190, 51, 170, 61, 104, 94, 173, 134, 86, 195, 233, 114, 39, 131, 218, 205, 35,
-            184, 80, 233, 53, 220, 244, 27, 165, 216, 133, 6, 251, 209, 206, 62, 148, 200, 51, 176,
-            66, 113, 38, 158, 246, 60, 234, 141, 183, 42, 176, 53, 65, 143, 195, 84, 99, 162, 156,
-            57, 192, 188, 82, 3, 23, 55, 169, 27,
-        ];
-        let expected_address = vec![
-            249, 139, 130, 139, 56, 155, 239, 78, 187, 181, 145, 28, 161, 126, 79, 121, 137, 201,
-            6, 141,
-        ];
-        let res = verify_ethereum_signature(
-            &message,
-            signature,
-            &expected_address,
-            ErrorCode::InvalidSolverSignature,
+    fn test_check_epoch_open_archived_deadline() {
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.snapshot_epoch = 2;
+        vamp_state.claim_deadline = Some(2_000);
+        let epoch_snapshot = EpochSnapshot {
+            bump: 255,
+            vamp_state: Pubkey::default(),
+            snapshot_epoch: 1,
+            intent_id: vec![1; 32],
+            merkle_root: None,
+            claim_deadline: Some(1_000),
+        };
+
+        // A top-up extending the deadline doesn't reopen the epoch it archived
+        assert_eq!(check_epoch_open(&vamp_state, None, 1_500), Ok(()));
+        assert_eq!(
+            check_epoch_open(&vamp_state, Some(&epoch_snapshot), 1_500),
+            Err(ErrorCode::ClaimWindowClosed.into())
+        );
+        assert_eq!(check_epoch_open(&vamp_state, Some(&epoch_snapshot), 1_000), Ok(()));
+    }
+
+    #[test]
+    fn test_check_epoch_open_swept() {
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.snapshot_epoch = 2;
+        let mut epoch_snapshot = EpochSnapshot {
+            bump: 255,
+            vamp_state: Pubkey::default(),
+            snapshot_epoch: 0,
+            intent_id: vec![1; 32],
+            merkle_root: None,
+            claim_deadline: None,
+        };
+
+        // Epochs without a deadline are open until a sweep took their tokens
+        assert_eq!(check_epoch_open(&vamp_state, Some(&epoch_snapshot), 1_000), Ok(()));
+        vamp_state.swept_epoch = Some(1);
+        assert_eq!(
+            check_epoch_open(&vamp_state, Some(&epoch_snapshot), 1_000),
+            Err(ErrorCode::EpochSwept.into())
+        );
+        epoch_snapshot.snapshot_epoch = 1;
+        assert_eq!(
+            check_epoch_open(&vamp_state, Some(&epoch_snapshot), 1_000),
+            Err(ErrorCode::EpochSwept.into())
         );
-        assert_eq!(Ok(()), res);
+        // The epoch opened by the top-up after the sweep
+        assert_eq!(check_epoch_open(&vamp_state, None, 1_000), Ok(()));
     }
 }
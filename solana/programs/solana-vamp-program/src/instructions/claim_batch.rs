@@ -0,0 +1,384 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program::{
+    allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer,
+};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::instructions::claim::{
+    charged_claim_payment, check_claims_open, record_claim_payment, verify_balance_claim,
+    verify_ownership_signature,
+};
+use crate::instructions::release::unlocked_amount;
+use crate::instructions::secp256k1_signatures::{load_verified_signatures, VerifiedSignature};
+use crate::{
+    constant::ANCHOR_DISCRIMINATOR,
+    event::{ErrorCode, TokensClaimed},
+    state::{
+        global_config::GlobalConfig,
+        vamp_state::{ClaimState, ClaimTracking, VampState},
+        vesting_state::VestingState,
+    },
+};
+
+/// A holder's claim settled by `claim_batch`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchClaim {
+    pub eth_address: [u8; 20],
+    pub balance: u64,
+    pub max_total_cost: Option<u64>,
+    /// Canonical bump of the claim state PDA
+    pub claim_state_bump: u8,
+    /// Bump of the vesting PDA, required when the vamp has a vesting schedule
+    pub vesting_state_bump: Option<u8>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimBatch<'info> {
+    /// The relayer, pays the claim costs and the holder accounts
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = global_config.bump,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", mint_account.key().as_ref()],
+        bump,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the SOL vault PDA
+    #[account(
+        mut,
+        seeds = [b"sol_vault", mint_account.key().as_ref()],
+        bump,
+    )]
+    pub sol_vault: UncheckedAccount<'info>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: The instructions sysvar, read to find the signatures verified by the Secp256k1 program
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    /// CHECK: Receives the protocol share of the claim payments, required while the protocol fee is set
+    #[account(
+        mut,
+        address = global_config.protocol_treasury @ ErrorCode::InvalidProtocolTreasury,
+    )]
+    pub protocol_treasury: Option<UncheckedAccount<'info>>,
+}
+
+impl<'info> ClaimBatch<'info> {
+    /// Settles the signature claims of several holders in one instruction. Each claim takes its
+    /// accounts from the remaining accounts, in order: the claim state PDA, the destination token
+    /// account and, when the vamp has a vesting schedule, the vesting PDA.
+    pub fn claim_batch(
+        &mut self,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        claims: &[BatchClaim],
+    ) -> Result<()> {
+        // Holders of snapshot vamps claim with their own Merkle proof
+        require!(
+            self.vamp_state.merkle_root.is_none(),
+            ErrorCode::InvalidClaimMode
+        );
+        require!(
+            self.vamp_state.claim_tracking == ClaimTracking::Accounts,
+            ErrorCode::InvalidClaimTracking
+        );
+        check_claims_open(&self.global_config, &self.vamp_state, None)?;
+
+        let accounts_per_claim = if self.vamp_state.vesting.is_some() { 3 } else { 2 };
+        require!(
+            !claims.is_empty() && remaining_accounts.len() == claims.len() * accounts_per_claim,
+            ErrorCode::InvalidBatchAccounts
+        );
+
+        // The Secp256k1 instructions of the transaction carry the signatures of every claim
+        let signatures = load_verified_signatures(&self.instructions_sysvar)?;
+        let now = Clock::get()?.unix_timestamp;
+
+        for (claim, accounts) in claims
+            .iter()
+            .zip(remaining_accounts.chunks_exact(accounts_per_claim))
+        {
+            self.settle_batch_claim(claim, accounts, &signatures, now)?;
+        }
+        Ok(())
+    }
+
+    fn settle_batch_claim(
+        &mut self,
+        claim: &BatchClaim,
+        accounts: &'info [AccountInfo<'info>],
+        signatures: &[VerifiedSignature],
+        now: i64,
+    ) -> Result<()> {
+        let claimer_token_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+        require_keys_eq!(
+            claimer_token_account.mint,
+            self.mint_account.key(),
+            ErrorCode::InvalidBatchAccounts
+        );
+
+        // Batches settle claims of the current epoch, archived epochs are claimed one at a time
+        let intent_id = &self.vamp_state.intent_id;
+        verify_balance_claim(signatures, &self.vamp_state, intent_id, claim.eth_address, claim.balance, now)?;
+        verify_ownership_signature(
+            signatures,
+            &self.vamp_state,
+            intent_id,
+            claim.eth_address,
+            claim.balance,
+            &claimer_token_account.owner,
+        )?;
+
+        // The claim state account exists once the holder claimed in this epoch
+        let vamp_key = self.vamp_state.key();
+        let epoch_seed = self.vamp_state.snapshot_epoch_seed();
+        let claim_state_seeds: &[&[u8]] =
+            &[b"claim", vamp_key.as_ref(), &claim.eth_address, &epoch_seed];
+        // Another bump would give the holder a second claim state, and a second claim
+        require!(
+            is_canonical_bump(claim_state_seeds, claim.claim_state_bump),
+            ErrorCode::InvalidBatchAccounts
+        );
+        self.create_holder_account(
+            &accounts[0],
+            claim_state_seeds,
+            claim.claim_state_bump,
+            ANCHOR_DISCRIMINATOR + ClaimState::INIT_SPACE,
+        )?;
+        write_account(&accounts[0], &ClaimState { is_claimed: true })?;
+
+        // Batched claims are relayed, so they carry no referrer
+        let payment = charged_claim_payment(
+            &self.global_config,
+            &self.vamp_state,
+            claim.balance,
+            self.mint_account.decimals,
+            false,
+            claim.max_total_cost,
+        )?;
+        payment.pay(
+            self.authority.to_account_info(),
+            self.sol_vault.to_account_info(),
+            self.protocol_treasury.as_ref().map(|account| account.to_account_info()),
+            None,
+            self.system_program.to_account_info(),
+        )?;
+        record_claim_payment(&mut self.vamp_state, &payment)?;
+
+        let unlocked = match self.vamp_state.vesting {
+            None => claim.balance,
+            Some(schedule) => {
+                let unlocked = unlocked_amount(&schedule, claim.balance, now)?;
+                // Releases derive the vesting PDA from the stored bump, any valid one will do
+                let bump = claim.vesting_state_bump.ok_or(ErrorCode::InvalidBatchAccounts)?;
+                self.create_holder_account(
+                    &accounts[2],
+                    &[b"vesting", vamp_key.as_ref(), &claim.eth_address, &epoch_seed],
+                    bump,
+                    ANCHOR_DISCRIMINATOR + VestingState::INIT_SPACE,
+                )?;
+                write_account(
+                    &accounts[2],
+                    &VestingState {
+                        bump,
+                        vamp_state: vamp_key,
+                        eth_address: claim.eth_address,
+                        beneficiary: claimer_token_account.key(),
+                        total_amount: claim.balance,
+                        released_amount: unlocked,
+                        snapshot_epoch: self.vamp_state.snapshot_epoch,
+                    },
+                )?;
+                self.vamp_state.vesting_locked = self
+                    .vamp_state
+                    .vesting_locked
+                    .checked_add(claim.balance - unlocked)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                unlocked
+            }
+        };
+
+        let mint_key = self.mint_account.key();
+        let seeds = &[b"vamp".as_ref(), mint_key.as_ref(), &[self.vamp_state.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        if unlocked > 0 {
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        mint: self.mint_account.to_account_info(),
+                        to: claimer_token_account.to_account_info(),
+                        authority: self.vamp_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                unlocked,
+                self.mint_account.decimals,
+            )?;
+        }
+
+        let vamp_state = &mut self.vamp_state;
+        vamp_state.total_claimed = vamp_state
+            .total_claimed
+            .checked_add(claim.balance)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(TokensClaimed {
+            mint_account: mint_key,
+            eth_address: claim.eth_address,
+            claimer: self.authority.key(),
+            destination: claimer_token_account.key(),
+            amount: claim.balance,
+            sol_paid: payment.total()?,
+            total_claimed: vamp_state.total_claimed,
+            protocol_fee: payment.protocol_fee,
+            referral_fee: payment.referral_fee,
+            referrer: None,
+        });
+        Ok(())
+    }
+
+    /// Creates the program owned account at the PDA of `seeds` and `bump`, paid by the relayer
+    /// as `init` would. The account already being in use means the holder claimed.
+    fn create_holder_account(
+        &self,
+        account: &AccountInfo<'info>,
+        seeds: &[&[u8]],
+        bump: u8,
+        space: usize,
+    ) -> Result<()> {
+        let bump_seed = [bump];
+        let mut signer_seeds = seeds.to_vec();
+        signer_seeds.push(&bump_seed);
+        let address = Pubkey::create_program_address(&signer_seeds, &crate::ID)
+            .map_err(|_| ErrorCode::InvalidBatchAccounts)?;
+        require_keys_eq!(account.key(), address, ErrorCode::InvalidBatchAccounts);
+        require!(
+            account.owner == &System::id() && account.data_is_empty(),
+            ErrorCode::TokensAlreadyClaimed
+        );
+
+        let signer_seeds = &[&signer_seeds[..]];
+        let rent = Rent::get()?.minimum_balance(space);
+        let system_program = self.system_program.to_account_info();
+
+        if account.lamports() == 0 {
+            create_account(
+                CpiContext::new_with_signer(
+                    system_program,
+                    CreateAccount {
+                        from: self.authority.to_account_info(),
+                        to: account.clone(),
+                    },
+                    signer_seeds,
+                ),
+                rent,
+                space as u64,
+                &crate::ID,
+            )?;
+        } else {
+            // Someone sent lamports to the address beforehand, top it up to rent exemption
+            let shortfall = rent.saturating_sub(account.lamports());
+            if shortfall > 0 {
+                transfer(
+                    CpiContext::new(
+                        system_program.clone(),
+                        Transfer {
+                            from: self.authority.to_account_info(),
+                            to: account.clone(),
+                        },
+                    ),
+                    shortfall,
+                )?;
+            }
+            allocate(
+                CpiContext::new_with_signer(
+                    system_program.clone(),
+                    Allocate {
+                        account_to_allocate: account.clone(),
+                    },
+                    signer_seeds,
+                ),
+                space as u64,
+            )?;
+            assign(
+                CpiContext::new_with_signer(
+                    system_program,
+                    Assign {
+                        account_to_assign: account.clone(),
+                    },
+                    signer_seeds,
+                ),
+                &crate::ID,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether no bump above `bump` gives a program address for `seeds`, which makes it the one
+/// `find_program_address` returns once the address is checked
+fn is_canonical_bump(seeds: &[&[u8]], bump: u8) -> bool {
+    (u16::from(bump) + 1..=u16::from(u8::MAX)).all(|higher| {
+        let higher = [higher as u8];
+        let mut seeds = seeds.to_vec();
+        seeds.push(&higher);
+        Pubkey::create_program_address(&seeds, &crate::ID).is_err()
+    })
+}
+
+/// Writes an account created by `create_holder_account`, discriminator included
+fn write_account<T: AccountSerialize>(account: &AccountInfo, value: &T) -> Result<()> {
+    let mut data = account.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data;
+    value.try_serialize(&mut writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_canonical_bump() {
+        let seeds: &[&[u8]] = &[b"claim", &[7; 32], &[9; 20]];
+        let (_, canonical) = Pubkey::find_program_address(seeds, &crate::ID);
+        assert!(is_canonical_bump(seeds, canonical));
+
+        // A lower bump can give another valid address for the same seeds
+        let lower = (0..canonical)
+            .rev()
+            .find(|&bump| {
+                let bump = [bump];
+                let mut seeds = seeds.to_vec();
+                seeds.push(&bump);
+                Pubkey::create_program_address(&seeds, &crate::ID).is_ok()
+            })
+            .unwrap();
+        assert!(!is_canonical_bump(seeds, lower));
+    }
+}
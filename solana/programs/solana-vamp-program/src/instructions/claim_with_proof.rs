@@ -0,0 +1,116 @@
+use anchor_lang::prelude::*;
+use balance_util::get_snapshot_root_typed_data;
+use merkle_tree::{verify_merkle_proof, Leaf};
+
+use crate::{
+    event::ErrorCode,
+    instructions::{
+        claim::{verify_attestation, Claim, EpochMessages},
+        secp256k1_signatures::load_verified_signatures,
+    },
+    state::vamp_state::VampState,
+};
+
+pub fn claim_tokens_with_proof(
+    ctx: Context<Claim>,
+    eth_address: [u8; 20],
+    balance: u64,
+    index: u64,
+    proof: Vec<[u8; 32]>,
+    max_total_cost: Option<u64>,
+) -> Result<()> {
+    let (snapshot_epoch, intent_id, merkle_root) = ctx.accounts.claimed_snapshot();
+    let merkle_root = merkle_root.ok_or(ErrorCode::InvalidClaimMode)?;
+
+    // Replaces the per-holder solver and validator signatures with inclusion in the snapshot
+    require!(
+        verify_snapshot_proof(
+            merkle_root,
+            eth_address,
+            balance,
+            ctx.accounts.mint_account.decimals,
+            index,
+            &proof,
+        ),
+        ErrorCode::InvalidMerkleProof
+    );
+
+    // Verify the owner signature, checked by the Secp256k1 program instruction of this transaction
+    let signatures = load_verified_signatures(&ctx.accounts.instructions_sysvar)?;
+    ctx.accounts.verify_ownership(&intent_id, eth_address, balance, &signatures)?;
+
+    ctx.accounts.settle(eth_address, snapshot_epoch, balance, Some(index), max_total_cost, &ctx.bumps)
+}
+
+/// Requires the solver and the validators to have signed the snapshot root of the vamp's
+/// current intent. Vamps claimed with signatures carry no root and need no attestation.
+pub(crate) fn verify_snapshot_root(instructions_sysvar: &AccountInfo, vamp_state: &VampState) -> Result<()> {
+    let Some(merkle_root) = vamp_state.merkle_root else {
+        return Ok(());
+    };
+    let messages = EpochMessages::new(vamp_state, |signer_epoch| {
+        get_snapshot_root_typed_data(
+            &merkle_root,
+            &vamp_state.intent_id,
+            signer_epoch,
+            vamp_state.source_chain_id,
+            &crate::ID.to_bytes(),
+        )
+    });
+    let signatures = load_verified_signatures(instructions_sysvar)?;
+    verify_attestation(&signatures, &messages, vamp_state, Clock::get()?.unix_timestamp)
+}
+
+fn verify_snapshot_proof(
+    merkle_root: [u8; 32],
+    eth_address: [u8; 20],
+    balance: u64,
+    decimals: u8,
+    index: u64,
+    proof: &[[u8; 32]],
+) -> bool {
+    let leaf = Leaf {
+        account: eth_address,
+        amount: balance,
+        decimals,
+    };
+    // Index bits above the proof depth aren't read by the verification, another index
+    // must not pass for the same leaf
+    if proof.len() < u64::BITS as usize && index >> proof.len() != 0 {
+        return false;
+    }
+    verify_merkle_proof(leaf.hash(), proof, merkle_root, index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_verify_snapshot_proof() {
+        let leaves: Vec<Leaf> = (1..=5u8)
+            .map(|i| Leaf {
+                account: [i; 20],
+                amount: i as u64 * 1_000,
+                decimals: 9,
+            })
+            .collect();
+        let tree = MerkleTree::new(&leaves);
+        let proof = tree.generate_proof(3);
+
+        assert!(verify_snapshot_proof(tree.root, [4; 20], 4_000, 9, 3, &proof));
+        // Inflated balance
+        assert!(!verify_snapshot_proof(tree.root, [4; 20], 5_000, 9, 3, &proof));
+        // Someone else's entry
+        assert!(!verify_snapshot_proof(tree.root, [5; 20], 4_000, 9, 3, &proof));
+        // Wrong position in the tree
+        assert!(!verify_snapshot_proof(tree.root, [4; 20], 4_000, 9, 2, &proof));
+        // Mint decimals differ from the snapshot
+        assert!(!verify_snapshot_proof(tree.root, [4; 20], 4_000, 6, 3, &proof));
+        // Same position with a bit above the tree depth set
+        assert_eq!(proof.len(), 3);
+        assert!(!verify_snapshot_proof(tree.root, [4; 20], 4_000, 9, 3 + 8, &proof));
+        assert!(!verify_snapshot_proof(tree.root, [4; 20], 4_000, 9, 3 | 1 << 63, &proof));
+    }
+}
@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    constant::ANCHOR_DISCRIMINATOR,
+    event::{ClaimBitmapCreated, ErrorCode},
+    state::{
+        claim_bitmap::{ClaimBitmap, CLAIM_BITMAP_BITS},
+        vamp_state::{ClaimTracking, VampState},
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(page: u32)]
+pub struct CreateClaimBitmap<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"claim_bitmap", vamp_state.key().as_ref(), &page.to_le_bytes(), &vamp_state.snapshot_epoch_seed()],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + std::mem::size_of::<ClaimBitmap>(),
+    )]
+    pub claim_bitmap: AccountLoader<'info, ClaimBitmap>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateClaimBitmap<'info> {
+    /// Creates a bitmap page of the current snapshot epoch for `holders` Merkle leaves.
+    /// The authority knows the leaf count of the snapshot it committed, so it bounds the
+    /// claimable indexes and the padding leaves of the tree can't be claimed again.
+    pub fn create_claim_bitmap(
+        &mut self,
+        page: u32,
+        holders: u32,
+        bumps: &CreateClaimBitmapBumps,
+    ) -> Result<()> {
+        require!(
+            self.vamp_state.claim_tracking == ClaimTracking::Bitmap,
+            ErrorCode::InvalidClaimTracking
+        );
+        require!(
+            holders > 0 && holders <= CLAIM_BITMAP_BITS,
+            ErrorCode::InvalidClaimIndex
+        );
+
+        let mut claim_bitmap = self.claim_bitmap.load_init()?;
+        claim_bitmap.vamp_state = self.vamp_state.key();
+        claim_bitmap.snapshot_epoch = self.vamp_state.snapshot_epoch;
+        claim_bitmap.page = page;
+        claim_bitmap.holders = holders;
+        claim_bitmap.bump = bumps.claim_bitmap;
+
+        emit!(ClaimBitmapCreated {
+            mint_account: self.mint_account.key(),
+            claim_bitmap: self.claim_bitmap.key(),
+            snapshot_epoch: claim_bitmap.snapshot_epoch,
+            page,
+            holders,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constant::{ANCHOR_DISCRIMINATOR, BPS_DENOMINATOR},
+    event::{EmergencyFreezeUpdated, ErrorCode, ProtocolFeesUpdated},
+    program::SolanaVampProgram,
+    state::global_config::GlobalConfig,
+};
+
+#[derive(Accounts)]
+pub struct InitializeGlobalConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"config"],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + GlobalConfig::INIT_SPACE
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    // Only the upgrade authority can create the config, so it can't be front-run after deploy
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, SolanaVampProgram>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeGlobalConfig<'info> {
+    pub fn initialize_global_config(&mut self, bumps: &InitializeGlobalConfigBumps) -> Result<()> {
+        self.global_config.set_inner(GlobalConfig {
+            bump: bumps.global_config,
+            admin: self.admin.key(),
+            emergency_freeze: false,
+            protocol_treasury: self.admin.key(),
+            protocol_fee_bps: 0,
+            referral_fee_bps: 0,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetEmergencyFreeze<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = global_config.bump,
+        has_one = admin @ ErrorCode::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+impl<'info> SetEmergencyFreeze<'info> {
+    /// Blocks or re-enables claims across every vamp at once
+    pub fn set_emergency_freeze(&mut self, emergency_freeze: bool) -> Result<()> {
+        self.global_config.emergency_freeze = emergency_freeze;
+
+        emit!(EmergencyFreezeUpdated {
+            admin: self.admin.key(),
+            emergency_freeze,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolFees<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = global_config.bump,
+        has_one = admin @ ErrorCode::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+impl<'info> SetProtocolFees<'info> {
+    /// Sets the fees claimers pay the protocol treasury and referrers, on top of the curve cost
+    /// that goes to the vamp reserve
+    pub fn set_protocol_fees(
+        &mut self,
+        protocol_treasury: Pubkey,
+        protocol_fee_bps: u16,
+        referral_fee_bps: u16,
+    ) -> Result<()> {
+        require!(
+            protocol_fee_bps as u64 + referral_fee_bps as u64 <= BPS_DENOMINATOR,
+            ErrorCode::InvalidFeeConfig
+        );
+
+        let global_config = &mut self.global_config;
+        global_config.protocol_treasury = protocol_treasury;
+        global_config.protocol_fee_bps = protocol_fee_bps;
+        global_config.referral_fee_bps = referral_fee_bps;
+
+        emit!(ProtocolFeesUpdated {
+            admin: self.admin.key(),
+            protocol_treasury,
+            protocol_fee_bps,
+            referral_fee_bps,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    mint_to, transfer_checked, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    event::{ErrorCode, GraduationLiquidityWithdrawn, GraduationUpdated, VampGraduated},
+    state::vamp_state::{GraduationConfig, VampState, VampStatus},
+};
+
+#[derive(Accounts)]
+pub struct SetGraduation<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> SetGraduation<'info> {
+    /// Sets the thresholds at which the vamp can graduate, `None` disables graduation.
+    /// Holders rely on them like on the pricing, so they are locked along with it.
+    pub fn set_graduation(&mut self, graduation: Option<GraduationConfig>) -> Result<()> {
+        let vamp_state = &mut self.vamp_state;
+        require!(
+            vamp_state.status == VampStatus::Active,
+            ErrorCode::VampGraduated
+        );
+        require!(
+            !(vamp_state.params_locked && vamp_state.total_claimed > 0),
+            ErrorCode::VampingParamsLocked
+        );
+        if let Some(graduation) = &graduation {
+            validate_graduation(graduation)?;
+        }
+        vamp_state.graduation = graduation;
+
+        emit!(GraduationUpdated {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            graduation,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Graduate<'info> {
+    /// Anyone can graduate a vamp that reached its thresholds, the payer funds the LP vault
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint", vamp_state.authority.as_ref(), &vamp_state.vamp_identifier.to_le_bytes()],
+        bump,
+    )]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+        token::token_program = token_program,
+        seeds = [b"lp_vault", mint_account.key().as_ref()],
+        bump,
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Graduate<'info> {
+    /// Ends claiming and mints the LP allocation into the LP vault. The allocation and the
+    /// reserve SOL stay in their PDAs until `withdraw_graduation_liquidity` hands them over.
+    pub fn graduate(&mut self, bumps: &GraduateBumps) -> Result<()> {
+        require!(
+            self.vamp_state.status == VampStatus::Active,
+            ErrorCode::VampGraduated
+        );
+        let graduation = self
+            .vamp_state
+            .graduation
+            .ok_or(ErrorCode::GraduationNotReached)?;
+        require!(
+            graduation.is_reached(self.vamp_state.reserve_balance, self.vamp_state.total_claimed),
+            ErrorCode::GraduationNotReached
+        );
+
+        if graduation.lp_allocation > 0 {
+            let vamp_identifier = self.vamp_state.vamp_identifier.to_le_bytes();
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                b"mint",
+                self.vamp_state.authority.as_ref(),
+                &vamp_identifier,
+                &[bumps.mint_account],
+            ]];
+            mint_to(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    MintTo {
+                        mint: self.mint_account.to_account_info(),
+                        to: self.lp_vault.to_account_info(),
+                        authority: self.mint_account.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                graduation.lp_allocation,
+            )?;
+        }
+
+        let vamp_state = &mut self.vamp_state;
+        vamp_state.token_supply = vamp_state
+            .token_supply
+            .checked_add(graduation.lp_allocation)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        vamp_state.status = VampStatus::Graduated;
+
+        emit!(VampGraduated {
+            mint_account: self.mint_account.key(),
+            lp_vault: self.lp_vault.key(),
+            lp_allocation: graduation.lp_allocation,
+            reserve_balance: vamp_state.reserve_balance,
+            total_claimed: vamp_state.total_claimed,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct WithdrawGraduationLiquidity<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        constraint = authority.key() == vamp_state.authority
+            || authority.key() == vamp_state.treasury @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    /// CHECK: This is the SOL vault PDA
+    #[account(
+        mut,
+        seeds = [b"sol_vault", mint_account.key().as_ref()],
+        bump,
+    )]
+    pub sol_vault: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"lp_vault", mint_account.key().as_ref()],
+        bump,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+    )]
+    pub lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Only the treasury configured in the vamp state can receive the liquidity
+    #[account(
+        mut,
+        address = vamp_state.treasury @ ErrorCode::InvalidTreasury,
+    )]
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint_account,
+        token::authority = treasury,
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> WithdrawGraduationLiquidity<'info> {
+    /// Hands the reserve SOL and the LP allocation of a graduated vamp to its treasury, which
+    /// seeds the liquidity pool with them
+    pub fn withdraw_graduation_liquidity(&mut self) -> Result<()> {
+        let (sol_amount, token_amount) =
+            graduation_liquidity(&self.vamp_state, self.lp_vault.amount)?;
+
+        // The SOL vault is owned by this program, so lamports are moved directly
+        let sol_vault = self.sol_vault.to_account_info();
+        let treasury = self.treasury.to_account_info();
+        let vault_lamports = sol_vault
+            .lamports()
+            .checked_sub(sol_amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let treasury_lamports = treasury
+            .lamports()
+            .checked_add(sol_amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        **sol_vault.try_borrow_mut_lamports()? = vault_lamports;
+        **treasury.try_borrow_mut_lamports()? = treasury_lamports;
+        self.vamp_state.reserve_balance = 0;
+
+        if token_amount > 0 {
+            let mint_key = self.mint_account.key();
+            let signer_seeds: &[&[&[u8]]] =
+                &[&[b"vamp", mint_key.as_ref(), &[self.vamp_state.bump]]];
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.lp_vault.to_account_info(),
+                        mint: self.mint_account.to_account_info(),
+                        to: self.treasury_token_account.to_account_info(),
+                        authority: self.vamp_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                token_amount,
+                self.mint_account.decimals,
+            )?;
+        }
+
+        emit!(GraduationLiquidityWithdrawn {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            treasury: self.treasury.key(),
+            sol_amount,
+            token_amount,
+        });
+        Ok(())
+    }
+}
+
+/// The reserve SOL and LP vault tokens a vamp hands over for its liquidity pool. Only a
+/// graduated vamp has them, before that the reserve backs redeems.
+fn graduation_liquidity(vamp_state: &VampState, lp_vault_amount: u64) -> Result<(u64, u64)> {
+    require!(
+        vamp_state.status == VampStatus::Graduated,
+        ErrorCode::VampNotGraduated
+    );
+    Ok((vamp_state.reserve_balance, lp_vault_amount))
+}
+
+fn validate_graduation(graduation: &GraduationConfig) -> Result<()> {
+    require!(
+        graduation.reserve_threshold.is_some() || graduation.claimed_threshold.is_some(),
+        ErrorCode::InvalidGraduationConfig
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::claim::tests::vamp_state_fixture;
+
+    fn graduation_fixture() -> GraduationConfig {
+        GraduationConfig {
+            reserve_threshold: Some(1_000_000_000),
+            claimed_threshold: None,
+            lp_allocation: 500_000,
+        }
+    }
+
+    #[test]
+    fn test_validate_graduation() {
+        assert_eq!(Ok(()), validate_graduation(&graduation_fixture()));
+        assert_eq!(
+            Err(ErrorCode::InvalidGraduationConfig.into()),
+            validate_graduation(&GraduationConfig {
+                reserve_threshold: None,
+                ..graduation_fixture()
+            })
+        );
+    }
+
+    #[test]
+    fn test_graduation_is_reached() {
+        let graduation = graduation_fixture();
+        assert!(!graduation.is_reached(999_999_999, u64::MAX));
+        assert!(graduation.is_reached(1_000_000_000, 0));
+
+        // Every threshold that is set has to be reached
+        let graduation = GraduationConfig {
+            claimed_threshold: Some(5_000),
+            ..graduation
+        };
+        assert!(!graduation.is_reached(1_000_000_000, 4_999));
+        assert!(!graduation.is_reached(999_999_999, 5_000));
+        assert!(graduation.is_reached(1_000_000_000, 5_000));
+    }
+
+    #[test]
+    fn test_graduation_liquidity() {
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.reserve_balance = 2_000_000_000;
+
+        // The reserve backs redeems until the vamp graduates
+        assert_eq!(
+            graduation_liquidity(&vamp_state, 500_000),
+            Err(ErrorCode::VampNotGraduated.into())
+        );
+
+        vamp_state.status = VampStatus::Graduated;
+        assert_eq!(
+            graduation_liquidity(&vamp_state, 500_000),
+            Ok((2_000_000_000, 500_000))
+        );
+    }
+}
@@ -1,6 +1,12 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
 use crate::constant::ANCHOR_DISCRIMINATOR;
-use crate::state::vamp_state::VampState;
+use crate::event::ErrorCode;
+use crate::instructions::claim_with_proof::verify_snapshot_root;
+use crate::instructions::release::validate_vesting_schedule;
+use crate::instructions::rotate_signers::validate_validator_set;
+use crate::instructions::update_vamping_params::validate_vamping_params;
+use crate::state::vamp_state::{ClaimTracking, CurveKind, VampState, VampStatus, VestingSchedule};
 use anchor_spl::metadata::{
     create_metadata_accounts_v3, mpl_token_metadata::types::DataV2, CreateMetadataAccountsV3,
     Metadata,
@@ -14,9 +20,10 @@ use anchor_spl::{
 const DEFAULT_CURVE_SLOPE: u64 = 1;                     // Much smaller slope for gentler curve
 const DEFAULT_BASE_PRICE: u64 = 10_000_000;             // 0.01 SOL base price in lamports
 const DEFAULT_MAX_PRICE: u64 = 100_000_000;             // 0.1 SOL max price per token in lamports
+const DEFAULT_CURVE_EXPONENT: u64 = 2;                  // Quadratic power curve
 
 // Structure for vamping parameters
-#[derive(Clone)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
 pub struct VampingParams {
     pub paid_claiming_enabled: bool,
     pub use_bonding_curve: bool,
@@ -24,6 +31,10 @@ pub struct VampingParams {
     pub base_price: u64,
     pub max_price: Option<u64>,
     pub flat_price_per_token: u64,
+    pub curve_kind: CurveKind,
+    pub curve_exponent: u64,
+    pub curve_step_size: u64,
+    pub redeem_spread_bps: u16,
 }
 
 #[derive(Accounts)]
@@ -85,23 +96,142 @@ pub struct Initialize<'info> {
     pub system_program: Program<'info, System>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub rent: Sysvar<'info, Rent>,
+
+    /// CHECK: The instructions sysvar, read to find the snapshot root attestation verified by the Secp256k1 program
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+}
+
+/// Vamp settings shared by the classic and the Token-2022 mint creation
+pub(crate) struct VampSetup {
+    pub vamp_identifier: u64,
+    pub solver_public_key: Vec<u8>,
+    pub validator_set: Vec<[u8; 20]>,
+    pub validator_threshold: u8,
+    pub intent_id: Vec<u8>,
+    pub source_chain_id: u64,
+    pub merkle_root: Option<[u8; 32]>,
+    pub claim_deadline: Option<i64>,
+    pub vesting: Option<VestingSchedule>,
+    pub claim_tracking: ClaimTracking,
+    pub vamping_params: Option<VampingParams>,
+}
+
+impl VampSetup {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(claim_deadline) = self.claim_deadline {
+            require!(
+                claim_deadline > Clock::get()?.unix_timestamp,
+                ErrorCode::InvalidClaimDeadline
+            );
+        }
+        validate_validator_set(&self.validator_set, self.validator_threshold)?;
+        validate_claim_tracking(self.claim_tracking, &self.merkle_root)?;
+        if let Some(vesting) = &self.vesting {
+            validate_vesting_schedule(vesting)?;
+        }
+        if let Some(vamping_params) = &self.vamping_params {
+            validate_vamping_params(vamping_params)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn into_vamp_state(
+        self,
+        bump: u8,
+        mint: Pubkey,
+        sol_vault: Pubkey,
+        authority: Pubkey,
+        token_supply: u64,
+    ) -> VampState {
+        // Use provided vamping parameters or fall back to defaults
+        let params = self.vamping_params.unwrap_or(VampingParams {
+            paid_claiming_enabled: false,
+            use_bonding_curve: false,
+            curve_slope: DEFAULT_CURVE_SLOPE,
+            base_price: DEFAULT_BASE_PRICE,
+            max_price: Some(DEFAULT_MAX_PRICE),
+            flat_price_per_token: 1,
+            curve_kind: CurveKind::Linear,
+            curve_exponent: DEFAULT_CURVE_EXPONENT,
+            curve_step_size: 0,
+            redeem_spread_bps: 0,
+        });
+
+        VampState {
+            bump,
+            mint,
+            solver_public_key: self.solver_public_key,
+            validator_set: self.validator_set,
+            vamp_identifier: self.vamp_identifier,
+            intent_id: self.intent_id,
+            total_claimed: 0,
+            reserve_balance: 0,
+            token_supply,
+            curve_exponent: params.curve_exponent,
+            sol_vault,
+            curve_slope: params.curve_slope,
+            base_price: params.base_price,
+            max_price: params.max_price,
+            use_bonding_curve: params.use_bonding_curve,
+            flat_price_per_token: params.flat_price_per_token,
+            paid_claiming_enabled: params.paid_claiming_enabled,
+            authority,
+            treasury: authority,
+            merkle_root: self.merkle_root,
+            claim_deadline: self.claim_deadline,
+            params_locked: false,
+            curve_kind: params.curve_kind,
+            curve_step_size: params.curve_step_size,
+            redeem_spread_bps: params.redeem_spread_bps,
+            paused: false,
+            signer_epoch: 0,
+            previous_solver_public_key: vec![],
+            previous_validator_set: vec![],
+            previous_signers_valid_until: None,
+            validator_threshold: self.validator_threshold,
+            source_chain_id: self.source_chain_id,
+            vesting: self.vesting,
+            vesting_locked: 0,
+            snapshot_epoch: 0,
+            protocol_fees_paid: 0,
+            referral_fees_paid: 0,
+            graduation: None,
+            status: VampStatus::Active,
+            claim_tracking: self.claim_tracking,
+            rotation_delay: 0,
+            pending_rotation: None,
+            previous_validator_threshold: 0,
+            swept_epoch: None,
+        }
+    }
+}
+
+/// Bitmaps are indexed by the Merkle leaf, a snapshot root has to be committed
+pub(crate) fn validate_claim_tracking(
+    claim_tracking: ClaimTracking,
+    merkle_root: &Option<[u8; 32]>,
+) -> Result<()> {
+    require!(
+        claim_tracking == ClaimTracking::Accounts || merkle_root.is_some(),
+        ErrorCode::InvalidClaimMode
+    );
+    Ok(())
 }
 
 impl<'info> Initialize<'info> {
-    pub fn create_token_mint(
+    pub(crate) fn create_token_mint(
         &mut self,
-        vamp_identifier: u64,
         token_name: String,
         token_symbol: String,
         token_uri: String,
         amount: u64,
-        solver_public_key: Vec<u8>,
-        validator_public_key: Vec<u8>,
-        intent_id: Vec<u8>,
+        setup: VampSetup,
         bumps: &InitializeBumps,
-        vamping_params: Option<VampingParams>,
     ) -> Result<()> {
-        let signer_seeds: &[&[&[u8]]] = &[&[b"mint", self.authority.key.as_ref(), &vamp_identifier.to_le_bytes(), &[bumps.mint_account]]];
+        setup.validate()?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[b"mint", self.authority.key.as_ref(), &setup.vamp_identifier.to_le_bytes(), &[bumps.mint_account]]];
 
         create_metadata_accounts_v3(
             CpiContext::new_with_signer(
@@ -146,35 +276,16 @@ impl<'info> Initialize<'info> {
             amount,
         )?;
 
-        // Use provided vamping parameters or fall back to defaults
-        let params = vamping_params.unwrap_or(VampingParams {
-            paid_claiming_enabled: false,
-            use_bonding_curve: false,
-            curve_slope: DEFAULT_CURVE_SLOPE,
-            base_price: DEFAULT_BASE_PRICE,
-            max_price: Some(DEFAULT_MAX_PRICE),
-            flat_price_per_token: 1,
-        });
+        self.vamp_state.set_inner(setup.into_vamp_state(
+            bumps.vamp_state,
+            self.mint_account.key(),
+            self.sol_vault.key(),
+            self.authority.key(),
+            amount,
+        ));
 
-        self.vamp_state.set_inner(VampState {
-            bump: bumps.vamp_state,
-            mint: self.mint_account.key(),
-            solver_public_key,
-            validator_public_key,
-            vamp_identifier,
-            intent_id,
-            total_claimed: 0,
-            reserve_balance: 0,
-            token_supply: amount,
-            curve_exponent: 2,
-            sol_vault: self.sol_vault.key(),
-            curve_slope: params.curve_slope,
-            base_price: params.base_price,
-            max_price: params.max_price,
-            use_bonding_curve: params.use_bonding_curve,
-            flat_price_per_token: params.flat_price_per_token,
-            paid_claiming_enabled: params.paid_claiming_enabled,
-        });
+        // A snapshot root is only trusted once the signers of the vamp attested it
+        verify_snapshot_root(&self.instructions_sysvar, &self.vamp_state)?;
 
         Ok(())
     }
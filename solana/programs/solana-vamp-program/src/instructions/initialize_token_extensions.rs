@@ -0,0 +1,176 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_2022_extensions::spl_pod::optional_keys::OptionalNonZeroPubkey;
+use anchor_spl::token_2022_extensions::spl_token_metadata_interface::state::TokenMetadata;
+use anchor_spl::token_interface::{
+    mint_to, token_metadata_initialize, Mint, MintTo, Token2022, TokenAccount,
+    TokenMetadataInitialize,
+};
+
+use crate::constant::ANCHOR_DISCRIMINATOR;
+use crate::event::ErrorCode;
+use crate::instructions::claim_with_proof::verify_snapshot_root;
+use crate::instructions::initialize::VampSetup;
+use crate::state::vamp_state::VampState;
+
+/// Same vamp as `Initialize`, with the mint created under Token-2022. The name, symbol and
+/// URI live in the mint's token-metadata extension instead of a Metaplex metadata account.
+#[derive(Accounts)]
+#[instruction(vamp_identifier: u64, token_decimals: u8)]
+pub struct InitializeTokenExtensions<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"mint", authority.key().as_ref(), &vamp_identifier.to_le_bytes()],
+        bump,
+        mint::decimals = token_decimals,
+        mint::authority = mint_account.key(),
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = mint_account,
+        extensions::metadata_pointer::metadata_address = mint_account,
+    )]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + VampState::INIT_SPACE
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+        token::token_program = token_program,
+        seeds = [b"vault", mint_account.key().as_ref()],
+        bump,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is safe because we're creating a SOL vault PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"sol_vault", mint_account.key().as_ref()],
+        bump,
+        space = 0, // SOL accounts don't need space
+    )]
+    pub sol_vault: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: The instructions sysvar, read to find the snapshot root attestation verified by the Secp256k1 program
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+}
+
+impl<'info> InitializeTokenExtensions<'info> {
+    pub(crate) fn create_token_extensions_mint(
+        &mut self,
+        token_name: String,
+        token_symbol: String,
+        token_uri: String,
+        amount: u64,
+        setup: VampSetup,
+        bumps: &InitializeTokenExtensionsBumps,
+    ) -> Result<()> {
+        setup.validate()?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[b"mint", self.authority.key.as_ref(), &setup.vamp_identifier.to_le_bytes(), &[bumps.mint_account]]];
+
+        // The token program grows the mint for the metadata, the rent has to be there first
+        let mint_info = self.mint_account.to_account_info();
+        let metadata = TokenMetadata {
+            update_authority: OptionalNonZeroPubkey::try_from(Some(mint_info.key()))?,
+            mint: mint_info.key(),
+            name: token_name.clone(),
+            symbol: token_symbol.clone(),
+            uri: token_uri.clone(),
+            additional_metadata: vec![],
+        };
+        let new_len = mint_info
+            .data_len()
+            .checked_add(metadata.tlv_size_of()?)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        fund_mint_growth(
+            self.authority.to_account_info(),
+            mint_info.clone(),
+            self.system_program.to_account_info(),
+            new_len,
+        )?;
+
+        token_metadata_initialize(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TokenMetadataInitialize {
+                    program_id: self.token_program.to_account_info(),
+                    metadata: mint_info.clone(),
+                    update_authority: mint_info.clone(),
+                    mint_authority: mint_info.clone(),
+                    mint: mint_info.clone(),
+                },
+                signer_seeds,
+            ),
+            token_name,
+            token_symbol,
+            token_uri,
+        )?;
+
+        msg!("Token created successfully.");
+
+        mint_to(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                MintTo {
+                    mint: mint_info.clone(),
+                    to: self.vault.to_account_info(),
+                    authority: mint_info,
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        self.vamp_state.set_inner(setup.into_vamp_state(
+            bumps.vamp_state,
+            self.mint_account.key(),
+            self.sol_vault.key(),
+            self.authority.key(),
+            amount,
+        ));
+
+        // A snapshot root is only trusted once the signers of the vamp attested it
+        verify_snapshot_root(&self.instructions_sysvar, &self.vamp_state)?;
+
+        Ok(())
+    }
+}
+
+/// Tops the mint up to the rent of `new_len` bytes, the token program only reallocates
+/// metadata that is already paid for
+pub(crate) fn fund_mint_growth<'info>(
+    payer: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    new_len: usize,
+) -> Result<()> {
+    let missing_lamports = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(mint.lamports());
+    if missing_lamports > 0 {
+        transfer(
+            CpiContext::new(system_program, Transfer { from: payer, to: mint }),
+            missing_lamports,
+        )?;
+    }
+    Ok(())
+}
@@ -1,6 +1,39 @@
 pub mod initialize;
 mod claim;
+mod claim_with_proof;
+mod redeem;
+mod quote_claim;
 mod calculate_claim_cost;
+mod secp256k1_signatures;
+mod withdraw_reserve;
+mod sweep_unclaimed;
+mod update_vamping_params;
+mod pause_vamp;
+mod global_config;
+mod rotate_signers;
+mod release;
+mod top_up_vamp;
+mod initialize_token_extensions;
+mod create_claim_bitmap;
+mod claim_batch;
+mod graduate;
+mod update_token_metadata;
 
 pub use initialize::*;
 pub use claim::*;
+pub use claim_with_proof::*;
+pub use redeem::*;
+pub use quote_claim::*;
+pub use withdraw_reserve::*;
+pub use sweep_unclaimed::*;
+pub use update_vamping_params::*;
+pub use pause_vamp::*;
+pub use global_config::*;
+pub use rotate_signers::*;
+pub use release::*;
+pub use top_up_vamp::*;
+pub use initialize_token_extensions::*;
+pub use create_claim_bitmap::*;
+pub use claim_batch::*;
+pub use graduate::*;
+pub use update_token_metadata::*;
@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    event::{ErrorCode, VampPauseUpdated},
+    state::vamp_state::VampState,
+};
+
+#[derive(Accounts)]
+pub struct PauseVamp<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> PauseVamp<'info> {
+    /// Stops or resumes claims on this vamp, redeem and reserve withdrawals are unaffected
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.vamp_state.paused = paused;
+
+        emit!(VampPauseUpdated {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            paused,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    instructions::claim::{charged_claim_payment, check_claims_open},
+    state::{global_config::GlobalConfig, vamp_state::VampState},
+};
+
+#[derive(Accounts)]
+pub struct QuoteClaim<'info> {
+    #[account(
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = global_config.bump,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> QuoteClaim<'info> {
+    /// Returns the lamports a claim of `balance` base units would cost right now, fees included.
+    /// A `referred` claim also pays the referral fee. Meant to be simulated, the value is
+    /// delivered as the transaction return data. Fails like the claim would while claims are closed.
+    pub fn quote_claim(&self, balance: u64, referred: bool) -> Result<u64> {
+        check_claims_open(&self.global_config, &self.vamp_state, None)?;
+        charged_claim_payment(
+            &self.global_config,
+            &self.vamp_state,
+            balance,
+            self.mint_account.decimals,
+            referred,
+            None,
+        )?
+        .total()
+    }
+}
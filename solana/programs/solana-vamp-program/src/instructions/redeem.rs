@@ -0,0 +1,288 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    constant::BPS_DENOMINATOR,
+    event::{ErrorCode, TokensRedeemed},
+    instructions::claim::calculate_curve_refund,
+    state::vamp_state::{VampState, VampStatus},
+};
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", mint_account.key().as_ref()],
+        bump,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is the SOL vault PDA
+    #[account(
+        mut,
+        seeds = [b"sol_vault", mint_account.key().as_ref()],
+        bump,
+    )]
+    pub sol_vault: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint_account,
+        token::authority = authority,
+    )]
+    pub redeemer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Redeem<'info> {
+    pub fn redeem(&mut self, amount: u64, min_payout: Option<u64>) -> Result<()> {
+        // Free vamps have no reserve to pay out from
+        require!(
+            self.vamp_state.paid_claiming_enabled,
+            ErrorCode::RedeemDisabled
+        );
+        // The reserve of a graduated vamp is kept for its liquidity pool
+        require!(
+            self.vamp_state.status == VampStatus::Active,
+            ErrorCode::VampGraduated
+        );
+
+        let (payout, spread) =
+            calculate_redeem_payout(&self.vamp_state, amount, self.mint_account.decimals)?;
+        if let Some(min_payout) = min_payout {
+            require!(payout >= min_payout, ErrorCode::PayoutTooLow);
+        }
+
+        transfer_checked(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.redeemer_token_account.to_account_info(),
+                    mint: self.mint_account.to_account_info(),
+                    to: self.vault.to_account_info(),
+                    authority: self.authority.to_account_info(),
+                },
+            ),
+            amount,
+            self.mint_account.decimals,
+        )?;
+
+        // The SOL vault is owned by this program, so lamports are moved directly
+        let sol_vault = self.sol_vault.to_account_info();
+        let redeemer = self.authority.to_account_info();
+        let vault_lamports = sol_vault
+            .lamports()
+            .checked_sub(payout)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let redeemer_lamports = redeemer
+            .lamports()
+            .checked_add(payout)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        **sol_vault.try_borrow_mut_lamports()? = vault_lamports;
+        **redeemer.try_borrow_mut_lamports()? = redeemer_lamports;
+
+        // The spread stays in the reserve
+        let vamp_state = &mut self.vamp_state;
+        vamp_state.reserve_balance = vamp_state
+            .reserve_balance
+            .checked_sub(payout)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        vamp_state.total_claimed = vamp_state
+            .total_claimed
+            .checked_sub(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(TokensRedeemed {
+            mint_account: self.mint_account.key(),
+            redeemer: self.authority.key(),
+            amount,
+            payout,
+            spread,
+            total_claimed: vamp_state.total_claimed,
+            reserve_balance: vamp_state.reserve_balance,
+        });
+        Ok(())
+    }
+}
+
+/// Returns the SOL paid out for redeeming `amount` base units and the spread kept in the reserve.
+/// The curve is walked back from the top of the claimed supply, so a redeem refunds the last claims.
+fn calculate_redeem_payout(vamp_state: &VampState, amount: u64, decimals: u8) -> Result<(u64, u64)> {
+    let start = vamp_state
+        .total_claimed
+        .checked_sub(amount)
+        .ok_or(ErrorCode::InsufficientClaimedSupply)?;
+    let curve_value = calculate_curve_refund(vamp_state, start, amount, decimals)?;
+
+    let spread = (curve_value as u128)
+        .checked_mul(vamp_state.redeem_spread_bps as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        .checked_div(BPS_DENOMINATOR as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)? as u64;
+    let payout = curve_value
+        .checked_sub(spread)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    // Claims round up and refunds round down, a shortfall means the reserve was drained
+    require!(
+        payout <= vamp_state.reserve_balance,
+        ErrorCode::InsufficientReserve
+    );
+
+    Ok((payout, spread))
+}
+
+/// Returns the SOL the reserve owes the claimed supply, the curve value of redeeming all of it.
+/// The spread of future redeems isn't counted as earned until they happen.
+pub(crate) fn redeemable_liability(vamp_state: &VampState, decimals: u8) -> Result<u64> {
+    if !vamp_state.paid_claiming_enabled {
+        return Ok(0);
+    }
+    calculate_curve_refund(vamp_state, 0, vamp_state.total_claimed, decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::vamp_state::CurveKind;
+    use crate::instructions::claim::{
+        calculate_curve_cost, charged_claim_payment, record_claim_payment, tests::vamp_state_fixture,
+    };
+    use crate::state::global_config::GlobalConfig;
+
+    #[test]
+    fn test_calculate_redeem_payout_reverses_claim() {
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.use_bonding_curve = true;
+        vamp_state.base_price = 10_000_000;
+        vamp_state.curve_slope = 1_000;
+
+        // Two holders claim 5 and 3 tokens
+        let first_cost = calculate_curve_cost(&vamp_state, 0, 5_000_000_000, 9).unwrap();
+        vamp_state.total_claimed = 5_000_000_000;
+        let second_cost = calculate_curve_cost(&vamp_state, 5_000_000_000, 3_000_000_000, 9).unwrap();
+        vamp_state.total_claimed = 8_000_000_000;
+        vamp_state.reserve_balance = first_cost + second_cost;
+
+        // Redeeming the last claim refunds exactly what it cost
+        assert_eq!(
+            calculate_redeem_payout(&vamp_state, 3_000_000_000, 9),
+            Ok((second_cost, 0))
+        );
+
+        vamp_state.redeem_spread_bps = 250;
+        let (payout, spread) = calculate_redeem_payout(&vamp_state, 3_000_000_000, 9).unwrap();
+        assert_eq!(payout + spread, second_cost);
+        assert_eq!(spread, second_cost * 250 / 10_000);
+
+        // The whole claimed supply is covered by the reserve
+        vamp_state.redeem_spread_bps = 0;
+        assert_eq!(
+            calculate_redeem_payout(&vamp_state, 8_000_000_000, 9),
+            Ok((first_cost + second_cost, 0))
+        );
+
+        assert_eq!(
+            calculate_redeem_payout(&vamp_state, 8_000_000_001, 9),
+            Err(ErrorCode::InsufficientClaimedSupply.into())
+        );
+
+        // A reserve short of the curve value fails instead of paying out less
+        vamp_state.reserve_balance -= 1;
+        assert_eq!(
+            calculate_redeem_payout(&vamp_state, 8_000_000_000, 9),
+            Err(ErrorCode::InsufficientReserve.into())
+        );
+    }
+
+    #[test]
+    fn test_redeemable_liability() {
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.use_bonding_curve = true;
+        vamp_state.curve_kind = CurveKind::Exponential;
+        vamp_state.base_price = 10_000_000;
+        vamp_state.curve_slope = 100_000_000;
+        vamp_state.redeem_spread_bps = 250;
+
+        // Claims in uneven chunks, each rounded up
+        for amount in [1_333_333_333, 7, 2_500_000_001, 999_999_999] {
+            let cost = calculate_curve_cost(&vamp_state, vamp_state.total_claimed, amount, 9).unwrap();
+            vamp_state.total_claimed += amount;
+            vamp_state.reserve_balance += cost;
+        }
+        let liability = redeemable_liability(&vamp_state, 9).unwrap();
+        assert!(liability <= vamp_state.reserve_balance);
+
+        // Once only the liability is left, redeeming everything in chunks still gets paid
+        vamp_state.reserve_balance = liability;
+        for amount in [2_000_000_000, 1, 2_833_333_339] {
+            let (payout, _) = calculate_redeem_payout(&vamp_state, amount, 9).unwrap();
+            vamp_state.total_claimed -= amount;
+            vamp_state.reserve_balance -= payout;
+        }
+        assert_eq!(vamp_state.total_claimed, 0);
+        assert_eq!(redeemable_liability(&vamp_state, 9), Ok(0));
+
+        // Free claims can't be redeemed, nothing is owed
+        vamp_state.total_claimed = 1_000_000_000;
+        vamp_state.paid_claiming_enabled = false;
+        assert_eq!(redeemable_liability(&vamp_state, 9), Ok(0));
+    }
+
+    #[test]
+    fn test_redeem_all_with_fees() {
+        let mut vamp_state = vamp_state_fixture();
+        vamp_state.use_bonding_curve = true;
+        vamp_state.base_price = 10_000_000;
+        vamp_state.curve_slope = 1_000;
+        let global_config = GlobalConfig {
+            bump: 255,
+            admin: Pubkey::default(),
+            emergency_freeze: false,
+            protocol_treasury: Pubkey::default(),
+            protocol_fee_bps: 250,
+            referral_fee_bps: 100,
+        };
+
+        // Every claim pays the fees on top of its curve cost
+        let claims = [(1_333_333_333, true), (7, false), (2_500_000_001, true)];
+        let mut fees = 0;
+        for (amount, referred) in claims {
+            let payment = charged_claim_payment(&global_config, &vamp_state, amount, 9, referred, None)
+                .unwrap();
+            fees += payment.protocol_fee + payment.referral_fee;
+            record_claim_payment(&mut vamp_state, &payment).unwrap();
+            vamp_state.total_claimed += amount;
+        }
+        assert!(fees > 0);
+        assert_eq!(vamp_state.protocol_fees_paid + vamp_state.referral_fees_paid, fees);
+
+        // Redeeming the whole claimed supply pays out the whole reserve. Claims round up and
+        // refunds round down, at most a lamport per claim stays behind.
+        let reserve = vamp_state.reserve_balance;
+        let mut paid_out = 0;
+        for (amount, _) in claims.into_iter().rev() {
+            let (payout, _) = calculate_redeem_payout(&vamp_state, amount, 9).unwrap();
+            vamp_state.total_claimed -= amount;
+            vamp_state.reserve_balance -= payout;
+            paid_out += payout;
+        }
+        assert_eq!(vamp_state.total_claimed, 0);
+        assert!(reserve - paid_out <= claims.len() as u64);
+    }
+}
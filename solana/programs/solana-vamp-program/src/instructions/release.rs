@@ -0,0 +1,202 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    event::{ErrorCode, TokensReleased},
+    state::{
+        global_config::GlobalConfig,
+        vamp_state::{snapshot_epoch_seed, VampState, VestingSchedule},
+        vesting_state::VestingState,
+    },
+};
+
+#[derive(Accounts)]
+pub struct Release<'info> {
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = global_config.bump,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting",
+            vamp_state.key().as_ref(),
+            &vesting_state.eth_address,
+            &snapshot_epoch_seed(vesting_state.snapshot_epoch),
+        ],
+        bump = vesting_state.bump,
+        has_one = beneficiary @ ErrorCode::InvalidBeneficiary,
+    )]
+    pub vesting_state: Account<'info, VestingState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", mint_account.key().as_ref()],
+        bump,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint_account,
+    )]
+    pub beneficiary: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Release<'info> {
+    /// Transfers the unlocked tokens not released yet. Anyone can crank it, the tokens
+    /// always go to the beneficiary recorded at claim time.
+    pub fn release(&mut self) -> Result<()> {
+        require!(!self.global_config.emergency_freeze, ErrorCode::ClaimsFrozen);
+
+        let schedule = self
+            .vamp_state
+            .vesting
+            .ok_or(ErrorCode::InvalidVestingAccount)?;
+        let unlocked = unlocked_amount(
+            &schedule,
+            self.vesting_state.total_amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+        let amount = unlocked
+            .checked_sub(self.vesting_state.released_amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(amount > 0, ErrorCode::NothingToRelease);
+
+        let mint_key = self.mint_account.key();
+        let seeds = &[b"vamp".as_ref(), mint_key.as_ref(), &[self.vamp_state.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.vault.to_account_info(),
+                    mint: self.mint_account.to_account_info(),
+                    to: self.beneficiary.to_account_info(),
+                    authority: self.vamp_state.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            self.mint_account.decimals,
+        )?;
+
+        let vesting_state = &mut self.vesting_state;
+        vesting_state.released_amount = unlocked;
+        self.vamp_state.vesting_locked = self
+            .vamp_state
+            .vesting_locked
+            .checked_sub(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(TokensReleased {
+            mint_account: mint_key,
+            eth_address: vesting_state.eth_address,
+            beneficiary: vesting_state.beneficiary,
+            amount,
+            released_amount: vesting_state.released_amount,
+            total_amount: vesting_state.total_amount,
+        });
+        Ok(())
+    }
+}
+
+pub(crate) fn validate_vesting_schedule(schedule: &VestingSchedule) -> Result<()> {
+    require!(
+        schedule.start_time >= 0
+            && schedule.duration > 0
+            && (0..=schedule.duration).contains(&schedule.cliff_duration),
+        ErrorCode::InvalidVestingSchedule
+    );
+    Ok(())
+}
+
+/// Part of `total_amount` unlocked at `now`
+pub(crate) fn unlocked_amount(
+    schedule: &VestingSchedule,
+    total_amount: u64,
+    now: i64,
+) -> Result<u64> {
+    let elapsed = now.saturating_sub(schedule.start_time);
+    if elapsed < schedule.cliff_duration {
+        return Ok(0);
+    }
+    if elapsed >= schedule.duration {
+        return Ok(total_amount);
+    }
+
+    let unlocked = (total_amount as u128)
+        .checked_mul(elapsed as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / schedule.duration as u128;
+    Ok(unlocked as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlocked_amount() {
+        let schedule = VestingSchedule {
+            start_time: 1_000,
+            cliff_duration: 100,
+            duration: 400,
+        };
+
+        // Nothing before the cliff
+        assert_eq!(unlocked_amount(&schedule, 1_000, 500), Ok(0));
+        assert_eq!(unlocked_amount(&schedule, 1_000, 1_099), Ok(0));
+
+        // The accrued amount unlocks at the cliff, then linearly
+        assert_eq!(unlocked_amount(&schedule, 1_000, 1_100), Ok(250));
+        assert_eq!(unlocked_amount(&schedule, 1_000, 1_200), Ok(500));
+
+        // Everything after the end
+        assert_eq!(unlocked_amount(&schedule, 1_000, 1_400), Ok(1_000));
+        assert_eq!(unlocked_amount(&schedule, u64::MAX, i64::MAX), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_validate_vesting_schedule() {
+        let schedule = VestingSchedule {
+            start_time: 1_000,
+            cliff_duration: 0,
+            duration: 400,
+        };
+        assert_eq!(validate_vesting_schedule(&schedule), Ok(()));
+        assert_eq!(
+            validate_vesting_schedule(&VestingSchedule { cliff_duration: 400, ..schedule }),
+            Ok(())
+        );
+
+        for invalid in [
+            VestingSchedule { duration: 0, ..schedule },
+            VestingSchedule { cliff_duration: 401, ..schedule },
+            VestingSchedule { cliff_duration: -1, ..schedule },
+            VestingSchedule { start_time: -1, ..schedule },
+        ] {
+            assert_eq!(
+                validate_vesting_schedule(&invalid),
+                Err(ErrorCode::InvalidVestingSchedule.into())
+            );
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    constant::MAX_VALIDATORS,
+    event::{
+        ErrorCode, RotationDelayUpdated, SignerRotationCancelled, SignerRotationQueued,
+        SignersRotated,
+    },
+    state::vamp_state::{SignerRotation, VampState},
+};
+
+/// Rotated out keys can't stay trusted for longer than a week
+const MAX_SIGNER_GRACE_PERIOD: i64 = 7 * 24 * 60 * 60;
+
+/// Holders get at most a month's notice of a rotation
+const MAX_ROTATION_DELAY: i64 = 30 * 24 * 60 * 60;
+
+const ETH_ADDRESS_LEN: usize = 20;
+
+/// Rotating, applying and cancelling a rotation and setting the rotation delay act on the same accounts
+#[derive(Accounts)]
+pub struct RotateSigners<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> RotateSigners<'info> {
+    /// Replaces the solver and validator keys. Signatures from the previous keys are accepted
+    /// for `grace_period` seconds, zero revokes them immediately. With a rotation delay the new
+    /// keys are queued, replacing any pending rotation, and applied with `apply_signer_rotation`.
+    pub fn rotate_signers(
+        &mut self,
+        solver_public_key: Vec<u8>,
+        validator_set: Vec<[u8; 20]>,
+        validator_threshold: u8,
+        grace_period: i64,
+    ) -> Result<()> {
+        require!(solver_public_key.len() == ETH_ADDRESS_LEN, ErrorCode::InvalidSignerKey);
+        validate_validator_set(&validator_set, validator_threshold)?;
+        require!(
+            (0..=MAX_SIGNER_GRACE_PERIOD).contains(&grace_period),
+            ErrorCode::InvalidGracePeriod
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let rotation = SignerRotation {
+            solver_public_key,
+            validator_set,
+            validator_threshold,
+            grace_period,
+            executable_at: now
+                .checked_add(self.vamp_state.rotation_delay)
+                .ok_or(ErrorCode::ArithmeticOverflow)?,
+        };
+        if self.vamp_state.rotation_delay == 0 {
+            return self.apply(rotation, now);
+        }
+
+        emit!(SignerRotationQueued {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            solver_public_key: rotation.solver_public_key.clone(),
+            validator_set: rotation.validator_set.clone(),
+            validator_threshold,
+            executable_at: rotation.executable_at,
+        });
+        self.vamp_state.pending_rotation = Some(rotation);
+        Ok(())
+    }
+
+    /// Applies the pending rotation once its delay has passed
+    pub fn apply_signer_rotation(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let rotation = self
+            .vamp_state
+            .pending_rotation
+            .take()
+            .ok_or(ErrorCode::NoPendingRotation)?;
+        require!(now >= rotation.executable_at, ErrorCode::RotationTimeLocked);
+        self.apply(rotation, now)
+    }
+
+    pub fn cancel_signer_rotation(&mut self) -> Result<()> {
+        require!(
+            self.vamp_state.pending_rotation.take().is_some(),
+            ErrorCode::NoPendingRotation
+        );
+
+        emit!(SignerRotationCancelled {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+        });
+        Ok(())
+    }
+
+    /// Sets the seconds a rotation waits before it can be applied. The delay can only be raised,
+    /// otherwise lowering it would skip the notice it gives.
+    pub fn set_rotation_delay(&mut self, rotation_delay: i64) -> Result<()> {
+        require!(
+            (self.vamp_state.rotation_delay..=MAX_ROTATION_DELAY).contains(&rotation_delay),
+            ErrorCode::InvalidRotationDelay
+        );
+        self.vamp_state.rotation_delay = rotation_delay;
+
+        emit!(RotationDelayUpdated {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            rotation_delay,
+        });
+        Ok(())
+    }
+
+    fn apply(&mut self, rotation: SignerRotation, now: i64) -> Result<()> {
+        let vamp_state = &mut self.vamp_state;
+        vamp_state.previous_signers_valid_until = if rotation.grace_period > 0 {
+            Some(now.checked_add(rotation.grace_period).ok_or(ErrorCode::ArithmeticOverflow)?)
+        } else {
+            None
+        };
+        vamp_state.previous_solver_public_key =
+            std::mem::replace(&mut vamp_state.solver_public_key, rotation.solver_public_key);
+        vamp_state.previous_validator_set =
+            std::mem::replace(&mut vamp_state.validator_set, rotation.validator_set);
+        vamp_state.previous_validator_threshold =
+            std::mem::replace(&mut vamp_state.validator_threshold, rotation.validator_threshold);
+        // Attestations name the epoch they were signed in, the ones of earlier epochs stop
+        // verifying once the grace period ends, even where a key is kept
+        vamp_state.signer_epoch = vamp_state
+            .signer_epoch
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(SignersRotated {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            signer_epoch: vamp_state.signer_epoch,
+            solver_public_key: vamp_state.solver_public_key.clone(),
+            validator_set: vamp_state.validator_set.clone(),
+            validator_threshold: rotation.validator_threshold,
+            previous_signers_valid_until: vamp_state.previous_signers_valid_until,
+        });
+        Ok(())
+    }
+}
+
+/// Checks the set holds 1 to MAX_VALIDATORS distinct addresses and the threshold is reachable
+/// with signatures that fit a claim transaction
+pub(crate) fn validate_validator_set(validator_set: &[[u8; 20]], validator_threshold: u8) -> Result<()> {
+    require!(
+        !validator_set.is_empty() && validator_set.len() <= MAX_VALIDATORS as usize,
+        ErrorCode::InvalidValidatorSet
+    );
+    require!(
+        validator_threshold > 0 && validator_threshold as usize <= validator_set.len(),
+        ErrorCode::InvalidValidatorSet
+    );
+    for (i, validator) in validator_set.iter().enumerate() {
+        require!(
+            !validator_set[i + 1..].contains(validator),
+            ErrorCode::InvalidValidatorSet
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_validator_set() {
+        assert_eq!(validate_validator_set(&[[1; 20], [2; 20]], 2), Ok(()));
+        assert_eq!(validate_validator_set(&[[1; 20]], 1), Ok(()));
+        assert_eq!(
+            validate_validator_set(&[], 0),
+            Err(ErrorCode::InvalidValidatorSet.into())
+        );
+        assert_eq!(
+            validate_validator_set(&[[1; 20], [2; 20]], 0),
+            Err(ErrorCode::InvalidValidatorSet.into())
+        );
+        assert_eq!(
+            validate_validator_set(&[[1; 20], [2; 20]], 3),
+            Err(ErrorCode::InvalidValidatorSet.into())
+        );
+        assert_eq!(
+            validate_validator_set(&[[1; 20], [1; 20]], 2),
+            Err(ErrorCode::InvalidValidatorSet.into())
+        );
+
+        // Larger sets and thresholds don't fit a claim transaction, the error message states the cap
+        assert_eq!(MAX_VALIDATORS, 2);
+        let validator_set: Vec<[u8; 20]> = (0..=MAX_VALIDATORS).map(|i| [i; 20]).collect();
+        assert_eq!(
+            validate_validator_set(&validator_set, 1),
+            Err(ErrorCode::InvalidValidatorSet.into())
+        );
+        assert_eq!(
+            validate_validator_set(&validator_set[..MAX_VALIDATORS as usize], MAX_VALIDATORS),
+            Ok(())
+        );
+        assert_eq!(
+            validate_validator_set(&validator_set[..MAX_VALIDATORS as usize], MAX_VALIDATORS + 1),
+            Err(ErrorCode::InvalidValidatorSet.into())
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    secp256k1_program, sysvar::instructions::load_instruction_at_checked,
+};
+
+use crate::event::ErrorCode;
+
+// Layout of the Secp256k1 program instruction data: a signature count followed by
+// one 11-byte offsets entry per signature, see solana_secp256k1_program::SecpSignatureOffsets
+const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 11;
+const ETH_ADDRESS_SERIALIZED_SIZE: usize = 20;
+
+/// A signature the Secp256k1 program verified within the current transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VerifiedSignature {
+    pub eth_address: [u8; 20],
+    pub message: Vec<u8>,
+}
+
+/// Collects the signatures checked by every Secp256k1 program instruction of the transaction.
+/// The runtime rejects the whole transaction when one of them is invalid, so being listed is the proof.
+pub(crate) fn load_verified_signatures(
+    instructions_sysvar: &AccountInfo,
+) -> Result<Vec<VerifiedSignature>> {
+    let instruction_count = {
+        let data = instructions_sysvar.try_borrow_data()?;
+        require!(data.len() >= 2, ErrorCode::InvalidSignatureInstruction);
+        u16::from_le_bytes([data[0], data[1]])
+    };
+
+    let mut verified = Vec::new();
+    for index in 0..instruction_count {
+        let instruction = load_instruction_at_checked(index as usize, instructions_sysvar)?;
+        if instruction.program_id == secp256k1_program::ID {
+            verified.extend(parse_secp256k1_instruction(index, &instruction.data)?);
+        }
+    }
+    Ok(verified)
+}
+
+fn parse_secp256k1_instruction(
+    instruction_index: u16,
+    data: &[u8],
+) -> Result<Vec<VerifiedSignature>> {
+    let count = *data.first().ok_or(ErrorCode::InvalidSignatureInstruction)? as usize;
+    require!(
+        data.len() > count * SIGNATURE_OFFSETS_SERIALIZED_SIZE,
+        ErrorCode::InvalidSignatureInstruction
+    );
+
+    let mut signatures = Vec::with_capacity(count);
+    for offsets in data[1..1 + count * SIGNATURE_OFFSETS_SERIALIZED_SIZE]
+        .chunks_exact(SIGNATURE_OFFSETS_SERIALIZED_SIZE)
+    {
+        let read_u16 = |at: usize| u16::from_le_bytes([offsets[at], offsets[at + 1]]) as usize;
+
+        // The precompile may read from any instruction, only data carried by the
+        // Secp256k1 instruction itself is known to be what was verified here
+        require!(
+            [offsets[2], offsets[5], offsets[10]]
+                .iter()
+                .all(|&index| index as u16 == instruction_index),
+            ErrorCode::InvalidSignatureInstruction
+        );
+
+        let eth_address_offset = read_u16(3);
+        let message_offset = read_u16(6);
+        let message_size = read_u16(8);
+        let eth_address = data
+            .get(eth_address_offset..eth_address_offset + ETH_ADDRESS_SERIALIZED_SIZE)
+            .ok_or(ErrorCode::InvalidSignatureInstruction)?;
+        let message = data
+            .get(message_offset..message_offset + message_size)
+            .ok_or(ErrorCode::InvalidSignatureInstruction)?;
+
+        signatures.push(VerifiedSignature {
+            eth_address: eth_address
+                .try_into()
+                .map_err(|_| ErrorCode::InvalidSignatureInstruction)?,
+            message: message.to_vec(),
+        });
+    }
+    Ok(signatures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mirrors solana_secp256k1_program::new_secp256k1_instruction_with_signature
+    fn secp256k1_instruction_data(
+        instruction_index: u8,
+        eth_address: [u8; 20],
+        message: &[u8],
+    ) -> Vec<u8> {
+        let eth_address_offset = 1 + SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+        let signature_offset = eth_address_offset + ETH_ADDRESS_SERIALIZED_SIZE;
+        let message_offset = signature_offset + 65;
+
+        let mut data = vec![1];
+        data.extend_from_slice(&(signature_offset as u16).to_le_bytes());
+        data.push(instruction_index);
+        data.extend_from_slice(&(eth_address_offset as u16).to_le_bytes());
+        data.push(instruction_index);
+        data.extend_from_slice(&(message_offset as u16).to_le_bytes());
+        data.extend_from_slice(&(message.len() as u16).to_le_bytes());
+        data.push(instruction_index);
+        data.extend_from_slice(&eth_address);
+        data.extend_from_slice(&[0; 65]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn test_parse_secp256k1_instruction() {
+        let message = vec![7; 66];
+        let data = secp256k1_instruction_data(1, [9; 20], &message);
+        assert_eq!(
+            parse_secp256k1_instruction(1, &data),
+            Ok(vec![VerifiedSignature {
+                eth_address: [9; 20],
+                message,
+            }])
+        );
+
+        // Offsets pointing into another instruction can't be trusted
+        assert_eq!(
+            parse_secp256k1_instruction(0, &data),
+            Err(ErrorCode::InvalidSignatureInstruction.into())
+        );
+
+        // Truncated data
+        assert_eq!(
+            parse_secp256k1_instruction(1, &data[..data.len() - 1]),
+            Err(ErrorCode::InvalidSignatureInstruction.into())
+        );
+        assert_eq!(
+            parse_secp256k1_instruction(1, &[]),
+            Err(ErrorCode::InvalidSignatureInstruction.into())
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    event::{ErrorCode, UnclaimedSwept},
+    state::vamp_state::VampState,
+};
+
+#[derive(Accounts)]
+pub struct SweepUnclaimed<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", mint_account.key().as_ref()],
+        bump,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Treasury token account receiving the unclaimed tokens, not needed when burning
+    #[account(
+        mut,
+        token::mint = mint_account,
+        constraint = destination.owner == vamp_state.treasury @ ErrorCode::InvalidSweepDestination,
+    )]
+    pub destination: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> SweepUnclaimed<'info> {
+    /// Sweeps what is left of the vault once the claim deadline passed. The sweep closes the
+    /// current epoch and every archived one, claims of them are refused from now on.
+    pub fn sweep_unclaimed(&mut self, burn_tokens: bool) -> Result<()> {
+        // Vamps without a deadline stay open forever
+        let claim_deadline = self
+            .vamp_state
+            .claim_deadline
+            .ok_or(ErrorCode::ClaimWindowOpen)?;
+        require!(
+            Clock::get()?.unix_timestamp > claim_deadline,
+            ErrorCode::ClaimWindowOpen
+        );
+
+        let mint_key = self.mint_account.key();
+        let seeds = &[b"vamp".as_ref(), mint_key.as_ref(), &[self.vamp_state.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        // Tokens locked for vesting were claimed, they stay in the vault until released
+        let swept = self
+            .vault
+            .amount
+            .checked_sub(self.vamp_state.vesting_locked)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let destination = if burn_tokens {
+            burn(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    Burn {
+                        mint: self.mint_account.to_account_info(),
+                        from: self.vault.to_account_info(),
+                        authority: self.vamp_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                swept,
+            )?;
+            None
+        } else {
+            let destination = self
+                .destination
+                .as_ref()
+                .ok_or(ErrorCode::InvalidSweepDestination)?;
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.vault.to_account_info(),
+                        mint: self.mint_account.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: self.vamp_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                swept,
+                self.mint_account.decimals,
+            )?;
+            Some(destination.key())
+        };
+
+        self.vamp_state.swept_epoch = Some(self.vamp_state.snapshot_epoch);
+
+        emit!(UnclaimedSwept {
+            mint_account: mint_key,
+            destination,
+            burned: burn_tokens,
+            total_claimed: self.vamp_state.total_claimed,
+            swept,
+            snapshot_epoch: self.vamp_state.snapshot_epoch,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
+use anchor_spl::token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface};
+
+use crate::{
+    event::{ErrorCode, VampToppedUp},
+    instructions::{claim_with_proof::verify_snapshot_root, initialize::validate_claim_tracking},
+    state::{
+        epoch_snapshot::EpochSnapshot,
+        vamp_state::{VampState, VampStatus},
+    },
+};
+
+#[derive(Accounts)]
+pub struct TopUpVamp<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint", authority.key().as_ref(), &vamp_state.vamp_identifier.to_le_bytes()],
+        bump,
+    )]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", mint_account.key().as_ref()],
+        bump,
+        token::mint = mint_account,
+        token::authority = vamp_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: The instructions sysvar, read to find the snapshot root attestation verified by the Secp256k1 program
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    /// Archive of the epoch the top-up closes
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"epoch_snapshot", vamp_state.key().as_ref(), &vamp_state.snapshot_epoch.to_le_bytes()],
+        bump,
+        space = 8 + EpochSnapshot::INIT_SPACE,
+    )]
+    pub epoch_snapshot: Account<'info, EpochSnapshot>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TopUpVamp<'info> {
+    /// Mints the supply of a new snapshot epoch into the vault. Claims of the new epoch are
+    /// attested for `intent_id` and tracked separately, so holders can claim again for the
+    /// balance they gained since the previous snapshot. The intent and root of the closed epoch
+    /// are archived, its holders who haven't claimed yet still claim with its attestations.
+    /// A new snapshot root has to be attested by the signers, like at creation.
+    pub fn top_up_vamp(
+        &mut self,
+        amount: u64,
+        intent_id: Vec<u8>,
+        merkle_root: Option<[u8; 32]>,
+        claim_deadline: Option<i64>,
+        bumps: &TopUpVampBumps,
+    ) -> Result<()> {
+        require!(
+            self.vamp_state.status == VampStatus::Active,
+            ErrorCode::VampGraduated
+        );
+        require!(
+            amount > 0 && intent_id.len() <= 32 && intent_id != self.vamp_state.intent_id,
+            ErrorCode::InvalidTopUp
+        );
+        validate_claim_tracking(self.vamp_state.claim_tracking, &merkle_root)?;
+        if let Some(claim_deadline) = claim_deadline {
+            require!(
+                claim_deadline > Clock::get()?.unix_timestamp,
+                ErrorCode::InvalidClaimDeadline
+            );
+        }
+
+        let vamp_identifier = self.vamp_state.vamp_identifier.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"mint",
+            self.authority.key.as_ref(),
+            &vamp_identifier,
+            &[bumps.mint_account],
+        ]];
+        mint_to(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                MintTo {
+                    mint: self.mint_account.to_account_info(),
+                    to: self.vault.to_account_info(),
+                    authority: self.mint_account.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        let vamp_state = &mut self.vamp_state;
+        self.epoch_snapshot.set_inner(EpochSnapshot {
+            bump: bumps.epoch_snapshot,
+            vamp_state: vamp_state.key(),
+            snapshot_epoch: vamp_state.snapshot_epoch,
+            intent_id: std::mem::take(&mut vamp_state.intent_id),
+            merkle_root: vamp_state.merkle_root,
+            claim_deadline: vamp_state.claim_deadline,
+        });
+        vamp_state.token_supply = vamp_state
+            .token_supply
+            .checked_add(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        vamp_state.snapshot_epoch = vamp_state
+            .snapshot_epoch
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        vamp_state.intent_id = intent_id;
+        vamp_state.merkle_root = merkle_root;
+        vamp_state.claim_deadline = claim_deadline;
+        verify_snapshot_root(&self.instructions_sysvar, vamp_state)?;
+
+        emit!(VampToppedUp {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            amount,
+            token_supply: vamp_state.token_supply,
+            snapshot_epoch: vamp_state.snapshot_epoch,
+            intent_id: vamp_state.intent_id.clone(),
+            merkle_root,
+            claim_deadline,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use anchor_lang::prelude::*;
+use anchor_spl::metadata::{
+    mpl_token_metadata::types::DataV2, update_metadata_accounts_v2, Metadata, MetadataAccount,
+    UpdateMetadataAccountsV2,
+};
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{BaseStateWithExtensions, StateWithExtensions},
+    state::Mint as Token2022Mint,
+};
+use anchor_spl::token_2022_extensions::spl_pod::optional_keys::OptionalNonZeroPubkey;
+use anchor_spl::token_2022_extensions::spl_token_metadata_interface::state::{
+    Field, TokenMetadata,
+};
+use anchor_spl::token_interface::{
+    token_metadata_update_authority, token_metadata_update_field, Mint, Token2022,
+    TokenInterface, TokenMetadataUpdateAuthority, TokenMetadataUpdateField,
+};
+
+use crate::event::{ErrorCode, MetadataAuthorityTransferred, TokenMetadataUpdated};
+use crate::instructions::initialize_token_extensions::fund_mint_growth;
+use crate::state::vamp_state::VampState;
+
+/// Both metadata instructions act on the same accounts. Classic mints pass their Metaplex
+/// metadata account and program, Token-2022 mints keep the metadata in the mint itself.
+#[derive(Accounts)]
+pub struct UpdateTokenMetadata<'info> {
+    /// Pays for the mint growing when Token-2022 metadata gets longer
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    #[account(
+        mut,
+        seeds = [b"mint", authority.key().as_ref(), &vamp_state.vamp_identifier.to_le_bytes()],
+        bump,
+    )]
+    pub mint_account: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: This is safe because we're deriving the PDA, Metaplex checks the rest
+    #[account(
+        mut,
+        seeds = [b"metadata", Metadata::id().as_ref(), mint_account.key().as_ref()],
+        bump,
+        seeds::program = Metadata::id(),
+    )]
+    pub metadata_account: Option<UncheckedAccount<'info>>,
+
+    pub token_metadata_program: Option<Program<'info, Metadata>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> UpdateTokenMetadata<'info> {
+    /// Replaces the fields that are given and keeps the others
+    pub fn update_token_metadata(
+        &mut self,
+        name: Option<String>,
+        symbol: Option<String>,
+        uri: Option<String>,
+        bumps: &UpdateTokenMetadataBumps,
+    ) -> Result<()> {
+        require!(
+            name.is_some() || symbol.is_some() || uri.is_some(),
+            ErrorCode::InvalidMetadataUpdate
+        );
+
+        let (name, symbol, uri) = if self.is_token_2022() {
+            self.update_token_2022_metadata(name, symbol, uri, bumps)?
+        } else {
+            self.update_metaplex_metadata(name, symbol, uri, bumps)?
+        };
+
+        emit!(TokenMetadataUpdated {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            name,
+            symbol,
+            uri,
+        });
+        Ok(())
+    }
+
+    /// Hands the metadata to `new_authority`, after which the vamp can't update it anymore.
+    /// `None` makes the metadata immutable.
+    pub fn transfer_metadata_authority(
+        &mut self,
+        new_authority: Option<Pubkey>,
+        bumps: &UpdateTokenMetadataBumps,
+    ) -> Result<()> {
+        let vamp_identifier = self.vamp_state.vamp_identifier.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"mint",
+            self.authority.key.as_ref(),
+            &vamp_identifier,
+            &[bumps.mint_account],
+        ]];
+        let mint_info = self.mint_account.to_account_info();
+
+        if self.is_token_2022() {
+            self.token_2022_metadata()?;
+            token_metadata_update_authority(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TokenMetadataUpdateAuthority {
+                        program_id: self.token_program.to_account_info(),
+                        metadata: mint_info.clone(),
+                        current_authority: mint_info.clone(),
+                        // Only the key argument is read, this isn't an account of the instruction
+                        new_authority: mint_info,
+                    },
+                    signer_seeds,
+                ),
+                OptionalNonZeroPubkey::try_from(new_authority)?,
+            )?;
+        } else {
+            let (metadata_account, token_metadata_program) = self.metaplex_accounts()?;
+            read_metaplex_metadata(&metadata_account, &mint_info.key())?;
+            update_metadata_accounts_v2(
+                CpiContext::new_with_signer(
+                    token_metadata_program,
+                    UpdateMetadataAccountsV2 {
+                        metadata: metadata_account,
+                        update_authority: mint_info,
+                    },
+                    signer_seeds,
+                ),
+                new_authority,
+                None,
+                None,
+                new_authority.is_none().then_some(false),
+            )?;
+        }
+
+        emit!(MetadataAuthorityTransferred {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            new_authority,
+        });
+        Ok(())
+    }
+
+    fn is_token_2022(&self) -> bool {
+        self.mint_account.to_account_info().owner == &Token2022::id()
+    }
+
+    fn metaplex_accounts(&self) -> Result<(AccountInfo<'info>, AccountInfo<'info>)> {
+        match (&self.metadata_account, &self.token_metadata_program) {
+            (Some(metadata_account), Some(token_metadata_program)) => Ok((
+                metadata_account.to_account_info(),
+                token_metadata_program.to_account_info(),
+            )),
+            _ => err!(ErrorCode::InvalidMetadataAccounts),
+        }
+    }
+
+    fn token_2022_metadata(&self) -> Result<TokenMetadata> {
+        let mint_info = self.mint_account.to_account_info();
+        let data = mint_info.try_borrow_data()?;
+        let metadata = StateWithExtensions::<Token2022Mint>::unpack(&data)?
+            .get_variable_len_extension::<TokenMetadata>()?;
+        require!(
+            Option::<Pubkey>::from(metadata.update_authority) == Some(mint_info.key()),
+            ErrorCode::MetadataAuthorityTransferred
+        );
+        Ok(metadata)
+    }
+
+    fn update_metaplex_metadata(
+        &self,
+        name: Option<String>,
+        symbol: Option<String>,
+        uri: Option<String>,
+        bumps: &UpdateTokenMetadataBumps,
+    ) -> Result<(String, String, String)> {
+        let (metadata_account, token_metadata_program) = self.metaplex_accounts()?;
+        let mint_info = self.mint_account.to_account_info();
+        let current = read_metaplex_metadata(&metadata_account, &mint_info.key())?;
+
+        // Metaplex stores the strings padded with zeros to their maximum length
+        let data = DataV2 {
+            name: name.unwrap_or_else(|| current.name.trim_end_matches('\0').to_string()),
+            symbol: symbol.unwrap_or_else(|| current.symbol.trim_end_matches('\0').to_string()),
+            uri: uri.unwrap_or_else(|| current.uri.trim_end_matches('\0').to_string()),
+            seller_fee_basis_points: current.seller_fee_basis_points,
+            creators: current.creators.clone(),
+            collection: current.collection.clone(),
+            uses: current.uses.clone(),
+        };
+
+        let vamp_identifier = self.vamp_state.vamp_identifier.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"mint",
+            self.authority.key.as_ref(),
+            &vamp_identifier,
+            &[bumps.mint_account],
+        ]];
+        update_metadata_accounts_v2(
+            CpiContext::new_with_signer(
+                token_metadata_program,
+                UpdateMetadataAccountsV2 {
+                    metadata: metadata_account,
+                    update_authority: mint_info,
+                },
+                signer_seeds,
+            ),
+            None,
+            Some(data.clone()),
+            None,
+            None,
+        )?;
+
+        Ok((data.name, data.symbol, data.uri))
+    }
+
+    fn update_token_2022_metadata(
+        &self,
+        name: Option<String>,
+        symbol: Option<String>,
+        uri: Option<String>,
+        bumps: &UpdateTokenMetadataBumps,
+    ) -> Result<(String, String, String)> {
+        let mut metadata = self.token_2022_metadata()?;
+        let old_size = metadata.tlv_size_of()?;
+        let updates: Vec<(Field, String)> = [(Field::Name, name), (Field::Symbol, symbol), (Field::Uri, uri)]
+            .into_iter()
+            .filter_map(|(field, value)| value.map(|value| (field, value)))
+            .collect();
+        for (field, value) in &updates {
+            metadata.update(field.clone(), value.clone());
+        }
+
+        // Longer fields grow the mint, the rent has to be there first
+        let mint_info = self.mint_account.to_account_info();
+        let new_len = (mint_info.data_len() - old_size)
+            .checked_add(metadata.tlv_size_of()?)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        fund_mint_growth(
+            self.authority.to_account_info(),
+            mint_info.clone(),
+            self.system_program.to_account_info(),
+            new_len,
+        )?;
+
+        let vamp_identifier = self.vamp_state.vamp_identifier.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"mint",
+            self.authority.key.as_ref(),
+            &vamp_identifier,
+            &[bumps.mint_account],
+        ]];
+        for (field, value) in updates {
+            token_metadata_update_field(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TokenMetadataUpdateField {
+                        program_id: self.token_program.to_account_info(),
+                        metadata: mint_info.clone(),
+                        update_authority: mint_info.clone(),
+                    },
+                    signer_seeds,
+                ),
+                field,
+                value,
+            )?;
+        }
+
+        Ok((metadata.name, metadata.symbol, metadata.uri))
+    }
+}
+
+/// Reads the Metaplex metadata, as long as the vamp still controls it
+fn read_metaplex_metadata(metadata_account: &AccountInfo, mint: &Pubkey) -> Result<MetadataAccount> {
+    let metadata = {
+        let data = metadata_account.try_borrow_data()?;
+        MetadataAccount::try_deserialize(&mut &data[..])?
+    };
+    require!(
+        metadata.update_authority == *mint && metadata.is_mutable,
+        ErrorCode::MetadataAuthorityTransferred
+    );
+    Ok(metadata)
+}
@@ -0,0 +1,209 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    constant::BPS_DENOMINATOR,
+    event::{ErrorCode, VampingParamsUpdated},
+    instructions::initialize::VampingParams,
+    state::vamp_state::{CurveKind, VampState, VampStatus},
+};
+
+/// Higher powers lose too much precision once the supply is scaled into the Decimal range,
+/// see `calculate_claim_cost_power_curve`
+const MAX_CURVE_EXPONENT: u64 = 4;
+
+#[derive(Accounts)]
+pub struct UpdateVampingParams<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> UpdateVampingParams<'info> {
+    pub fn update_vamping_params(&mut self, vamping_params: VampingParams, lock: bool) -> Result<()> {
+        let vamp_state = &mut self.vamp_state;
+        require!(
+            vamp_state.status == VampStatus::Active,
+            ErrorCode::VampGraduated
+        );
+        require!(
+            !(vamp_state.params_locked && vamp_state.total_claimed > 0),
+            ErrorCode::VampingParamsLocked
+        );
+        // Redeems walk back the curve the claimed supply was paid on, it can't change under them
+        require!(
+            !((vamp_state.paid_claiming_enabled || vamping_params.paid_claiming_enabled)
+                && vamp_state.total_claimed > 0),
+            ErrorCode::VampingParamsLocked
+        );
+        validate_vamping_params(&vamping_params)?;
+
+        let old_params = current_vamping_params(vamp_state);
+        vamp_state.paid_claiming_enabled = vamping_params.paid_claiming_enabled;
+        vamp_state.use_bonding_curve = vamping_params.use_bonding_curve;
+        vamp_state.curve_slope = vamping_params.curve_slope;
+        vamp_state.base_price = vamping_params.base_price;
+        vamp_state.max_price = vamping_params.max_price;
+        vamp_state.flat_price_per_token = vamping_params.flat_price_per_token;
+        vamp_state.curve_kind = vamping_params.curve_kind;
+        vamp_state.curve_exponent = vamping_params.curve_exponent;
+        vamp_state.curve_step_size = vamping_params.curve_step_size;
+        vamp_state.redeem_spread_bps = vamping_params.redeem_spread_bps;
+        vamp_state.params_locked = lock;
+
+        emit!(VampingParamsUpdated {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            old_params,
+            new_params: vamping_params,
+            params_locked: lock,
+        });
+        Ok(())
+    }
+}
+
+fn current_vamping_params(vamp_state: &VampState) -> VampingParams {
+    VampingParams {
+        paid_claiming_enabled: vamp_state.paid_claiming_enabled,
+        use_bonding_curve: vamp_state.use_bonding_curve,
+        curve_slope: vamp_state.curve_slope,
+        base_price: vamp_state.base_price,
+        max_price: vamp_state.max_price,
+        flat_price_per_token: vamp_state.flat_price_per_token,
+        curve_kind: vamp_state.curve_kind,
+        curve_exponent: vamp_state.curve_exponent,
+        curve_step_size: vamp_state.curve_step_size,
+        redeem_spread_bps: vamp_state.redeem_spread_bps,
+    }
+}
+
+/// Rejects the parameter sets the claim cost calculation can't price sensibly
+pub(crate) fn validate_vamping_params(params: &VampingParams) -> Result<()> {
+    // Prices are converted into a signed Decimal mantissa
+    let max_value = i64::MAX as u64;
+    require!(
+        params.curve_slope <= max_value
+            && params.base_price <= max_value
+            && params.flat_price_per_token <= max_value,
+        ErrorCode::InvalidVampingParams
+    );
+
+    if params.use_bonding_curve {
+        match params.curve_kind {
+            CurveKind::Power => require!(
+                (1..=MAX_CURVE_EXPONENT).contains(&params.curve_exponent),
+                ErrorCode::InvalidVampingParams
+            ),
+            CurveKind::Step => require!(
+                params.curve_step_size > 0 && params.curve_step_size <= max_value,
+                ErrorCode::InvalidVampingParams
+            ),
+            CurveKind::Linear | CurveKind::Exponential => {}
+        }
+    }
+
+    require!(
+        params.redeem_spread_bps as u64 <= BPS_DENOMINATOR,
+        ErrorCode::InvalidVampingParams
+    );
+
+    let starting_price = if params.use_bonding_curve {
+        params.base_price
+    } else {
+        params.flat_price_per_token
+    };
+    if let Some(max_price) = params.max_price {
+        // A cap below the starting price would reject every claim
+        require!(
+            max_price > 0 && starting_price <= max_price,
+            ErrorCode::InvalidVampingParams
+        );
+    }
+    if params.paid_claiming_enabled {
+        require!(
+            starting_price > 0 || (params.use_bonding_curve && params.curve_slope > 0),
+            ErrorCode::InvalidVampingParams
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params_fixture() -> VampingParams {
+        VampingParams {
+            paid_claiming_enabled: true,
+            use_bonding_curve: true,
+            curve_slope: 1_000,
+            base_price: 10_000_000,
+            max_price: Some(100_000_000),
+            flat_price_per_token: 30_000_000,
+            curve_kind: CurveKind::Linear,
+            curve_exponent: 2,
+            curve_step_size: 0,
+            redeem_spread_bps: 200,
+        }
+    }
+
+    #[test]
+    fn test_validate_vamping_params() {
+        assert!(validate_vamping_params(&params_fixture()).is_ok());
+
+        let uncapped = VampingParams { max_price: None, ..params_fixture() };
+        assert!(validate_vamping_params(&uncapped).is_ok());
+
+        let cap_below_base = VampingParams { max_price: Some(1), ..params_fixture() };
+        assert!(validate_vamping_params(&cap_below_base).is_err());
+
+        let zero_cap = VampingParams { max_price: Some(0), ..params_fixture() };
+        assert!(validate_vamping_params(&zero_cap).is_err());
+
+        let flat_above_cap = VampingParams {
+            use_bonding_curve: false,
+            flat_price_per_token: 200_000_000,
+            ..params_fixture()
+        };
+        assert!(validate_vamping_params(&flat_above_cap).is_err());
+
+        let free_paid_claim = VampingParams {
+            use_bonding_curve: false,
+            flat_price_per_token: 0,
+            ..params_fixture()
+        };
+        assert!(validate_vamping_params(&free_paid_claim).is_err());
+
+        let free_claim = VampingParams {
+            paid_claiming_enabled: false,
+            ..free_paid_claim
+        };
+        assert!(validate_vamping_params(&free_claim).is_ok());
+
+        let overflowing_slope = VampingParams { curve_slope: u64::MAX, ..params_fixture() };
+        assert!(validate_vamping_params(&overflowing_slope).is_err());
+
+        let cubic = VampingParams { curve_kind: CurveKind::Power, curve_exponent: 3, ..params_fixture() };
+        assert!(validate_vamping_params(&cubic).is_ok());
+
+        let huge_power = VampingParams { curve_exponent: 10, ..cubic };
+        assert!(validate_vamping_params(&huge_power).is_err());
+
+        let stepless = VampingParams { curve_kind: CurveKind::Step, ..params_fixture() };
+        assert!(validate_vamping_params(&stepless).is_err());
+
+        let steps = VampingParams { curve_step_size: 1_000_000_000_000, ..stepless };
+        assert!(validate_vamping_params(&steps).is_ok());
+
+        let excessive_spread = VampingParams { redeem_spread_bps: 10_001, ..params_fixture() };
+        assert!(validate_vamping_params(&excessive_spread).is_err());
+    }
+}
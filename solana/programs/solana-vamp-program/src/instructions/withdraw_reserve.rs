@@ -0,0 +1,114 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    event::{ErrorCode, ReserveWithdrawn, TreasuryUpdated},
+    instructions::redeem::redeemable_liability,
+    state::vamp_state::{VampState, VampStatus},
+};
+
+#[derive(Accounts)]
+pub struct WithdrawReserve<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        constraint = authority.key() == vamp_state.authority
+            || authority.key() == vamp_state.treasury @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    /// CHECK: This is the SOL vault PDA
+    #[account(
+        mut,
+        seeds = [b"sol_vault", mint_account.key().as_ref()],
+        bump,
+    )]
+    pub sol_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Only the treasury configured in the vamp state can receive the reserve
+    #[account(
+        mut,
+        address = vamp_state.treasury @ ErrorCode::InvalidTreasury,
+    )]
+    pub treasury: UncheckedAccount<'info>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> WithdrawReserve<'info> {
+    pub fn withdraw_reserve(&mut self, amount: u64) -> Result<()> {
+        // The reserve of a graduated vamp is kept for its liquidity pool
+        require!(
+            self.vamp_state.status == VampStatus::Active,
+            ErrorCode::VampGraduated
+        );
+        // What redeeming the claimed supply would pay out stays in the reserve
+        let liability = redeemable_liability(&self.vamp_state, self.mint_account.decimals)?;
+        require!(
+            amount <= self.vamp_state.reserve_balance.saturating_sub(liability),
+            ErrorCode::InsufficientReserve
+        );
+
+        // The SOL vault is owned by this program, so the system program can't debit it.
+        // Lamports are moved directly; the rent-exempt minimum is never part of the reserve.
+        let sol_vault = self.sol_vault.to_account_info();
+        let treasury = self.treasury.to_account_info();
+        let vault_lamports = sol_vault
+            .lamports()
+            .checked_sub(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let treasury_lamports = treasury
+            .lamports()
+            .checked_add(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        **sol_vault.try_borrow_mut_lamports()? = vault_lamports;
+        **treasury.try_borrow_mut_lamports()? = treasury_lamports;
+
+        self.vamp_state.reserve_balance = self
+            .vamp_state
+            .reserve_balance
+            .checked_sub(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(ReserveWithdrawn {
+            mint_account: self.mint_account.key(),
+            authority: self.authority.key(),
+            treasury: self.treasury.key(),
+            amount,
+            reserve_balance: self.vamp_state.reserve_balance,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vamp", mint_account.key().as_ref()],
+        bump = vamp_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub vamp_state: Account<'info, VampState>,
+
+    pub mint_account: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> SetTreasury<'info> {
+    pub fn set_treasury(&mut self, treasury: Pubkey) -> Result<()> {
+        let old_treasury = self.vamp_state.treasury;
+        self.vamp_state.treasury = treasury;
+
+        emit!(TreasuryUpdated {
+            mint_account: self.mint_account.key(),
+            old_treasury,
+            new_treasury: treasury,
+        });
+        Ok(())
+    }
+}
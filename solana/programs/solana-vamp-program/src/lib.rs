@@ -4,20 +4,26 @@ declare_id!("FAyBECn6ppQgRwb5R4LryAzNic3XwsCuHakVpD1X7hFW");
 
 // Module declarations
 mod constant;
-mod event;
+pub mod event;
 mod instructions;
-mod state;
+pub mod state;
 
 // Re-exports
 use event::TokenMintCreated;
 use instructions::*;
-use instructions::initialize::VampingParams;
+use instructions::initialize::VampSetup;
+pub use instructions::initialize::VampingParams;
+pub use instructions::BatchClaim;
+use state::vamp_state::{ClaimTracking, GraduationConfig, VestingSchedule};
 
 #[program]
 pub mod solana_vamp_program {
     use super::*;
     use hex::ToHex;
 
+    /// Creates the vamp with a classic SPL mint and its Metaplex metadata.
+    /// The validator set holds at most `MAX_VALIDATORS` (2) validators.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_token_mint(
         ctx: Context<Initialize>,
         vamp_identifier: u64,
@@ -28,49 +34,109 @@ pub mod solana_vamp_program {
         token_uri: String,
         amount: u64,
         solver_public_key: Vec<u8>,
-        validator_public_key: Vec<u8>,
+        validator_set: Vec<[u8; 20]>,
+        validator_threshold: u8,
         intent_id: Vec<u8>,
-        paid_claiming_enabled: bool,
-        use_bonding_curve: bool,
-        curve_slope: u64,
-        base_price: u64,
-        max_price: u64,
-        flat_price_per_token: u64
+        source_chain_id: u64,
+        merkle_root: Option<[u8; 32]>,
+        claim_deadline: Option<i64>,
+        vesting: Option<VestingSchedule>,
+        claim_tracking: ClaimTracking,
+        vamping_params: Option<VampingParams>,
     ) -> Result<()> {
-        // Vamping parameters
-        let vamping_params = VampingParams {
-            paid_claiming_enabled,
-            use_bonding_curve,
-            curve_slope,
-            base_price,
-            max_price: Some(max_price),
-            flat_price_per_token,
-        };
-
         ctx.accounts.create_token_mint(
-            vamp_identifier,
             token_name.clone(),
             token_symbol.clone(),
             token_uri,
             amount,
-            solver_public_key,
-            validator_public_key,
-            intent_id,
+            VampSetup {
+                vamp_identifier,
+                solver_public_key,
+                validator_set,
+                validator_threshold,
+                intent_id,
+                source_chain_id,
+                merkle_root,
+                claim_deadline,
+                vesting,
+                claim_tracking,
+                vamping_params,
+            },
             &ctx.bumps,
-            Some(vamping_params)
         )?;
 
-        let hex_address = format!(
-            "0x{}",
-            token_erc20_address.encode_hex::<String>()
-        );
+        emit!(TokenMintCreated {
+            mint_account: ctx.accounts.mint_account.key(),
+            token_name,
+            token_symbol,
+            token_erc20_address: format!("0x{}", token_erc20_address.encode_hex::<String>()),
+            amount,
+            vamp_state: ctx.accounts.vamp_state.key(),
+            authority: ctx.accounts.authority.key(),
+            intent_id: ctx.accounts.vamp_state.intent_id.clone(),
+            merkle_root,
+            claim_deadline,
+            token_decimals,
+        });
+        Ok(())
+    }
+
+    /// Creates the vamp with a Token-2022 mint carrying its own metadata.
+    /// The validator set holds at most `MAX_VALIDATORS` (2) validators.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_token_extensions_mint(
+        ctx: Context<InitializeTokenExtensions>,
+        vamp_identifier: u64,
+        token_decimals: u8,
+        token_name: String,
+        token_symbol: String,
+        token_erc20_address: Vec<u8>,
+        token_uri: String,
+        amount: u64,
+        solver_public_key: Vec<u8>,
+        validator_set: Vec<[u8; 20]>,
+        validator_threshold: u8,
+        intent_id: Vec<u8>,
+        source_chain_id: u64,
+        merkle_root: Option<[u8; 32]>,
+        claim_deadline: Option<i64>,
+        vesting: Option<VestingSchedule>,
+        claim_tracking: ClaimTracking,
+        vamping_params: Option<VampingParams>,
+    ) -> Result<()> {
+        ctx.accounts.create_token_extensions_mint(
+            token_name.clone(),
+            token_symbol.clone(),
+            token_uri,
+            amount,
+            VampSetup {
+                vamp_identifier,
+                solver_public_key,
+                validator_set,
+                validator_threshold,
+                intent_id,
+                source_chain_id,
+                merkle_root,
+                claim_deadline,
+                vesting,
+                claim_tracking,
+                vamping_params,
+            },
+            &ctx.bumps,
+        )?;
 
         emit!(TokenMintCreated {
             mint_account: ctx.accounts.mint_account.key(),
             token_name,
             token_symbol,
-            token_erc20_address: hex_address,
-            amount: amount
+            token_erc20_address: format!("0x{}", token_erc20_address.encode_hex::<String>()),
+            amount,
+            vamp_state: ctx.accounts.vamp_state.key(),
+            authority: ctx.accounts.authority.key(),
+            intent_id: ctx.accounts.vamp_state.intent_id.clone(),
+            merkle_root,
+            claim_deadline,
+            token_decimals,
         });
         Ok(())
     }
@@ -79,10 +145,148 @@ pub mod solana_vamp_program {
         ctx: Context<Claim>,
         eth_address: [u8; 20],
         balance: u64,
-        solver_individual_balance_sig: [u8; 65],
-        validator_individual_balance_sig: [u8; 65],
-        ownership_sig: [u8; 65],
+        max_total_cost: Option<u64>,
+    ) -> Result<()> {
+        buy_claim_tokens(ctx, eth_address, balance, max_total_cost)
+    }
+
+    pub fn claim_with_proof(
+        ctx: Context<Claim>,
+        eth_address: [u8; 20],
+        balance: u64,
+        index: u64,
+        proof: Vec<[u8; 32]>,
+        max_total_cost: Option<u64>,
+    ) -> Result<()> {
+        claim_tokens_with_proof(ctx, eth_address, balance, index, proof, max_total_cost)
+    }
+
+    pub fn quote_claim(ctx: Context<QuoteClaim>, balance: u64, referred: bool) -> Result<u64> {
+        ctx.accounts.quote_claim(balance, referred)
+    }
+
+    pub fn withdraw_reserve(ctx: Context<WithdrawReserve>, amount: u64) -> Result<()> {
+        ctx.accounts.withdraw_reserve(amount)
+    }
+
+    pub fn set_treasury(ctx: Context<SetTreasury>, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.set_treasury(treasury)
+    }
+
+    pub fn update_vamping_params(
+        ctx: Context<UpdateVampingParams>,
+        vamping_params: VampingParams,
+        lock: bool,
+    ) -> Result<()> {
+        ctx.accounts.update_vamping_params(vamping_params, lock)
+    }
+
+    pub fn redeem(ctx: Context<Redeem>, amount: u64, min_payout: Option<u64>) -> Result<()> {
+        ctx.accounts.redeem(amount, min_payout)
+    }
+
+    pub fn sweep_unclaimed(ctx: Context<SweepUnclaimed>, burn: bool) -> Result<()> {
+        ctx.accounts.sweep_unclaimed(burn)
+    }
+
+    pub fn pause(ctx: Context<PauseVamp>) -> Result<()> {
+        ctx.accounts.set_paused(true)
+    }
+
+    pub fn unpause(ctx: Context<PauseVamp>) -> Result<()> {
+        ctx.accounts.set_paused(false)
+    }
+
+    pub fn initialize_global_config(ctx: Context<InitializeGlobalConfig>) -> Result<()> {
+        ctx.accounts.initialize_global_config(&ctx.bumps)
+    }
+
+    pub fn set_emergency_freeze(ctx: Context<SetEmergencyFreeze>, emergency_freeze: bool) -> Result<()> {
+        ctx.accounts.set_emergency_freeze(emergency_freeze)
+    }
+
+    pub fn set_protocol_fees(
+        ctx: Context<SetProtocolFees>,
+        protocol_treasury: Pubkey,
+        protocol_fee_bps: u16,
+        referral_fee_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.set_protocol_fees(protocol_treasury, protocol_fee_bps, referral_fee_bps)
+    }
+
+    /// Replaces the solver and the validators, at most `MAX_VALIDATORS` (2) of them
+    pub fn rotate_signers(
+        ctx: Context<RotateSigners>,
+        solver_public_key: Vec<u8>,
+        validator_set: Vec<[u8; 20]>,
+        validator_threshold: u8,
+        grace_period: i64,
+    ) -> Result<()> {
+        ctx.accounts.rotate_signers(solver_public_key, validator_set, validator_threshold, grace_period)
+    }
+
+    pub fn apply_signer_rotation(ctx: Context<RotateSigners>) -> Result<()> {
+        ctx.accounts.apply_signer_rotation()
+    }
+
+    pub fn cancel_signer_rotation(ctx: Context<RotateSigners>) -> Result<()> {
+        ctx.accounts.cancel_signer_rotation()
+    }
+
+    pub fn set_rotation_delay(ctx: Context<RotateSigners>, rotation_delay: i64) -> Result<()> {
+        ctx.accounts.set_rotation_delay(rotation_delay)
+    }
+
+    pub fn release(ctx: Context<Release>) -> Result<()> {
+        ctx.accounts.release()
+    }
+
+    pub fn top_up_vamp(
+        ctx: Context<TopUpVamp>,
+        amount: u64,
+        intent_id: Vec<u8>,
+        merkle_root: Option<[u8; 32]>,
+        claim_deadline: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts.top_up_vamp(amount, intent_id, merkle_root, claim_deadline, &ctx.bumps)
+    }
+
+    pub fn create_claim_bitmap(ctx: Context<CreateClaimBitmap>, page: u32, holders: u32) -> Result<()> {
+        ctx.accounts.create_claim_bitmap(page, holders, &ctx.bumps)
+    }
+
+    pub fn claim_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimBatch<'info>>,
+        claims: Vec<BatchClaim>,
+    ) -> Result<()> {
+        ctx.accounts.claim_batch(ctx.remaining_accounts, &claims)
+    }
+
+    pub fn set_graduation(ctx: Context<SetGraduation>, graduation: Option<GraduationConfig>) -> Result<()> {
+        ctx.accounts.set_graduation(graduation)
+    }
+
+    pub fn graduate(ctx: Context<Graduate>) -> Result<()> {
+        ctx.accounts.graduate(&ctx.bumps)
+    }
+
+    pub fn withdraw_graduation_liquidity(ctx: Context<WithdrawGraduationLiquidity>) -> Result<()> {
+        ctx.accounts.withdraw_graduation_liquidity()
+    }
+
+    pub fn update_token_metadata(
+        ctx: Context<UpdateTokenMetadata>,
+        name: Option<String>,
+        symbol: Option<String>,
+        uri: Option<String>,
+    ) -> Result<()> {
+        ctx.accounts.update_token_metadata(name, symbol, uri, &ctx.bumps)
+    }
+
+    pub fn transfer_metadata_authority(
+        ctx: Context<UpdateTokenMetadata>,
+        new_authority: Option<Pubkey>,
     ) -> Result<()> {
-        buy_claim_tokens(ctx, eth_address, balance, solver_individual_balance_sig, validator_individual_balance_sig, ownership_sig)
+        ctx.accounts.transfer_metadata_authority(new_authority, &ctx.bumps)
     }
 }
@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+
+use crate::event::ErrorCode;
+
+/// Holders tracked by one bitmap page
+pub const CLAIM_BITMAP_BITS: u32 = 65_536;
+
+/// Claimed flags of up to `CLAIM_BITMAP_BITS` holders of a snapshot epoch, a PDA at seeds
+/// [b"claim_bitmap", vamp_state, page in little endian, snapshot epoch seed]. The holder at
+/// Merkle leaf `index` is bit `index % CLAIM_BITMAP_BITS` of page `index / CLAIM_BITMAP_BITS`.
+#[account(zero_copy)]
+#[repr(C)]
+pub struct ClaimBitmap {
+    pub vamp_state: Pubkey,
+    pub bits: [u8; CLAIM_BITMAP_BITS as usize / 8],
+    pub snapshot_epoch: u32,
+    pub page: u32,
+    pub holders: u32,                // Leaves of the snapshot in this page, the padding leaves of the tree are left out
+    pub bump: u8,
+    pub _padding: [u8; 3],
+}
+
+impl ClaimBitmap {
+    /// Page and bit tracking the holder at a Merkle leaf index
+    pub fn position(index: u64) -> Result<(u32, u32)> {
+        let page = u32::try_from(index / CLAIM_BITMAP_BITS as u64)
+            .map_err(|_| ErrorCode::InvalidClaimIndex)?;
+        Ok((page, (index % CLAIM_BITMAP_BITS as u64) as u32))
+    }
+
+    /// Flags the holder as claimed, fails if it already was
+    pub fn set_claimed(&mut self, bit: u32) -> Result<()> {
+        require!(bit < self.holders, ErrorCode::InvalidClaimIndex);
+        let byte = &mut self.bits[bit as usize / 8];
+        let mask = 1u8 << (bit % 8);
+        require!(*byte & mask == 0, ErrorCode::TokensAlreadyClaimed);
+        *byte |= mask;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        assert_eq!(ClaimBitmap::position(0), Ok((0, 0)));
+        assert_eq!(ClaimBitmap::position(65_535), Ok((0, 65_535)));
+        assert_eq!(ClaimBitmap::position(65_536), Ok((1, 0)));
+        assert_eq!(ClaimBitmap::position(100_000), Ok((1, 34_464)));
+        assert_eq!(
+            ClaimBitmap::position(u64::MAX),
+            Err(ErrorCode::InvalidClaimIndex.into())
+        );
+    }
+
+    #[test]
+    fn test_set_claimed() {
+        let mut bitmap = Box::new(ClaimBitmap {
+            vamp_state: Pubkey::default(),
+            bits: [0; CLAIM_BITMAP_BITS as usize / 8],
+            snapshot_epoch: 0,
+            page: 0,
+            holders: 10,
+            bump: 0,
+            _padding: [0; 3],
+        });
+
+        assert_eq!(bitmap.set_claimed(9), Ok(()));
+        assert_eq!(bitmap.set_claimed(8), Ok(()));
+        assert_eq!(bitmap.bits[1], 0b11);
+        assert_eq!(bitmap.set_claimed(9), Err(ErrorCode::TokensAlreadyClaimed.into()));
+
+        // Indexes past the holders are padding leaves repeating the last holder
+        assert_eq!(bitmap.set_claimed(10), Err(ErrorCode::InvalidClaimIndex.into()));
+    }
+}
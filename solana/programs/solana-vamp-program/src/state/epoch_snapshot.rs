@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+/// Intent and snapshot root of a closed snapshot epoch, a PDA at seeds
+/// [b"epoch_snapshot", vamp_state, snapshot epoch in little endian]. Written by the top-up
+/// closing the epoch, so the balances attested for it stay claimable until its own deadline.
+#[account]
+#[derive(InitSpace)]
+pub struct EpochSnapshot {
+    pub bump: u8,
+    pub vamp_state: Pubkey,
+    pub snapshot_epoch: u32,
+    #[max_len(32)]
+    pub intent_id: Vec<u8>,
+    pub merkle_root: Option<[u8; 32]>,
+    pub claim_deadline: Option<i64>,
+}
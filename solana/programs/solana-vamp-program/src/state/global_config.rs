@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+/// Program-wide settings, a single PDA at seeds [b"config"]
+#[account]
+#[derive(InitSpace)]
+pub struct GlobalConfig {
+    pub bump: u8,
+    pub admin: Pubkey,               // Account allowed to change the program-wide settings
+    pub emergency_freeze: bool,      // Blocks claims on every vamp while set
+    pub protocol_treasury: Pubkey,   // Receives the protocol share of paid claims
+    pub protocol_fee_bps: u16,       // Fee on the curve cost of every claim, paid to the protocol treasury
+    pub referral_fee_bps: u16,       // Fee on the curve cost of a claim, paid to the referrer passed with the claim
+}
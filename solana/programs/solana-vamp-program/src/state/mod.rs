@@ -1 +1,5 @@
 pub mod vamp_state;
+pub mod global_config;
+pub mod vesting_state;
+pub mod claim_bitmap;
+pub mod epoch_snapshot;
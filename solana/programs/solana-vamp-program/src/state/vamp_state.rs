@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
 
+use crate::constant::MAX_VALIDATORS;
+
 #[account]
 #[derive(InitSpace)]
 pub struct VampState {
@@ -7,8 +9,8 @@ pub struct VampState {
     pub mint: Pubkey,
     #[max_len(20)]
     pub solver_public_key: Vec<u8>,
-    #[max_len(20)]
-    pub validator_public_key: Vec<u8>,
+    #[max_len(MAX_VALIDATORS)]
+    pub validator_set: Vec<[u8; 20]>, // Ethereum addresses of the validators
     pub vamp_identifier: u64,
     #[max_len(32)]
     pub intent_id: Vec<u8>,
@@ -24,6 +26,119 @@ pub struct VampState {
     pub use_bonding_curve: bool,     // Whether to use a bonding curve
     pub flat_price_per_token: u64,   // Flat price per token in lamports
     pub paid_claiming_enabled: bool, // Whether paid claiming is enabled
+    pub authority: Pubkey,           // Account that created the vamp
+    pub treasury: Pubkey,            // Destination for SOL withdrawn from the reserve
+    pub merkle_root: Option<[u8; 32]>, // Snapshot root, when set claims are proven against it
+    pub claim_deadline: Option<i64>, // Unix timestamp after which claims are closed
+    pub params_locked: bool,         // Pricing can't be updated once claiming has started
+    pub curve_kind: CurveKind,       // Shape of the bonding curve when it's used
+    pub curve_step_size: u64,        // Width of a price step in base units, for the step curve
+    pub redeem_spread_bps: u16,      // Share of the curve payout kept in the reserve on redeem
+    pub paused: bool,                // Claims are rejected while the authority has the vamp paused
+    pub signer_epoch: u32,           // Incremented by every applied key rotation, signed into the attestations
+    #[max_len(20)]
+    pub previous_solver_public_key: Vec<u8>,
+    #[max_len(MAX_VALIDATORS)]
+    pub previous_validator_set: Vec<[u8; 20]>,
+    pub previous_signers_valid_until: Option<i64>, // End of the grace period for the rotated out keys
+    pub validator_threshold: u8,     // Distinct validator signatures a signed claim needs
+    pub source_chain_id: u64,        // EVM chain of the vamped token, part of the EIP-712 signing domain
+    pub vesting: Option<VestingSchedule>, // When set, claimed tokens unlock over time and are released from the vault
+    pub vesting_locked: u64,         // Claimed tokens still held in the vault for their vesting
+    pub snapshot_epoch: u32,         // Incremented by every top-up, claims are tracked per epoch
+    pub claim_tracking: ClaimTracking, // How claimed holders are recorded, fixed at creation
+    pub protocol_fees_paid: u64,     // Lamports of the claim payments routed to the protocol treasury
+    pub referral_fees_paid: u64,     // Lamports of the claim payments routed to referrers
+    pub graduation: Option<GraduationConfig>, // When set, the vamp can graduate once its thresholds are reached
+    pub status: VampStatus,          // Lifecycle of the vamp, claims stop once it graduated
+    pub rotation_delay: i64,         // Seconds a signer rotation waits before it can be applied, zero applies it at once
+    pub pending_rotation: Option<SignerRotation>, // Signer rotation waiting for the rotation delay
+    pub previous_validator_threshold: u8, // Signatures the rotated out validator set needs on its own
+    pub swept_epoch: Option<u32>,    // Last epoch whose unclaimed tokens were swept, claims of it and earlier epochs are refused
+}
+
+impl VampState {
+    /// Extra seed of the per-holder claim accounts. Empty for the initial snapshot,
+    /// so its accounts keep the addresses they had before top-ups existed.
+    pub fn snapshot_epoch_seed(&self) -> Vec<u8> {
+        snapshot_epoch_seed(self.snapshot_epoch)
+    }
+}
+
+pub fn snapshot_epoch_seed(snapshot_epoch: u32) -> Vec<u8> {
+    if snapshot_epoch == 0 {
+        vec![]
+    } else {
+        snapshot_epoch.to_le_bytes().to_vec()
+    }
+}
+
+/// Price function of the bonding curve, x is the number of tokens claimed so far
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CurveKind {
+    /// base_price + curve_slope * x, charged per whole token
+    Linear,
+    /// base_price + curve_slope * x^curve_exponent
+    Power,
+    /// base_price * e^(curve_slope * x)
+    Exponential,
+    /// base_price + curve_slope * floor(x / curve_step_size)
+    Step,
+}
+
+/// Record of the holders who already claimed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum ClaimTracking {
+    /// A `ClaimState` account per holder and snapshot epoch
+    Accounts,
+    /// One bit per holder in `ClaimBitmap` pages, indexed by the holder's Merkle leaf.
+    /// Needs a snapshot root, as the proof is what binds a holder to its index.
+    Bitmap,
+}
+
+/// Lifecycle of a vamp
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum VampStatus {
+    /// Claims, redeems and reserve withdrawals are open
+    Active,
+    /// Claiming is over, the reserve SOL and the LP allocation are kept to seed a liquidity pool
+    Graduated,
+}
+
+/// Thresholds at which a vamp can graduate, every threshold that is set has to be reached
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct GraduationConfig {
+    pub reserve_threshold: Option<u64>, // Lamports the reserve has to hold
+    pub claimed_threshold: Option<u64>, // Base units the holders have to have claimed
+    pub lp_allocation: u64,             // Base units minted for the liquidity pool on graduation
+}
+
+impl GraduationConfig {
+    pub fn is_reached(&self, reserve_balance: u64, total_claimed: u64) -> bool {
+        reserve_balance >= self.reserve_threshold.unwrap_or(0)
+            && total_claimed >= self.claimed_threshold.unwrap_or(0)
+    }
+}
+
+/// New solver and validator keys, queued by `rotate_signers` while a rotation delay is set
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct SignerRotation {
+    #[max_len(20)]
+    pub solver_public_key: Vec<u8>,
+    #[max_len(MAX_VALIDATORS)]
+    pub validator_set: Vec<[u8; 20]>,
+    pub validator_threshold: u8,
+    pub grace_period: i64,  // Seconds the keys being replaced stay trusted once the rotation is applied
+    pub executable_at: i64, // Unix timestamp from which the rotation can be applied
+}
+
+/// Linear unlock of the claimed tokens between start_time and start_time + duration.
+/// Nothing unlocks before the cliff, at the cliff the linearly accrued amount unlocks at once.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct VestingSchedule {
+    pub start_time: i64,     // Unix timestamp the vesting starts accruing from
+    pub cliff_duration: i64, // Seconds after start_time before anything unlocks
+    pub duration: i64,       // Seconds after start_time when everything is unlocked
 }
 
 #[account]
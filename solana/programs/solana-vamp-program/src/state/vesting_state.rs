@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+/// Tokens of a holder claimed from a vamp with a vesting schedule,
+/// a PDA at seeds [b"vesting", vamp_state, eth_address, snapshot epoch seed]
+#[account]
+#[derive(InitSpace)]
+pub struct VestingState {
+    pub bump: u8,
+    pub vamp_state: Pubkey,
+    pub eth_address: [u8; 20],
+    pub beneficiary: Pubkey,         // Token account the tokens are released to
+    pub total_amount: u64,           // Claimed balance
+    pub released_amount: u64,        // Part of the balance already transferred to the beneficiary
+    pub snapshot_epoch: u32,         // Epoch of the vamp the balance was claimed in
+}
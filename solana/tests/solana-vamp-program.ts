@@ -1,13 +1,15 @@
 import * as anchor from "@coral-xyz/anchor";
 import { ethers } from "ethers";
 import { Program } from "@coral-xyz/anchor";
-import { ComputeBudgetProgram, PublicKey } from "@solana/web3.js";
+import { ComputeBudgetProgram, PublicKey, Secp256k1Program, SYSVAR_INSTRUCTIONS_PUBKEY, TransactionInstruction } from "@solana/web3.js";
 import { assert } from "chai";
 import { SolanaVampProgram } from "../target/types/solana_vamp_program";
 import {
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
+  getTokenMetadata,
   createAssociatedTokenAccountInstruction
 } from "@solana/spl-token";
 import { BN } from "@coral-xyz/anchor";
@@ -24,6 +26,51 @@ const claimerKeypair = anchor.web3.Keypair.generate();
 
 const vampIdBase = Date.now() * 10;
 
+const INTENT_ID = Buffer.from([17, 17, 17, 17, 17, 17, 17, 17, 34, 34, 34, 34, 34, 34, 34, 34, 119, 119, 119, 119, 119, 119, 119, 119, 153, 153, 153, 153, 153, 153, 153, 153]);
+const SOURCE_CHAIN_ID = 1;
+// Signer and holder keys from vamping_data_mock
+const SOLVER_PRIVATE_KEY = "0x9aa4451744ed6f2e3eeee95923c8c5323d86a41315114961e5cabac111719c64";
+const VALIDATOR_PRIVATE_KEY = "0xc9927bc21d1c962ee9a4f0634b49868ab80cb1b3f3522881849a5e81ca21edb0";
+const OWNER_PRIVATE_KEY = "0xfc813315c55817d4fb1396dcf772e1dfa84b8d3328713a6647930ec1983a67cf";
+const SECOND_OWNER_PRIVATE_KEY = "0xa5b69751153da2de176da6dc42934799fe0403cf97c67efe223d51ef20c98950";
+// Signer addresses the test vamps are created with
+const SOLVER_ADDRESS = [249, 139, 130, 139, 56, 155, 239, 78, 187, 181, 145, 28, 161, 126, 79, 121, 137, 201, 6, 141];
+const VALIDATOR_ADDRESS = [139, 37, 237, 6, 226, 22, 85, 63, 141, 66, 101, 153, 96, 97, 176, 160, 101, 175, 163, 92];
+
+// EIP-712 domain and types, must match balance_util
+const EIP712_DOMAIN = {
+  name: "vamp.fun",
+  version: "1",
+  chainId: SOURCE_CHAIN_ID,
+  salt: PROGRAM_ID.toBuffer(),
+};
+const BALANCE_CLAIM_TYPES = {
+  BalanceClaim: [
+    { name: "holder", type: "address" },
+    { name: "amount", type: "uint256" },
+    { name: "intentId", type: "bytes" },
+    { name: "signerEpoch", type: "uint32" },
+  ],
+};
+const CLAIM_OWNERSHIP_TYPES = {
+  ClaimOwnership: [
+    { name: "holder", type: "address" },
+    { name: "amount", type: "uint256" },
+    { name: "intentId", type: "bytes" },
+    { name: "recipient", type: "bytes32" },
+  ],
+};
+const SNAPSHOT_ROOT_TYPES = {
+  SnapshotRoot: [
+    { name: "root", type: "bytes32" },
+    { name: "intentId", type: "bytes" },
+    { name: "signerEpoch", type: "uint32" },
+  ],
+};
+
+// A signature with the encoded typed data, the Secp256k1 program checks it over its keccak
+type TypedSignature = { typedData: Uint8Array; signature: number[] };
+
 describe("solana-vamp-project", () => {
   const authority = provider.wallet.publicKey;
 
@@ -107,7 +154,13 @@ describe("solana-vamp-project", () => {
       PROGRAM_ID
     );
 
+    const [globalConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      PROGRAM_ID
+    );
+
     return {
+      globalConfig,
       metadataAccount,
       vampState,
       vampStateBump,
@@ -162,14 +215,75 @@ describe("solana-vamp-project", () => {
     return hexToBytes(getSecondOwnerAddress());
   }
 
+  // "\x19\x01" || domainSeparator || hashStruct(message), see balance_util::get_balance_claim_typed_data
+  async function signTypedData(privateKey: string, types: Record<string, ethers.TypedDataField[]>, value: Record<string, any>): Promise<TypedSignature> {
+    const signature = await new ethers.Wallet(privateKey).signTypedData(EIP712_DOMAIN, types, value);
+    const typedData = ethers.getBytes(ethers.concat([
+      "0x1901",
+      ethers.TypedDataEncoder.hashDomain(EIP712_DOMAIN),
+      ethers.TypedDataEncoder.from(types).hash(value),
+    ]));
+    return { typedData, signature: Array.from(ethers.getBytes(signature)) };
+  }
+
+  // Solver and validator sign the same BalanceClaim, in the signer epoch of the vamp's keys
+  function signBalance(privateKey: string, ethAddress: string, amount: BN, intentId: Buffer = INTENT_ID, signerEpoch = 0): Promise<TypedSignature> {
+    return signTypedData(privateKey, BALANCE_CLAIM_TYPES, {
+      holder: ethAddress,
+      amount: amount.toString(),
+      intentId,
+      signerEpoch,
+    });
+  }
+
+  // The owner signs over the wallet receiving the tokens
+  function signOwnership(privateKey: string, ethAddress: string, amount: BN, destinationOwner: PublicKey, intentId: Buffer = INTENT_ID): Promise<TypedSignature> {
+    return signTypedData(privateKey, CLAIM_OWNERSHIP_TYPES, {
+      holder: ethAddress,
+      amount: amount.toString(),
+      intentId,
+      recipient: destinationOwner.toBuffer(),
+    });
+  }
+
+  // Solver and validator attest the Merkle root a vamp is created or topped up with
+  function signSnapshotRoot(privateKey: string, root: number[], intentId: Buffer = INTENT_ID, signerEpoch = 0): Promise<TypedSignature> {
+    return signTypedData(privateKey, SNAPSHOT_ROOT_TYPES, {
+      root: Uint8Array.from(root),
+      intentId,
+      signerEpoch,
+    });
+  }
+
+  // A signature checked by the Secp256k1 program, the vamp program reads the result
+  function secp256k1Instruction(ethAddress: number[] | Uint8Array, signature: TypedSignature, instructionIndex: number): TransactionInstruction {
+    return Secp256k1Program.createInstructionWithEthAddress({
+      ethAddress: Buffer.from(ethAddress),
+      message: Buffer.from(signature.typedData),
+      signature: Buffer.from(signature.signature.slice(0, 64)),
+      recoveryId: signature.signature[64] - 27,
+      instructionIndex,
+    });
+  }
+
+  // The signature instructions preceding a claim, in transaction order
+  function claimSignatureInstructions(ethAddress: string, solverSignature: TypedSignature, validatorSignature: TypedSignature, ownerSignature: TypedSignature): TransactionInstruction[] {
+    return [
+      secp256k1Instruction(SOLVER_ADDRESS, solverSignature, 0),
+      secp256k1Instruction(VALIDATOR_ADDRESS, validatorSignature, 1),
+      secp256k1Instruction(hexToBytes(ethAddress), ownerSignature, 2),
+    ];
+  }
+
   async function getSignatures() {
-    const solverSignature = [251, 190, 51, 170, 61, 104, 94, 173, 134, 86, 195, 233, 114, 39, 131, 218, 205, 35, 184, 80, 233, 53, 220, 244, 27, 165, 216, 133, 6, 251, 209, 206, 62, 148, 200, 51, 176, 66, 113, 38, 158, 246, 60, 234, 141, 183, 42, 176, 53, 65, 143, 195, 84, 99, 162, 156, 57, 192, 188, 82, 3, 23, 55, 169, 27];
-    const validatorSignature = [132, 102, 82, 207, 139, 9, 105, 132, 111, 194, 73, 232, 249, 93, 122, 112, 80, 215, 153, 195, 146, 169, 161, 84, 195, 61, 80, 124, 160, 220, 174, 148, 91, 127, 181, 185, 19, 26, 125, 186, 208, 87, 72, 6, 210, 252, 242, 117, 76, 4, 174, 63, 192, 211, 223, 144, 225, 206, 40, 241, 224, 119, 94, 225, 27];
-    const ownerSignature = [140, 92, 134, 184, 0, 228, 15, 165, 64, 112, 11, 199, 184, 110, 96, 93, 125, 4, 68, 147, 124, 176, 160, 51, 76, 86, 4, 248, 101, 100, 3, 147, 9, 252, 21, 198, 4, 40, 200, 2, 43, 44, 193, 163, 224, 105, 113, 21, 65, 218, 235, 207, 125, 43, 216, 68, 106, 155, 15, 99, 210, 221, 127, 220, 28];
+    const solverSignature = await signBalance(SOLVER_PRIVATE_KEY, getOwnerAddress(), new BN(1_000_000_000));
+    const validatorSignature = await signBalance(VALIDATOR_PRIVATE_KEY, getOwnerAddress(), new BN(1_000_000_000));
 
-    const secondSolverSignature = [78, 69, 141, 74, 57, 150, 183, 190, 149, 211, 64, 137, 152, 192, 166, 69, 240, 192, 144, 42, 246, 182, 91, 60, 81, 207, 59, 28, 197, 183, 143, 243, 68, 94, 88, 238, 235, 148, 194, 195, 190, 154, 174, 48, 60, 253, 215, 248, 164, 228, 77, 56, 10, 43, 37, 16, 193, 82, 19, 205, 168, 244, 53, 121, 27];
-    const secondValidatorSignature = [90, 241, 93, 132, 20, 114, 46, 241, 201, 99, 229, 113, 223, 183, 21, 128, 36, 101, 179, 154, 32, 182, 75, 177, 11, 238, 75, 187, 173, 98, 114, 202, 9, 57, 143, 30, 39, 240, 127, 203, 251, 20, 185, 172, 200, 44, 236, 192, 243, 162, 243, 210, 208, 23, 108, 110, 7, 136, 227, 17, 1, 2, 74, 191, 28];
-    const secondOwnerSignature = [78, 179, 178, 224, 98, 52, 7, 40, 55, 149, 95, 61, 239, 74, 75, 217, 238, 76, 2, 66, 198, 137, 90, 68, 88, 44, 57, 56, 170, 84, 194, 169, 110, 197, 220, 4, 155, 3, 206, 205, 197, 138, 136, 15, 24, 103, 149, 69, 29, 59, 5, 23, 62, 142, 204, 150, 232, 64, 160, 214, 112, 178, 106, 48, 27];
+    const secondSolverSignature = await signBalance(SOLVER_PRIVATE_KEY, getSecondOwnerAddress(), new BN(3_000_000_000));
+    const secondValidatorSignature = await signBalance(VALIDATOR_PRIVATE_KEY, getSecondOwnerAddress(), new BN(3_000_000_000));
+
+    const ownerSignature = await signOwnership(OWNER_PRIVATE_KEY, getOwnerAddress(), new BN(1_000_000_000), claimerKeypair.publicKey);
+    const secondOwnerSignature = await signOwnership(SECOND_OWNER_PRIVATE_KEY, getSecondOwnerAddress(), new BN(3_000_000_000), claimerKeypair.publicKey);
 
     return {
       solverSignature,
@@ -196,6 +310,30 @@ describe("solana-vamp-project", () => {
 }
   */
 
+  // The global config is a singleton, so it's only created on a fresh validator
+  before(async () => {
+    const accounts = await setupInitAccounts(authority);
+    const existing = await provider.connection.getAccountInfo(accounts.globalConfig);
+    if (existing) {
+      return;
+    }
+
+    const [programData] = anchor.web3.PublicKey.findProgramAddressSync(
+      [PROGRAM_ID.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    await program.methods
+      .initializeGlobalConfig()
+      .accounts({
+        admin: authority,
+        globalConfig: accounts.globalConfig,
+        program: PROGRAM_ID,
+        programData,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+  });
+
   // Test cases
   it("Initializes Vamp State and Mints Token", async () => {
     const accounts = await setupInitAccounts(authority);
@@ -211,14 +349,26 @@ describe("solana-vamp-project", () => {
           "https://example.com/token/1",  // Token URI
           new BN(312012000000000),  // Amount
           Buffer.from([249, 139, 130, 139, 56, 155, 239, 78, 187, 181, 145, 28, 161, 126, 79, 121, 137, 201, 6, 141]),  // Solver Public Key
-          Buffer.from([139, 37, 237, 6, 226, 22, 85, 63, 141, 66, 101, 153, 96, 97, 176, 160, 101, 175, 163, 92]),  // Validator Public Key
+          [[139, 37, 237, 6, 226, 22, 85, 63, 141, 66, 101, 153, 96, 97, 176, 160, 101, 175, 163, 92]],  // Validator set
+          1,  // Validator threshold
           Buffer.from([17, 17, 17, 17, 17, 17, 17, 17, 34, 34, 34, 34, 34, 34, 34, 34, 119, 119, 119, 119, 119, 119, 119, 119, 153, 153, 153, 153, 153, 153, 153, 153]),  // Intent ID
-          true,  // Paid Claim Enabled
-          true,  // Use Bonding Curve
-          new BN(1_000),  // Curve slope, => 1e-6
-          new BN(10_000_000), // Base Price, 0.01 SOL
-          new BN(100_000_000), // Max Price, 0.1 SOL
-          new BN(30_000_000),  // Flat Price if Bonding Curve isn't used
+          new BN(SOURCE_CHAIN_ID),  // Source chain ID
+          null,  // Merkle root, per-holder signatures are used when absent
+          null,  // Claim deadline, claims stay open when absent
+          null,  // Vesting schedule, claimed tokens unlock at once when absent
+          { accounts: {} },  // Claim tracking, a claim state account per holder
+          {
+            paidClaimingEnabled: true,
+            useBondingCurve: true,
+            curveSlope: new BN(1_000),  // => 1e-6
+            basePrice: new BN(10_000_000),  // 0.01 SOL
+            maxPrice: new BN(100_000_000),  // 0.1 SOL
+            flatPricePerToken: new BN(30_000_000),  // Used without the bonding curve
+            curveKind: { linear: {} },
+            curveExponent: new BN(2),  // Used by the power curve
+            curveStepSize: new BN(0),  // Used by the step curve
+            redeemSpreadBps: 0,
+          },  // Vamping params
         )
         .accounts({
           authority,
@@ -281,14 +431,26 @@ describe("solana-vamp-project", () => {
         "https://example.com/token/1",  // Token URI
         new BN(312012000000000),  // Amount
         Buffer.from([249, 139, 130, 139, 56, 155, 239, 78, 187, 181, 145, 28, 161, 126, 79, 121, 137, 201, 6, 141]),  // Solver Public Key
-        Buffer.from([139, 37, 237, 6, 226, 22, 85, 63, 141, 66, 101, 153, 96, 97, 176, 160, 101, 175, 163, 92]),  // Validator Public Key
+        [[139, 37, 237, 6, 226, 22, 85, 63, 141, 66, 101, 153, 96, 97, 176, 160, 101, 175, 163, 92]],  // Validator set
+        1,  // Validator threshold
         Buffer.from([17, 17, 17, 17, 17, 17, 17, 17, 34, 34, 34, 34, 34, 34, 34, 34, 119, 119, 119, 119, 119, 119, 119, 119, 153, 153, 153, 153, 153, 153, 153, 153]),  // Intent ID
-        true,  // Paid Claim Enabled
-        true,  // Use Bonding Curve
-        new BN(1_000),  // Curve slope, => 1e-6
-        new BN(10_000_000), // Base Price, 0.01 SOL
-        new BN(100_000_000), // Max Price, 0.1 SOL
-        new BN(30_000_000),  // Flat Price if Bonding Curve isn't used
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        null,  // Merkle root, per-holder signatures are used when absent
+        null,  // Claim deadline, claims stay open when absent
+        null,  // Vesting schedule, claimed tokens unlock at once when absent
+        { accounts: {} },  // Claim tracking, a claim state account per holder
+        {
+          paidClaimingEnabled: true,
+          useBondingCurve: true,
+          curveSlope: new BN(1_000),  // => 1e-6
+          basePrice: new BN(10_000_000),  // 0.01 SOL
+          maxPrice: new BN(100_000_000),  // 0.1 SOL
+          flatPricePerToken: new BN(30_000_000),  // Used without the bonding curve
+          curveKind: { linear: {} },
+          curveExponent: new BN(2),  // Used by the power curve
+          curveStepSize: new BN(0),  // Used by the step curve
+          redeemSpreadBps: 0,
+        },  // Vamping params
       )
       .accounts({
         authority,
@@ -332,22 +494,168 @@ describe("solana-vamp-project", () => {
     // Execute claim
     const { solverSignature, validatorSignature, ownerSignature } = await getSignatures();
 
-    await program.methods
-      .claim(getEthAddressBytes(), new BN(1_000_000_000), solverSignature, validatorSignature, ownerSignature)
+    // An ownership signature issued for another wallet can't be redirected to this token account
+    const foreignOwnerSignature = await signOwnership(OWNER_PRIVATE_KEY, getOwnerAddress(), new BN(1_000_000_000), anchor.web3.Keypair.generate().publicKey);
+    try {
+      await program.methods
+        .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState: accounts.vampState2,
+          globalConfig: accounts.globalConfig,
+          claimState,
+          vault: accounts.vault2,
+          solVault: accounts.solVault2,
+          claimerTokenAccount,
+          mintAccount: mintAccount2,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+          vestingState: null,
+          claimBitmap: null,
+          protocolTreasury: null,
+          referrer: null,
+          epochSnapshot: null,
+        })
+        .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, foreignOwnerSignature))
+        .signers([claimerKeypair])
+        .rpc();
+
+      assert.fail("Claim with a redirected ownership signature should have failed but succeeded");
+    } catch (err) {
+      assert.include(err.message, "InvalidOwnerSignature");
+    }
+
+    // The vamp was created without a snapshot root, so Merkle proofs aren't accepted
+    try {
+      await program.methods
+        .claimWithProof(getEthAddressBytes(), new BN(1_000_000_000), new BN(0), [], null)
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState: accounts.vampState2,
+          globalConfig: accounts.globalConfig,
+          claimState,
+          vault: accounts.vault2,
+          solVault: accounts.solVault2,
+          claimerTokenAccount,
+          mintAccount: mintAccount2,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+          vestingState: null,
+          claimBitmap: null,
+          protocolTreasury: null,
+          referrer: null,
+          epochSnapshot: null,
+        })
+        .preInstructions([secp256k1Instruction(getEthAddressBytes(), ownerSignature, 0)])
+        .signers([claimerKeypair])
+        .rpc();
+
+      assert.fail("Claim with a proof on a signature vamp should have failed but succeeded");
+    } catch (err) {
+      assert.include(err.message, "InvalidClaimMode");
+    }
+
+    const claimOwnerBalance = () => program.methods
+      .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
       .accounts({
         authority: claimerKeypair.publicKey,
         vampState: accounts.vampState2,
+        globalConfig: accounts.globalConfig,
         claimState,
         vault: accounts.vault2,
         solVault: accounts.solVault2,
         claimerTokenAccount,
         mintAccount: mintAccount2,
-        token_program: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+        vestingState: null,
+        claimBitmap: null,
+        protocolTreasury: null,
+        referrer: null,
+        epochSnapshot: null,
       })
+      .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
       .signers([claimerKeypair])
       .rpc();
 
+    // Claims are rejected while the authority has the vamp paused
+    await program.methods
+      .pause()
+      .accounts({ authority, vampState: accounts.vampState2, mintAccount: mintAccount2 })
+      .rpc();
+    try {
+      await claimOwnerBalance();
+      assert.fail("Claim on a paused vamp should have failed but succeeded");
+    } catch (err) {
+      assert.include(err.message, "VampPaused");
+    }
+    // A quote doesn't price a claim that would fail
+    try {
+      await program.methods
+        .quoteClaim(new BN(1_000_000_000), false)
+        .accounts({ vampState: accounts.vampState2, globalConfig: accounts.globalConfig, mintAccount: mintAccount2 })
+        .view();
+      assert.fail("Quote on a paused vamp should have failed but succeeded");
+    } catch (err) {
+      assert.include(err.message, "VampPaused");
+    }
+    await program.methods
+      .unpause()
+      .accounts({ authority, vampState: accounts.vampState2, mintAccount: mintAccount2 })
+      .rpc();
+
+    // The emergency freeze blocks claims on every vamp
+    await program.methods
+      .setEmergencyFreeze(true)
+      .accounts({ admin: authority, globalConfig: accounts.globalConfig })
+      .rpc();
+    try {
+      await claimOwnerBalance();
+      assert.fail("Claim during an emergency freeze should have failed but succeeded");
+    } catch (err) {
+      assert.include(err.message, "ClaimsFrozen");
+    }
+    await program.methods
+      .setEmergencyFreeze(false)
+      .accounts({ admin: authority, globalConfig: accounts.globalConfig })
+      .rpc();
+
+    // A slippage bound below the curve cost must be rejected
+    try {
+      await program.methods
+        .claim(getEthAddressBytes(), new BN(1_000_000_000), new BN(1))
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState: accounts.vampState2,
+          globalConfig: accounts.globalConfig,
+          claimState,
+          vault: accounts.vault2,
+          solVault: accounts.solVault2,
+          claimerTokenAccount,
+          mintAccount: mintAccount2,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+          vestingState: null,
+          claimBitmap: null,
+          protocolTreasury: null,
+          referrer: null,
+          epochSnapshot: null,
+        })
+        .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+        .signers([claimerKeypair])
+        .rpc();
+
+      assert.fail("Claim above max_total_cost should have failed but succeeded");
+    } catch (err) {
+      assert.include(err.message, "SlippageExceeded");
+    }
+
+    await claimOwnerBalance();
+
     // Verify token balance
     const claimerData = await provider.connection.getTokenAccountBalance(claimerTokenAccount);
     assert.equal(claimerData.value.amount, "1000000000", "Token amount mismatch");
@@ -368,18 +676,26 @@ describe("solana-vamp-project", () => {
     // Verify double claim prevention
     try {
       await program.methods
-        .claim(getEthAddressBytes(), new BN(1_000_000_000), solverSignature, validatorSignature, ownerSignature)
+        .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
         .accounts({
           authority: claimerKeypair.publicKey,
           vampState: accounts.vampState2,
+          globalConfig: accounts.globalConfig,
           claimState,
           vault: accounts.vault2,
           solVault: accounts.solVault2,
           claimerTokenAccount,
           mintAccount: mintAccount2,
-          token_program: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: anchor.web3.SystemProgram.programId,
+          instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+          vestingState: null,
+          claimBitmap: null,
+          protocolTreasury: null,
+          referrer: null,
+          epochSnapshot: null,
         })
+        .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
         .signers([claimerKeypair])
         .rpc();
 
@@ -403,14 +719,26 @@ describe("solana-vamp-project", () => {
         "https://example.com/token/1",  // Token URI
         new BN(312012000000000),  // Amount
         Buffer.from([249, 139, 130, 139, 56, 155, 239, 78, 187, 181, 145, 28, 161, 126, 79, 121, 137, 201, 6, 141]),  // Solver Public Key
-        Buffer.from([139, 37, 237, 6, 226, 22, 85, 63, 141, 66, 101, 153, 96, 97, 176, 160, 101, 175, 163, 92]),  // Validator Public Key
+        [[139, 37, 237, 6, 226, 22, 85, 63, 141, 66, 101, 153, 96, 97, 176, 160, 101, 175, 163, 92]],  // Validator set
+        1,  // Validator threshold
         Buffer.from([17, 17, 17, 17, 17, 17, 17, 17, 34, 34, 34, 34, 34, 34, 34, 34, 119, 119, 119, 119, 119, 119, 119, 119, 153, 153, 153, 153, 153, 153, 153, 153]),  // Intent ID
-        true,  // Paid Claim Enabled
-        true,  // Use Bonding Curve
-        new BN(1_000),  // Curve slope, => 1e-6
-        new BN(10_000_000), // Base Price, 0.01 SOL
-        new BN(100_000_000), // Max Price, 0.1 SOL
-        new BN(30_000_000),  // Flat Price if Bonding Curve isn't used, 0.03 SOL
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        null,  // Merkle root, per-holder signatures are used when absent
+        null,  // Claim deadline, claims stay open when absent
+        null,  // Vesting schedule, claimed tokens unlock at once when absent
+        { accounts: {} },  // Claim tracking, a claim state account per holder
+        {
+          paidClaimingEnabled: true,
+          useBondingCurve: true,
+          curveSlope: new BN(1_000),  // => 1e-6
+          basePrice: new BN(10_000_000),  // 0.01 SOL
+          maxPrice: new BN(100_000_000),  // 0.1 SOL
+          flatPricePerToken: new BN(30_000_000),  // 0.03 SOL, used without the bonding curve
+          curveKind: { linear: {} },
+          curveExponent: new BN(2),  // Used by the power curve
+          curveStepSize: new BN(0),  // Used by the step curve
+          redeemSpreadBps: 250,  // 2.5%
+        },  // Vamping params
       )
       .accounts({
         authority,
@@ -458,18 +786,26 @@ describe("solana-vamp-project", () => {
     const { solverSignature, validatorSignature, ownerSignature, secondSolverSignature, secondValidatorSignature, secondOwnerSignature } = await getSignatures();
 
     await program.methods
-      .claim(getEthAddressBytes(), smallAmount, solverSignature, validatorSignature, ownerSignature)
+      .claim(getEthAddressBytes(), smallAmount, null)
       .accounts({
         authority: claimerKeypair.publicKey,
         vampState: accounts.vampState3,
+        globalConfig: accounts.globalConfig,
         claimState: claimState1,
         vault: accounts.vault3,
         solVault: accounts.solVault3,
         claimerTokenAccount,
         mintAccount: accounts.mintAccount3,
-        token_program: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+        vestingState: null,
+        claimBitmap: null,
+        protocolTreasury: null,
+        referrer: null,
+        epochSnapshot: null,
       })
+      .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
       .signers([claimerKeypair])
       .rpc();
 
@@ -486,24 +822,40 @@ describe("solana-vamp-project", () => {
 
     // Claim a larger amount (should cost more due to bonding curve)
     const nextAmount = new BN(3_000_000_000); // 3 tokens
+    const quotedCost = await program.methods
+      .quoteClaim(nextAmount, false)
+      .accounts({
+        vampState: accounts.vampState3,
+        globalConfig: accounts.globalConfig,
+        mintAccount: accounts.mintAccount3,
+      })
+      .view();
     const [claimState2] = anchor.web3.PublicKey.findProgramAddressSync(
       [Buffer.from("claim"), accounts.vampState3.toBuffer(), Buffer.from(getSecondOwnerAddress().slice(2), "hex")],
       PROGRAM_ID
     );
 
     await program.methods
-      .claim(getSecondEthAddressBytes(), nextAmount, secondSolverSignature, secondValidatorSignature, secondOwnerSignature)
+      .claim(getSecondEthAddressBytes(), nextAmount, null)
       .accounts({
         authority: claimerKeypair.publicKey,
         vampState: accounts.vampState3,
+        globalConfig: accounts.globalConfig,
         claimState: claimState2,
         vault: accounts.vault3,
         solVault: accounts.solVault3,
         claimerTokenAccount,
         mintAccount: accounts.mintAccount3,
-        token_program: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+        vestingState: null,
+        claimBitmap: null,
+        protocolTreasury: null,
+        referrer: null,
+        epochSnapshot: null,
       })
+      .preInstructions(claimSignatureInstructions(getSecondOwnerAddress(), secondSolverSignature, secondValidatorSignature, secondOwnerSignature))
       .signers([claimerKeypair])
       .rpc();
 
@@ -515,6 +867,7 @@ describe("solana-vamp-project", () => {
 
     console.log(`Second claim cost: ${secondClaimCost} lamports (${secondClaimCost / anchor.web3.LAMPORTS_PER_SOL} SOL)`);
     console.log(`Second claim deposited: ${secondClaimDeposited} lamports`);
+    assert.equal(secondClaimDeposited, quotedCost.toNumber(), "Quoted cost doesn't match the charged cost");
 
     // Verify bonding curve behavior: second claim should cost more per token
     const firstCostPerToken = firstClaimCost / smallAmount.toNumber();
@@ -540,5 +893,1356 @@ describe("solana-vamp-project", () => {
     console.log(`Transaction fees: ${transactionFees} lamports`);
 
     assert.isTrue(transactionFees > 0, "Transaction fees should be positive");
+
+    // Selling the last token back refunds its curve price from the reserve
+    const redeemAmount = new BN(1_000_000_000);
+    const vampStateBeforeRedeem = await program.account.vampState.fetch(accounts.vampState3);
+    await program.methods
+      .redeem(redeemAmount, new BN(1))
+      .accounts({
+        authority: claimerKeypair.publicKey,
+        vampState: accounts.vampState3,
+        vault: accounts.vault3,
+        solVault: accounts.solVault3,
+        redeemerTokenAccount: claimerTokenAccount,
+        mintAccount: accounts.mintAccount3,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([claimerKeypair])
+      .rpc();
+
+    const vampStateAfterRedeem = await program.account.vampState.fetch(accounts.vampState3);
+    const solVaultBalanceAfterRedeem = await provider.connection.getBalance(accounts.solVault3);
+    const payout = finalSolVaultBalance - solVaultBalanceAfterRedeem;
+
+    assert.equal(
+      vampStateAfterRedeem.totalClaimed.toString(),
+      vampStateBeforeRedeem.totalClaimed.sub(redeemAmount).toString(),
+      "Total claimed was not decremented"
+    );
+    assert.equal(
+      vampStateAfterRedeem.reserveBalance.toString(),
+      vampStateBeforeRedeem.reserveBalance.subn(payout).toString(),
+      "Reserve balance doesn't match the payout"
+    );
+    // The redeemed token was the most expensive one of the second claim, 0.01 SOL plus 3 slope steps,
+    // refunded less the spread
+    const curvePrice = 10_003_000;
+    assert.equal(payout, curvePrice - (curvePrice * 250) / 10_000, "Redeem didn't pay the curve price less the spread");
+  });
+
+  it("Withdraws reserve SOL from the vault to the treasury", async () => {
+    const accounts = await setupInitAccounts(authority);
+
+    const vampStateBefore = await program.account.vampState.fetch(accounts.vampState3);
+    const reserveBefore = vampStateBefore.reserveBalance;
+    assert.isTrue(reserveBefore.gtn(0), "Reserve should contain SOL from the previous claims");
+
+    const initialTreasuryBalance = await provider.connection.getBalance(authority);
+    const initialSolVaultBalance = await provider.connection.getBalance(accounts.solVault3);
+    // The 3 tokens still claimed can be redeemed for their curve value, 0.03 SOL plus 0 + 1 + 2 slope
+    // steps, only the spread kept from the redeem is above it
+    const liability = new BN(30_003_000);
+    const withdrawAmount = reserveBefore.sub(liability);
+    assert.isTrue(withdrawAmount.gtn(0), "Reserve should contain the redeem spread");
+
+    // Claimers can't withdraw the reserve
+    try {
+      await program.methods
+        .withdrawReserve(withdrawAmount)
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState: accounts.vampState3,
+          solVault: accounts.solVault3,
+          treasury: authority,
+          mintAccount: accounts.mintAccount3,
+        })
+        .signers([claimerKeypair])
+        .rpc();
+      assert.fail("Withdrawal by a non-authority should have failed");
+    } catch (err) {
+      assert.include(err.message, "Unauthorized");
+    }
+
+    // The curve value of the claimed supply can't be withdrawn
+    try {
+      await program.methods
+        .withdrawReserve(withdrawAmount.addn(1))
+        .accounts({
+          authority,
+          vampState: accounts.vampState3,
+          solVault: accounts.solVault3,
+          treasury: authority,
+          mintAccount: accounts.mintAccount3,
+        })
+        .rpc();
+      assert.fail("Withdrawal of the redeemable reserve should have failed");
+    } catch (err) {
+      assert.include(err.message, "InsufficientReserve");
+    }
+
+    await program.methods
+      .withdrawReserve(withdrawAmount)
+      .accounts({
+        authority,
+        vampState: accounts.vampState3,
+        solVault: accounts.solVault3,
+        treasury: authority,
+        mintAccount: accounts.mintAccount3,
+      })
+      .rpc();
+
+    const vampStateAfter = await program.account.vampState.fetch(accounts.vampState3);
+    const finalSolVaultBalance = await provider.connection.getBalance(accounts.solVault3);
+    const finalTreasuryBalance = await provider.connection.getBalance(authority);
+
+    assert.equal(
+      vampStateAfter.reserveBalance.toString(),
+      reserveBefore.sub(withdrawAmount).toString(),
+      "Reserve balance was not decremented"
+    );
+    assert.equal(
+      initialSolVaultBalance - finalSolVaultBalance,
+      withdrawAmount.toNumber(),
+      "SOL vault balance mismatch"
+    );
+    // The authority pays the transaction fee, so the treasury gain is slightly below the amount
+    assert.isTrue(finalTreasuryBalance > initialTreasuryBalance, "Treasury didn't receive SOL");
+  });
+
+  it("Refuses to sweep unclaimed tokens while the claim window is open", async () => {
+    const accounts = await setupInitAccounts(authority);
+
+    // The vamp was created without a claim deadline, so the vault can't be swept
+    try {
+      await program.methods
+        .sweepUnclaimed(true)
+        .accounts({
+          authority,
+          vampState: accounts.vampState3,
+          vault: accounts.vault3,
+          destination: null,
+          mintAccount: accounts.mintAccount3,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      assert.fail("Sweep without a claim deadline should have failed");
+    } catch (err) {
+      assert.include(err.message, "ClaimWindowOpen");
+    }
+  });
+
+  it("Updates vamping params and locks them once claiming has started", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const vampingParams = {
+      paidClaimingEnabled: true,
+      useBondingCurve: true,
+      curveSlope: new BN(1_000),
+      basePrice: new BN(10_000_000),
+      maxPrice: new BN(50_000_000),
+      flatPricePerToken: new BN(30_000_000),
+      curveKind: { linear: {} },
+      curveExponent: new BN(2),
+      curveStepSize: new BN(0),
+      redeemSpreadBps: 0,
+    };
+
+    // A cap below the base price would reject every claim
+    try {
+      await program.methods
+        .updateVampingParams({ ...vampingParams, maxPrice: new BN(1) }, false)
+        .accounts({
+          authority,
+          vampState: accounts.vampState,
+          mintAccount: accounts.mintAccount,
+        })
+        .rpc();
+      assert.fail("Update with out of bounds params should have failed");
+    } catch (err) {
+      assert.include(err.message, "InvalidVampingParams");
+    }
+
+    // Nothing was claimed from this vamp yet, so its params can still change
+    await program.methods
+      .updateVampingParams(vampingParams, true)
+      .accounts({
+        authority,
+        vampState: accounts.vampState,
+        mintAccount: accounts.mintAccount,
+      })
+      .rpc();
+
+    const vampState = await program.account.vampState.fetch(accounts.vampState);
+    assert.equal(vampState.maxPrice.toString(), vampingParams.maxPrice.toString());
+    assert.isTrue(vampState.paramsLocked);
+
+    // Redeems walk back the curve the claimed supply of this vamp was paid on
+    try {
+      await program.methods
+        .updateVampingParams(vampingParams, false)
+        .accounts({
+          authority,
+          vampState: accounts.vampState3,
+          mintAccount: accounts.mintAccount3,
+        })
+        .rpc();
+      assert.fail("Update of params with redeemable claims should have failed");
+    } catch (err) {
+      assert.include(err.message, "VampingParamsLocked");
+    }
+  });
+
+  it("Rotates the solver and validator keys with a grace period", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const before = await program.account.vampState.fetch(accounts.vampState2);
+    const newSolver = Buffer.from(ethers.Wallet.createRandom().address.slice(2), "hex");
+    const newValidator = Array.from(Buffer.from(ethers.Wallet.createRandom().address.slice(2), "hex"));
+
+    try {
+      await program.methods
+        .rotateSigners(newSolver.subarray(0, 19), [newValidator], 1, new BN(0))
+        .accounts({ authority, vampState: accounts.vampState2, mintAccount: accounts.mintAccount2 })
+        .rpc();
+      assert.fail("Rotation to a malformed key should have failed");
+    } catch (err) {
+      assert.include(err.message, "InvalidSignerKey");
+    }
+
+    await program.methods
+      .rotateSigners(newSolver, [newValidator], 1, new BN(3600))
+      .accounts({ authority, vampState: accounts.vampState2, mintAccount: accounts.mintAccount2 })
+      .rpc();
+
+    const after = await program.account.vampState.fetch(accounts.vampState2);
+    assert.deepEqual(Buffer.from(after.solverPublicKey), newSolver);
+    assert.deepEqual(after.validatorSet.map((v) => Array.from(v)), [newValidator]);
+    assert.equal(after.validatorThreshold, 1);
+    assert.deepEqual(Buffer.from(after.previousSolverPublicKey), Buffer.from(before.solverPublicKey));
+    assert.isNotNull(after.previousSignersValidUntil);
+    // Attestations signed before the rotation name the previous epoch
+    assert.equal(after.signerEpoch, before.signerEpoch + 1);
+
+    // With a rotation delay the new keys are only queued
+    await program.methods
+      .setRotationDelay(new BN(86400))
+      .accounts({ authority, vampState: accounts.vampState2, mintAccount: accounts.mintAccount2 })
+      .rpc();
+    try {
+      await program.methods
+        .setRotationDelay(new BN(0))
+        .accounts({ authority, vampState: accounts.vampState2, mintAccount: accounts.mintAccount2 })
+        .rpc();
+      assert.fail("Lowering the rotation delay should have failed");
+    } catch (err) {
+      assert.include(err.message, "InvalidRotationDelay");
+    }
+
+    await program.methods
+      .rotateSigners(Buffer.from(before.solverPublicKey), before.validatorSet, 1, new BN(0))
+      .accounts({ authority, vampState: accounts.vampState2, mintAccount: accounts.mintAccount2 })
+      .rpc();
+    const queued = await program.account.vampState.fetch(accounts.vampState2);
+    assert.deepEqual(Buffer.from(queued.solverPublicKey), newSolver);
+    assert.deepEqual(Buffer.from(queued.pendingRotation.solverPublicKey), Buffer.from(before.solverPublicKey));
+
+    try {
+      await program.methods
+        .applySignerRotation()
+        .accounts({ authority, vampState: accounts.vampState2, mintAccount: accounts.mintAccount2 })
+        .rpc();
+      assert.fail("Applying a time locked rotation should have failed");
+    } catch (err) {
+      assert.include(err.message, "RotationTimeLocked");
+    }
+
+    await program.methods
+      .cancelSignerRotation()
+      .accounts({ authority, vampState: accounts.vampState2, mintAccount: accounts.mintAccount2 })
+      .rpc();
+    const cancelled = await program.account.vampState.fetch(accounts.vampState2);
+    assert.isNull(cancelled.pendingRotation);
+    // Only applied rotations start a new signer epoch
+    assert.equal(cancelled.signerEpoch, after.signerEpoch);
+  });
+
+  it("Vests claimed tokens and releases the unlocked part", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const [mintAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('mint'), authority.toBuffer(), new BN(vampIdBase + 3).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [metadataAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mintAccount.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+    const [vampState] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vamp"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [vault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [solVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const ethAddress = Buffer.from(getOwnerAddress().slice(2), "hex");
+    const [claimState] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("claim"), vampState.toBuffer(), ethAddress],
+      PROGRAM_ID
+    );
+    const [vestingState] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("vesting"), vampState.toBuffer(), ethAddress],
+      PROGRAM_ID
+    );
+
+    // Half of the schedule has already elapsed
+    const now = Math.floor(Date.now() / 1000);
+    await program.methods
+      .createTokenMint(
+        new BN(vampIdBase + 3),  // Vamp ID
+        9,  // Decimals
+        "My Memetoken",  // Token Name
+        "MEME",  // Token Symbol
+        Buffer.from([10, 11, 85, 6, 100, 79, 145, 115, 236, 165, 13, 29, 125, 44, 172, 229, 150, 165, 229, 85]),  // Token ERC20 Address
+        "https://example.com/token/1",  // Token URI
+        new BN(312012000000000),  // Amount
+        Buffer.from(SOLVER_ADDRESS),  // Solver Public Key
+        [VALIDATOR_ADDRESS],  // Validator set
+        1,  // Validator threshold
+        INTENT_ID,  // Intent ID
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        null,  // Merkle root
+        null,  // Claim deadline
+        { startTime: new BN(now - 100), cliffDuration: new BN(0), duration: new BN(200) },  // Vesting schedule
+        { accounts: {} },  // Claim tracking
+        {
+          paidClaimingEnabled: false,
+          useBondingCurve: false,
+          curveSlope: new BN(0),
+          basePrice: new BN(0),
+          maxPrice: null,  // Uncapped
+          flatPricePerToken: new BN(0),
+          curveKind: { linear: {} },
+          curveExponent: new BN(2),
+          curveStepSize: new BN(0),
+          redeemSpreadBps: 0,
+        },  // Vamping params
+      )
+      .accounts({
+        authority,
+        mintAccount,
+        metadataAccount,
+        vampState,
+        vault,
+        solVault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 2_000_000 })])
+      .rpc();
+
+    const claimerTokenAccount = await getAssociatedTokenAddress(mintAccount, claimerKeypair.publicKey);
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(claimerKeypair.publicKey, claimerTokenAccount, claimerKeypair.publicKey, mintAccount)
+      ),
+      [claimerKeypair]
+    );
+
+    const claimAccounts = {
+      authority: claimerKeypair.publicKey,
+      vampState,
+      globalConfig: accounts.globalConfig,
+      claimState,
+      vault,
+      solVault,
+      claimerTokenAccount,
+      mintAccount,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+      claimBitmap: null,
+      protocolTreasury: null,
+      referrer: null,
+      epochSnapshot: null,
+    };
+    const { solverSignature, validatorSignature, ownerSignature } = await getSignatures();
+
+    // A vesting vamp needs the vesting account
+    try {
+      await program.methods
+        .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
+        .accounts({ ...claimAccounts, vestingState: null })
+        .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+        .signers([claimerKeypair])
+        .rpc();
+      assert.fail("Claim without a vesting account should have failed");
+    } catch (err) {
+      assert.include(err.message, "InvalidVestingAccount");
+    }
+
+    await program.methods
+      .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
+      .accounts({ ...claimAccounts, vestingState })
+      .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+      .signers([claimerKeypair])
+      .rpc();
+
+    // Only the unlocked part is transferred at claim time
+    const vesting = await program.account.vestingState.fetch(vestingState);
+    const claimed = new BN((await provider.connection.getTokenAccountBalance(claimerTokenAccount)).value.amount);
+    assert.equal(vesting.totalAmount.toString(), "1000000000");
+    assert.equal(vesting.releasedAmount.toString(), claimed.toString());
+    assert.isTrue(claimed.gt(new BN(0)) && claimed.lt(new BN(1_000_000_000)));
+    const locked = (await program.account.vampState.fetch(vampState)).vestingLocked;
+    assert.equal(locked.toString(), new BN(1_000_000_000).sub(claimed).toString());
+
+    // Anyone can crank the release once more has unlocked
+    await new Promise((resolve) => setTimeout(resolve, 2_000));
+    await program.methods
+      .release()
+      .accounts({
+        vampState,
+        globalConfig: accounts.globalConfig,
+        vestingState,
+        vault,
+        beneficiary: claimerTokenAccount,
+        mintAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const released = new BN((await provider.connection.getTokenAccountBalance(claimerTokenAccount)).value.amount);
+    assert.isTrue(released.gt(claimed));
+    assert.equal((await program.account.vestingState.fetch(vestingState)).releasedAmount.toString(), released.toString());
+  });
+
+  it("Tops up a vamp for a new snapshot epoch", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const topUpIntentId = Buffer.alloc(32, 0x42);
+    const [epochSnapshot] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("epoch_snapshot"), accounts.vampState3.toBuffer(), Buffer.alloc(4)],
+      PROGRAM_ID
+    );
+    const topUpAccounts = {
+      authority,
+      vampState: accounts.vampState3,
+      mintAccount: accounts.mintAccount3,
+      vault: accounts.vault3,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+      epochSnapshot,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+
+    // Every epoch needs its own intent
+    try {
+      await program.methods
+        .topUpVamp(new BN(2_000_000_000), INTENT_ID, null, null)
+        .accounts(topUpAccounts)
+        .rpc();
+      assert.fail("Top-up with the current intent should have failed");
+    } catch (err) {
+      assert.include(err.message, "InvalidTopUp");
+    }
+
+    const before = await program.account.vampState.fetch(accounts.vampState3);
+    const vaultBefore = new BN((await provider.connection.getTokenAccountBalance(accounts.vault3)).value.amount);
+    await program.methods
+      .topUpVamp(new BN(2_000_000_000), topUpIntentId, null, null)
+      .accounts(topUpAccounts)
+      .rpc();
+
+    const after = await program.account.vampState.fetch(accounts.vampState3);
+    const vaultAfter = new BN((await provider.connection.getTokenAccountBalance(accounts.vault3)).value.amount);
+    assert.equal(after.snapshotEpoch, before.snapshotEpoch + 1);
+    assert.equal(after.tokenSupply.sub(before.tokenSupply).toString(), "2000000000");
+    assert.equal(vaultAfter.sub(vaultBefore).toString(), "2000000000");
+    assert.deepEqual(Buffer.from(after.intentId), topUpIntentId);
+    const archived = await program.account.epochSnapshot.fetch(epochSnapshot);
+    assert.equal(archived.snapshotEpoch, before.snapshotEpoch);
+    assert.deepEqual(Buffer.from(archived.intentId), INTENT_ID);
+
+    // The holder already claimed in the first epoch, the gained balance is claimed on the same mint
+    const ethAddress = Buffer.from(getOwnerAddress().slice(2), "hex");
+    const epochSeed = Buffer.alloc(4);
+    epochSeed.writeUInt32LE(after.snapshotEpoch);
+    const [claimState] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("claim"), accounts.vampState3.toBuffer(), ethAddress, epochSeed],
+      PROGRAM_ID
+    );
+    const claimerTokenAccount = await getAssociatedTokenAddress(accounts.mintAccount3, claimerKeypair.publicKey);
+    const claimedBefore = new BN((await provider.connection.getTokenAccountBalance(claimerTokenAccount)).value.amount);
+
+    const delta = new BN(2_000_000_000);
+    const solverSignature = await signBalance(SOLVER_PRIVATE_KEY, getOwnerAddress(), delta, topUpIntentId);
+    const validatorSignature = await signBalance(VALIDATOR_PRIVATE_KEY, getOwnerAddress(), delta, topUpIntentId);
+    const ownerSignature = await signOwnership(OWNER_PRIVATE_KEY, getOwnerAddress(), delta, claimerKeypair.publicKey, topUpIntentId);
+    await program.methods
+      .claim(getEthAddressBytes(), delta, null)
+      .accounts({
+        authority: claimerKeypair.publicKey,
+        vampState: accounts.vampState3,
+        globalConfig: accounts.globalConfig,
+        claimState,
+        vault: accounts.vault3,
+        solVault: accounts.solVault3,
+        claimerTokenAccount,
+        mintAccount: accounts.mintAccount3,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+        vestingState: null,
+        claimBitmap: null,
+        protocolTreasury: null,
+        referrer: null,
+        epochSnapshot: null,
+      })
+      .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+      .signers([claimerKeypair])
+      .rpc();
+
+    const claimedAfter = new BN((await provider.connection.getTokenAccountBalance(claimerTokenAccount)).value.amount);
+    assert.equal(claimedAfter.sub(claimedBefore).toString(), delta.toString());
+
+    // A holder who didn't claim in the first epoch still claims the balance attested for it
+    const lateHolder = ethers.Wallet.createRandom();
+    const lateAmount = new BN(1_000_000_000);
+    const lateClaimState = (seeds: Buffer[]) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("claim"), accounts.vampState3.toBuffer(), Buffer.from(lateHolder.address.slice(2), "hex"), ...seeds],
+        PROGRAM_ID
+      )[0];
+    const lateSolverSignature = await signBalance(SOLVER_PRIVATE_KEY, lateHolder.address, lateAmount);
+    const lateValidatorSignature = await signBalance(VALIDATOR_PRIVATE_KEY, lateHolder.address, lateAmount);
+    const lateOwnerSignature = await signOwnership(lateHolder.privateKey, lateHolder.address, lateAmount, claimerKeypair.publicKey);
+    const claimFirstEpoch = (epochSnapshotAccount: PublicKey | null, claimStateAccount: PublicKey) =>
+      program.methods
+        .claim(hexToBytes(lateHolder.address), lateAmount, null)
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState: accounts.vampState3,
+          globalConfig: accounts.globalConfig,
+          claimState: claimStateAccount,
+          vault: accounts.vault3,
+          solVault: accounts.solVault3,
+          claimerTokenAccount,
+          mintAccount: accounts.mintAccount3,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+          vestingState: null,
+          claimBitmap: null,
+          protocolTreasury: null,
+          referrer: null,
+          epochSnapshot: epochSnapshotAccount,
+        })
+        .preInstructions(claimSignatureInstructions(lateHolder.address, lateSolverSignature, lateValidatorSignature, lateOwnerSignature))
+        .signers([claimerKeypair])
+        .rpc();
+
+    // Without the archive the claim is checked against the current intent
+    try {
+      await claimFirstEpoch(null, lateClaimState([epochSeed]));
+      assert.fail("Claim of a closed epoch without its archive should have failed");
+    } catch (err) {
+      assert.include(err.message, "InvalidSolverSignature");
+    }
+
+    await claimFirstEpoch(epochSnapshot, lateClaimState([]));
+    const lateClaimed = new BN((await provider.connection.getTokenAccountBalance(claimerTokenAccount)).value.amount);
+    assert.equal(lateClaimed.sub(claimedAfter).toString(), lateAmount.toString());
+  });
+
+  it("Creates a Token-2022 vamp and claims from it", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const [mintAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('mint'), authority.toBuffer(), new BN(vampIdBase + 4).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [vampState] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vamp"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [vault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [solVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [claimState] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("claim"), vampState.toBuffer(), Buffer.from(getOwnerAddress().slice(2), "hex")],
+      PROGRAM_ID
+    );
+
+    await program.methods
+      .createTokenExtensionsMint(
+        new BN(vampIdBase + 4),  // Vamp ID
+        9,  // Decimals
+        "My Memetoken",  // Token Name
+        "MEME",  // Token Symbol
+        Buffer.from([10, 11, 85, 6, 100, 79, 145, 115, 236, 165, 13, 29, 125, 44, 172, 229, 150, 165, 229, 85]),  // Token ERC20 Address
+        "https://example.com/token/1",  // Token URI
+        new BN(312012000000000),  // Amount
+        Buffer.from(SOLVER_ADDRESS),  // Solver Public Key
+        [VALIDATOR_ADDRESS],  // Validator set
+        1,  // Validator threshold
+        INTENT_ID,  // Intent ID
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        null,  // Merkle root
+        null,  // Claim deadline
+        null,  // Vesting schedule
+        { accounts: {} },  // Claim tracking
+        null,  // Vamping params, free claiming when absent
+      )
+      .accounts({
+        authority,
+        mintAccount,
+        vampState,
+        vault,
+        solVault,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 2_000_000 })])
+      .rpc();
+
+    // The metadata lives in the mint itself
+    const mintInfo = await provider.connection.getAccountInfo(mintAccount);
+    assert.isTrue(mintInfo.owner.equals(TOKEN_2022_PROGRAM_ID));
+    const metadata = await getTokenMetadata(provider.connection, mintAccount, "confirmed", TOKEN_2022_PROGRAM_ID);
+    assert.equal(metadata.name, "My Memetoken");
+    assert.equal(metadata.symbol, "MEME");
+    assert.equal(metadata.uri, "https://example.com/token/1");
+    assert.isTrue(metadata.updateAuthority.equals(mintAccount));
+
+    const claimerTokenAccount = await getAssociatedTokenAddress(mintAccount, claimerKeypair.publicKey, false, TOKEN_2022_PROGRAM_ID);
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          claimerKeypair.publicKey,
+          claimerTokenAccount,
+          claimerKeypair.publicKey,
+          mintAccount,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ),
+      [claimerKeypair]
+    );
+
+    const { solverSignature, validatorSignature, ownerSignature } = await getSignatures();
+    await program.methods
+      .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
+      .accounts({
+        authority: claimerKeypair.publicKey,
+        vampState,
+        globalConfig: accounts.globalConfig,
+        claimState,
+        vault,
+        solVault,
+        claimerTokenAccount,
+        mintAccount,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+        vestingState: null,
+        claimBitmap: null,
+        protocolTreasury: null,
+        referrer: null,
+        epochSnapshot: null,
+      })
+      .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+      .signers([claimerKeypair])
+      .rpc();
+
+    const claimed = await provider.connection.getTokenAccountBalance(claimerTokenAccount);
+    assert.equal(claimed.value.amount, "1000000000");
+  });
+
+  it("Tracks Merkle claims in a bitmap page", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const [mintAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('mint'), authority.toBuffer(), new BN(vampIdBase + 5).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [metadataAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mintAccount.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+    const [vampState] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vamp"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [vault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [solVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [claimBitmap] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("claim_bitmap"), vampState.toBuffer(), Buffer.alloc(4)],
+      PROGRAM_ID
+    );
+
+    // Two holder snapshot, see merkle_tree::Leaf::hash
+    const leafHash = (ethAddress: string, amount: BN) =>
+      Buffer.from(ethers.getBytes(ethers.keccak256(ethers.concat([ethAddress, amount.toArrayLike(Buffer, "be", 8), "0x09"]))));
+    const amount = new BN(1_000_000_000);
+    const leaves = [leafHash(getOwnerAddress(), amount), leafHash(getSecondOwnerAddress(), new BN(2_000_000_000))];
+    const merkleRoot = Array.from(ethers.getBytes(ethers.keccak256(Buffer.concat(leaves))));
+    const solverRootSignature = await signSnapshotRoot(SOLVER_PRIVATE_KEY, merkleRoot);
+    const validatorRootSignature = await signSnapshotRoot(VALIDATOR_PRIVATE_KEY, merkleRoot);
+
+    await program.methods
+      .createTokenMint(
+        new BN(vampIdBase + 5),  // Vamp ID
+        9,  // Decimals
+        "My Memetoken",  // Token Name
+        "MEME",  // Token Symbol
+        Buffer.from([10, 11, 85, 6, 100, 79, 145, 115, 236, 165, 13, 29, 125, 44, 172, 229, 150, 165, 229, 85]),  // Token ERC20 Address
+        "https://example.com/token/1",  // Token URI
+        new BN(312012000000000),  // Amount
+        Buffer.from(SOLVER_ADDRESS),  // Solver Public Key
+        [VALIDATOR_ADDRESS],  // Validator set
+        1,  // Validator threshold
+        INTENT_ID,  // Intent ID
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        merkleRoot,  // Merkle root
+        null,  // Claim deadline
+        null,  // Vesting schedule
+        { bitmap: {} },  // Claim tracking
+        {
+          paidClaimingEnabled: false,
+          useBondingCurve: false,
+          curveSlope: new BN(0),
+          basePrice: new BN(0),
+          maxPrice: null,  // Uncapped
+          flatPricePerToken: new BN(0),
+          curveKind: { linear: {} },
+          curveExponent: new BN(2),
+          curveStepSize: new BN(0),
+          redeemSpreadBps: 0,
+        },  // Vamping params
+      )
+      .accounts({
+        authority,
+        mintAccount,
+        metadataAccount,
+        vampState,
+        vault,
+        solVault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+      })
+      .preInstructions([
+        ComputeBudgetProgram.setComputeUnitLimit({ units: 2_000_000 }),
+        secp256k1Instruction(SOLVER_ADDRESS, solverRootSignature, 1),
+        secp256k1Instruction(VALIDATOR_ADDRESS, validatorRootSignature, 2),
+      ])
+      .rpc();
+
+    await program.methods
+      .createClaimBitmap(0, 2)
+      .accounts({
+        authority,
+        vampState,
+        claimBitmap,
+        mintAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    const claimerTokenAccount = await getAssociatedTokenAddress(mintAccount, claimerKeypair.publicKey);
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(claimerKeypair.publicKey, claimerTokenAccount, claimerKeypair.publicKey, mintAccount)
+      ),
+      [claimerKeypair]
+    );
+
+    const ownerSignature = await signOwnership(OWNER_PRIVATE_KEY, getOwnerAddress(), amount, claimerKeypair.publicKey);
+    const claimWithProof = (maxTotalCost: BN | null) =>
+      program.methods
+        .claimWithProof(getEthAddressBytes(), amount, new BN(0), [Array.from(leaves[1])], maxTotalCost)
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState,
+          globalConfig: accounts.globalConfig,
+          claimState: null,
+          vault,
+          solVault,
+          claimerTokenAccount,
+          mintAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+          vestingState: null,
+          claimBitmap,
+          protocolTreasury: null,
+          referrer: null,
+          epochSnapshot: null,
+        })
+        .preInstructions([secp256k1Instruction(getEthAddressBytes(), ownerSignature, 0)])
+        .signers([claimerKeypair])
+        .rpc();
+
+    await claimWithProof(null);
+    const claimed = await provider.connection.getTokenAccountBalance(claimerTokenAccount);
+    assert.equal(claimed.value.amount, amount.toString());
+
+    // The holder's bit is set, no account was created for the claim
+    try {
+      // A distinct slippage bound keeps the retry from being a duplicate transaction
+      await claimWithProof(new BN(0));
+      assert.fail("Second claim from the bitmap should have failed");
+    } catch (err) {
+      assert.include(err.message, "TokensAlreadyClaimed");
+    }
+  });
+
+  it("Claims for holders in a batch", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const [mintAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('mint'), authority.toBuffer(), new BN(vampIdBase + 6).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [metadataAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mintAccount.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+    const [vampState] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vamp"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [vault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [solVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [claimState, claimStateBump] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("claim"), vampState.toBuffer(), Buffer.from(getEthAddressBytes())],
+      PROGRAM_ID
+    );
+
+    await program.methods
+      .createTokenMint(
+        new BN(vampIdBase + 6),  // Vamp ID
+        9,  // Decimals
+        "My Memetoken",  // Token Name
+        "MEME",  // Token Symbol
+        Buffer.from([10, 11, 85, 6, 100, 79, 145, 115, 236, 165, 13, 29, 125, 44, 172, 229, 150, 165, 229, 85]),  // Token ERC20 Address
+        "https://example.com/token/1",  // Token URI
+        new BN(312012000000000),  // Amount
+        Buffer.from(SOLVER_ADDRESS),  // Solver Public Key
+        [VALIDATOR_ADDRESS],  // Validator set
+        1,  // Validator threshold
+        INTENT_ID,  // Intent ID
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        null,  // Merkle root
+        null,  // Claim deadline
+        null,  // Vesting schedule
+        { accounts: {} },  // Claim tracking
+        {
+          paidClaimingEnabled: false,
+          useBondingCurve: false,
+          curveSlope: new BN(0),
+          basePrice: new BN(0),
+          maxPrice: null,  // Uncapped
+          flatPricePerToken: new BN(0),
+          curveKind: { linear: {} },
+          curveExponent: new BN(2),
+          curveStepSize: new BN(0),
+          redeemSpreadBps: 0,
+        },  // Vamping params
+      )
+      .accounts({
+        authority,
+        mintAccount,
+        metadataAccount,
+        vampState,
+        vault,
+        solVault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 2_000_000 })])
+      .rpc();
+
+    const claimerTokenAccount = await getAssociatedTokenAddress(mintAccount, claimerKeypair.publicKey);
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(claimerKeypair.publicKey, claimerTokenAccount, claimerKeypair.publicKey, mintAccount)
+      ),
+      [claimerKeypair]
+    );
+
+    const { solverSignature, validatorSignature, ownerSignature } = await getSignatures();
+    // Every claim takes its claim state and destination token account from the remaining accounts
+    const claimBatch = (remainingAccounts: anchor.web3.AccountMeta[], maxTotalCost: BN | null = null) =>
+      program.methods
+        .claimBatch([
+          {
+            ethAddress: getEthAddressBytes(),
+            balance: new BN(1_000_000_000),
+            maxTotalCost,
+            claimStateBump,
+            vestingStateBump: null,
+          },
+        ])
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState,
+          globalConfig: accounts.globalConfig,
+          vault,
+          solVault,
+          mintAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+          protocolTreasury: null,
+        })
+        .remainingAccounts(remainingAccounts)
+        .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+        .signers([claimerKeypair])
+        .rpc();
+    const holderAccounts = [
+      { pubkey: claimState, isWritable: true, isSigner: false },
+      { pubkey: claimerTokenAccount, isWritable: true, isSigner: false },
+    ];
+
+    try {
+      await claimBatch(holderAccounts.slice(1));
+      assert.fail("Batch without the claim state account should have failed");
+    } catch (err) {
+      assert.include(err.message, "InvalidBatchAccounts");
+    }
+
+    await claimBatch(holderAccounts);
+    const claimed = await provider.connection.getTokenAccountBalance(claimerTokenAccount);
+    assert.equal(claimed.value.amount, "1000000000");
+    assert.isTrue((await program.account.claimState.fetch(claimState)).isClaimed);
+
+    try {
+      await claimBatch(holderAccounts, new BN(0));
+      assert.fail("Second batch claim of the holder should have failed");
+    } catch (err) {
+      assert.include(err.message, "TokensAlreadyClaimed");
+    }
+  });
+
+  it("Splits paid claims between the vault, the protocol and the referrer", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const [mintAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('mint'), authority.toBuffer(), new BN(vampIdBase + 7).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [metadataAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mintAccount.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+    const [vampState] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vamp"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [vault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [solVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [claimState] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("claim"), vampState.toBuffer(), Buffer.from(getEthAddressBytes())],
+      PROGRAM_ID
+    );
+
+    // Fee recipients have to be rent exempt to receive small shares
+    const protocolTreasury = anchor.web3.Keypair.generate().publicKey;
+    const referrer = anchor.web3.Keypair.generate().publicKey;
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.transfer({ fromPubkey: authority, toPubkey: protocolTreasury, lamports: anchor.web3.LAMPORTS_PER_SOL }),
+        anchor.web3.SystemProgram.transfer({ fromPubkey: authority, toPubkey: referrer, lamports: anchor.web3.LAMPORTS_PER_SOL })
+      )
+    );
+
+    try {
+      await program.methods
+        .setProtocolFees(protocolTreasury, 10_000, 1)
+        .accounts({ admin: authority, globalConfig: accounts.globalConfig })
+        .rpc();
+      assert.fail("Fees above the whole payment should have been rejected");
+    } catch (err) {
+      assert.include(err.message, "InvalidFeeConfig");
+    }
+
+    // 2.5% to the protocol and 1% to the referrer
+    await program.methods
+      .setProtocolFees(protocolTreasury, 250, 100)
+      .accounts({ admin: authority, globalConfig: accounts.globalConfig })
+      .rpc();
+
+    try {
+      await program.methods
+        .createTokenMint(
+          new BN(vampIdBase + 7),  // Vamp ID
+          9,  // Decimals
+          "My Memetoken",  // Token Name
+          "MEME",  // Token Symbol
+          Buffer.from([10, 11, 85, 6, 100, 79, 145, 115, 236, 165, 13, 29, 125, 44, 172, 229, 150, 165, 229, 85]),  // Token ERC20 Address
+          "https://example.com/token/1",  // Token URI
+          new BN(312012000000000),  // Amount
+          Buffer.from(SOLVER_ADDRESS),  // Solver Public Key
+          [VALIDATOR_ADDRESS],  // Validator set
+          1,  // Validator threshold
+          INTENT_ID,  // Intent ID
+          new BN(SOURCE_CHAIN_ID),  // Source chain ID
+          null,  // Merkle root
+          null,  // Claim deadline
+          null,  // Vesting schedule
+          { accounts: {} },  // Claim tracking
+          {
+            paidClaimingEnabled: true,
+            useBondingCurve: false,
+            curveSlope: new BN(0),
+            basePrice: new BN(0),
+            maxPrice: null,  // Uncapped
+            flatPricePerToken: new BN(10_000_000),  // 0.01 SOL
+            curveKind: { linear: {} },
+            curveExponent: new BN(2),
+            curveStepSize: new BN(0),
+            redeemSpreadBps: 0,
+          },  // Vamping params
+        )
+        .accounts({
+          authority,
+          mintAccount,
+          metadataAccount,
+          vampState,
+          vault,
+          solVault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 2_000_000 })])
+        .rpc();
+
+      const claimerTokenAccount = await getAssociatedTokenAddress(mintAccount, claimerKeypair.publicKey);
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          createAssociatedTokenAccountInstruction(claimerKeypair.publicKey, claimerTokenAccount, claimerKeypair.publicKey, mintAccount)
+        ),
+        [claimerKeypair]
+      );
+
+      const { solverSignature, validatorSignature, ownerSignature } = await getSignatures();
+      const claim = (protocolTreasuryAccount: PublicKey | null, referrerAccount: PublicKey | null) =>
+        program.methods
+          .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
+          .accounts({
+            authority: claimerKeypair.publicKey,
+            vampState,
+            globalConfig: accounts.globalConfig,
+            claimState,
+            vault,
+            solVault,
+            claimerTokenAccount,
+            mintAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+            vestingState: null,
+            claimBitmap: null,
+            protocolTreasury: protocolTreasuryAccount,
+            referrer: referrerAccount,
+            epochSnapshot: null,
+          })
+          .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+          .signers([claimerKeypair])
+          .rpc();
+
+      try {
+        await claim(null, referrer);
+        assert.fail("Paid claim without the protocol treasury should have failed");
+      } catch (err) {
+        assert.include(err.message, "InvalidProtocolTreasury");
+      }
+
+      try {
+        await claim(protocolTreasury, claimerKeypair.publicKey);
+        assert.fail("Claimer referring their own claim should have failed");
+      } catch (err) {
+        assert.include(err.message, "InvalidReferrer");
+      }
+
+      const treasuryBefore = await provider.connection.getBalance(protocolTreasury);
+      const referrerBefore = await provider.connection.getBalance(referrer);
+      await claim(protocolTreasury, referrer);
+
+      // One token at 0.01 SOL
+      assert.equal(await provider.connection.getBalance(protocolTreasury) - treasuryBefore, 250_000);
+      assert.equal(await provider.connection.getBalance(referrer) - referrerBefore, 100_000);
+      const vampStateAccount = await program.account.vampState.fetch(vampState);
+      // The fees come on top, the reserve keeps the whole curve cost
+      assert.equal(vampStateAccount.reserveBalance.toString(), "10000000");
+      assert.equal(vampStateAccount.protocolFeesPaid.toString(), "250000");
+      assert.equal(vampStateAccount.referralFeesPaid.toString(), "100000");
+    } finally {
+      await program.methods
+        .setProtocolFees(authority, 0, 0)
+        .accounts({ admin: authority, globalConfig: accounts.globalConfig })
+        .rpc();
+    }
+  });
+
+  it("Graduates a vamp once its reserve reaches the threshold", async () => {
+    const accounts = await setupInitAccounts(authority);
+    const [mintAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('mint'), authority.toBuffer(), new BN(vampIdBase + 8).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [metadataAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mintAccount.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+    const [vampState] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vamp"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [vault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [solVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [lpVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("lp_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [claimState] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("claim"), vampState.toBuffer(), Buffer.from(getEthAddressBytes())],
+      PROGRAM_ID
+    );
+
+    await program.methods
+      .createTokenMint(
+        new BN(vampIdBase + 8),  // Vamp ID
+        9,  // Decimals
+        "My Memetoken",  // Token Name
+        "MEME",  // Token Symbol
+        Buffer.from([10, 11, 85, 6, 100, 79, 145, 115, 236, 165, 13, 29, 125, 44, 172, 229, 150, 165, 229, 85]),  // Token ERC20 Address
+        "https://example.com/token/1",  // Token URI
+        new BN(312012000000000),  // Amount
+        Buffer.from(SOLVER_ADDRESS),  // Solver Public Key
+        [VALIDATOR_ADDRESS],  // Validator set
+        1,  // Validator threshold
+        INTENT_ID,  // Intent ID
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        null,  // Merkle root
+        null,  // Claim deadline
+        null,  // Vesting schedule
+        { accounts: {} },  // Claim tracking
+        {
+          paidClaimingEnabled: true,
+          useBondingCurve: false,
+          curveSlope: new BN(0),
+          basePrice: new BN(0),
+          maxPrice: null,  // Uncapped
+          flatPricePerToken: new BN(10_000_000),  // 0.01 SOL
+          curveKind: { linear: {} },
+          curveExponent: new BN(2),
+          curveStepSize: new BN(0),
+          redeemSpreadBps: 0,
+        },  // Vamping params
+      )
+      .accounts({
+        authority,
+        mintAccount,
+        metadataAccount,
+        vampState,
+        vault,
+        solVault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 2_000_000 })])
+      .rpc();
+
+    try {
+      await program.methods
+        .setGraduation({ reserveThreshold: null, claimedThreshold: null, lpAllocation: new BN(1) })
+        .accounts({ authority, vampState, mintAccount })
+        .rpc();
+      assert.fail("Graduation without a threshold should have been rejected");
+    } catch (err) {
+      assert.include(err.message, "InvalidGraduationConfig");
+    }
+
+    // Graduates once a single token is paid for
+    const lpAllocation = new BN(1_000_000_000_000);
+    await program.methods
+      .setGraduation({ reserveThreshold: new BN(10_000_000), claimedThreshold: null, lpAllocation })
+      .accounts({ authority, vampState, mintAccount })
+      .rpc();
+
+    const graduate = (computeUnits: number) =>
+      program.methods
+        .graduate()
+        .accounts({
+          payer: authority,
+          vampState,
+          mintAccount,
+          lpVault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        // Keeps the retry from being a duplicate of the rejected transaction
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: computeUnits })])
+        .rpc();
+
+    try {
+      await graduate(200_000);
+      assert.fail("Graduation below the threshold should have failed");
+    } catch (err) {
+      assert.include(err.message, "GraduationNotReached");
+    }
+
+    const claimerTokenAccount = await getAssociatedTokenAddress(mintAccount, claimerKeypair.publicKey);
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        createAssociatedTokenAccountInstruction(claimerKeypair.publicKey, claimerTokenAccount, claimerKeypair.publicKey, mintAccount)
+      ),
+      [claimerKeypair]
+    );
+    const { solverSignature, validatorSignature, ownerSignature } = await getSignatures();
+    await program.methods
+      .claim(getEthAddressBytes(), new BN(1_000_000_000), null)
+      .accounts({
+        authority: claimerKeypair.publicKey,
+        vampState,
+        globalConfig: accounts.globalConfig,
+        claimState,
+        vault,
+        solVault,
+        claimerTokenAccount,
+        mintAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        instructionsSysvar: SYSVAR_INSTRUCTIONS_PUBKEY,
+        vestingState: null,
+        claimBitmap: null,
+        protocolTreasury: null,
+        referrer: null,
+        epochSnapshot: null,
+      })
+      .preInstructions(claimSignatureInstructions(getOwnerAddress(), solverSignature, validatorSignature, ownerSignature))
+      .signers([claimerKeypair])
+      .rpc();
+
+    await graduate(200_001);
+
+    const vampStateAccount = await program.account.vampState.fetch(vampState);
+    assert.deepEqual(vampStateAccount.status, { graduated: {} });
+    assert.equal(vampStateAccount.reserveBalance.toString(), "10000000");
+    const lpVaultBalance = await provider.connection.getTokenAccountBalance(lpVault);
+    assert.equal(lpVaultBalance.value.amount, lpAllocation.toString());
+
+    // The reserve now belongs to the pool
+    try {
+      await program.methods
+        .withdrawReserve(new BN(1))
+        .accounts({ authority, vampState, solVault, treasury: authority, mintAccount })
+        .rpc();
+      assert.fail("Withdrawal from a graduated vamp should have failed");
+    } catch (err) {
+      assert.include(err.message, "VampGraduated");
+    }
+
+    try {
+      await program.methods
+        .redeem(new BN(1_000_000_000), null)
+        .accounts({
+          authority: claimerKeypair.publicKey,
+          vampState,
+          vault,
+          solVault,
+          redeemerTokenAccount: claimerTokenAccount,
+          mintAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([claimerKeypair])
+        .rpc();
+      assert.fail("Redeem from a graduated vamp should have failed");
+    } catch (err) {
+      assert.include(err.message, "VampGraduated");
+    }
+  });
+
+  it("Updates token metadata and hands over the update authority", async () => {
+    // Metaplex metadata of the first vamp, the name follows the key and the two pubkeys
+    const accounts = await setupInitAccounts(authority);
+    await program.methods
+      .updateTokenMetadata("Renamed Memetoken", null, null)
+      .accounts({
+        authority,
+        vampState: accounts.vampState,
+        mintAccount: accounts.mintAccount,
+        metadataAccount: accounts.metadataAccount,
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+    const metaplexInfo = await provider.connection.getAccountInfo(accounts.metadataAccount);
+    const nameLength = metaplexInfo.data.readUInt32LE(65);
+    const name = metaplexInfo.data.subarray(69, 69 + nameLength).toString().replace(/\0+$/, "");
+    assert.equal(name, "Renamed Memetoken");
+
+    // Token-2022 metadata lives in the mint, which grows for longer fields
+    const [mintAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('mint'), authority.toBuffer(), new BN(vampIdBase + 9).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [extensionsVampState] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vamp"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [vault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    const [solVault] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sol_vault"), mintAccount.toBuffer()], PROGRAM_ID);
+    await program.methods
+      .createTokenExtensionsMint(
+        new BN(vampIdBase + 9),  // Vamp ID
+        9,  // Decimals
+        "My Memetoken",  // Token Name
+        "MEME",  // Token Symbol
+        Buffer.from([10, 11, 85, 6, 100, 79, 145, 115, 236, 165, 13, 29, 125, 44, 172, 229, 150, 165, 229, 85]),  // Token ERC20 Address
+        "https://example.com/token/1",  // Token URI
+        new BN(312012000000000),  // Amount
+        Buffer.from(SOLVER_ADDRESS),  // Solver Public Key
+        [VALIDATOR_ADDRESS],  // Validator set
+        1,  // Validator threshold
+        INTENT_ID,  // Intent ID
+        new BN(SOURCE_CHAIN_ID),  // Source chain ID
+        null,  // Merkle root
+        null,  // Claim deadline
+        null,  // Vesting schedule
+        { accounts: {} },  // Claim tracking
+        null,  // Vamping params, free claiming when absent
+      )
+      .accounts({
+        authority,
+        mintAccount,
+        vampState: extensionsVampState,
+        vault,
+        solVault,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({ units: 2_000_000 })])
+      .rpc();
+
+    const extensionsAccounts = {
+      authority,
+      vampState: extensionsVampState,
+      mintAccount,
+      metadataAccount: null,
+      tokenMetadataProgram: null,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+    await program.methods
+      .updateTokenMetadata(null, "MEME2", "https://example.com/token/1/community-metadata.json")
+      .accounts(extensionsAccounts)
+      .rpc();
+    let metadata = await getTokenMetadata(provider.connection, mintAccount, "confirmed", TOKEN_2022_PROGRAM_ID);
+    assert.equal(metadata.name, "My Memetoken");
+    assert.equal(metadata.symbol, "MEME2");
+    assert.equal(metadata.uri, "https://example.com/token/1/community-metadata.json");
+
+    const community = anchor.web3.Keypair.generate().publicKey;
+    await program.methods
+      .transferMetadataAuthority(community)
+      .accounts(extensionsAccounts)
+      .rpc();
+    metadata = await getTokenMetadata(provider.connection, mintAccount, "confirmed", TOKEN_2022_PROGRAM_ID);
+    assert.isTrue(metadata.updateAuthority.equals(community));
+
+    try {
+      await program.methods
+        .updateTokenMetadata("Taken Back", null, null)
+        .accounts(extensionsAccounts)
+        .rpc();
+      assert.fail("Update after the authority transfer should have failed");
+    } catch (err) {
+      assert.include(err.message, "MetadataAuthorityTransferred");
+    }
   });
 });
\ No newline at end of file
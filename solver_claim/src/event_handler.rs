@@ -1,22 +1,28 @@
 use std::sync::Arc;
 
-use crate::{cfg::Cfg, events::ClaimToken};
+use crate::{cfg::Cfg, events::ClaimToken, mysql_conn::create_db_conn};
+use alloy_primitives::{Signature, keccak256};
 use anchor_client::{Client as AnchorClient, Cluster, Program};
 use anchor_lang::declare_program;
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use array_bytes::vec2array;
-use balance_util::convert_to_sol_with_dec;
+use balance_util::{convert_to_sol_with_dec, get_balance_claim_typed_data, get_ownership_typed_data};
 use intent_id_util::fold_intent_id;
 use solana_sdk::{
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    system_program,
+    system_program, sysvar,
 };
 use solana_transaction_util::{
-    SolanaTransaction,
-    solana_vamp_program::client::{accounts, args},
+    SolanaTransaction, claim_state_address,
+    secp256k1::EthSignature,
+    solana_vamp_program::{
+        client::{accounts, args},
+        types::ClaimTracking,
+    },
+    vesting_state_address,
 };
-use spl_token::ID as TOKEN_PROGRAM_ID;
+use sqlx::Row;
 use tracing::info;
 
 
@@ -28,6 +34,101 @@ fn get_program_instance(payer_keypair: Arc<Keypair>) -> Result<Program<Arc<Keypa
     Ok(anchor_client.program(solana_vamp_program::ID)?)
 }
 
+/// Pairs an EIP-712 signature with the address that produced it, for the Secp256k1 program.
+/// `typed_data` is the encoded typed data, its keccak is the signed digest.
+fn eth_signature(signature: &[u8], typed_data: &[u8]) -> Result<EthSignature> {
+    let signer = Signature::try_from(signature)?.recover_address_from_prehash(&keccak256(typed_data))?;
+    Ok(EthSignature {
+        eth_address: signer.into_array(),
+        signature: vec2array::<_, 65>(signature.to_vec())?,
+        message: typed_data.to_vec(),
+    })
+}
+
+/// The BalanceClaim typed data the solver and validators signed. Balances are signed in the vamp's
+/// current signer epoch or, before the last key rotation, in the one before it, the solver
+/// signature tells which. The program accepts the latter only during the rotation grace period.
+fn balance_claim_typed_data(
+    event: &ClaimToken,
+    balance: u64,
+    source_chain_id: u64,
+    signer_epoch: u32,
+    solver_public_key: &[u8],
+) -> Result<Vec<u8>> {
+    let typed_data = |signer_epoch| {
+        get_balance_claim_typed_data(
+            event.claimer.as_slice(),
+            balance,
+            event.intent_id.as_slice(),
+            signer_epoch,
+            source_chain_id,
+            &solana_vamp_program::ID.to_bytes(),
+        )
+        .map_err(|e| anyhow!("get balance claim typed data: {}", e))
+    };
+    let current = typed_data(signer_epoch)?;
+    let solver = eth_signature(event.solver_signature.as_ref(), &current)?.eth_address;
+    match signer_epoch.checked_sub(1) {
+        Some(previous_epoch) if solver.as_slice() != solver_public_key => typed_data(previous_epoch),
+        _ => Ok(current),
+    }
+}
+
+/// Checks that the owner signed the claim for the wallet owning the destination token account.
+/// The program enforces the same rule, this only avoids paying for transactions that will fail.
+fn verify_ownership_signature(
+    event: &ClaimToken,
+    balance: u64,
+    destination_owner: &Pubkey,
+    source_chain_id: u64,
+) -> Result<EthSignature> {
+    let ownership_typed_data = get_ownership_typed_data(
+        event.claimer.as_slice(),
+        balance,
+        event.intent_id.as_slice(),
+        &destination_owner.to_bytes(),
+        source_chain_id,
+        &solana_vamp_program::ID.to_bytes(),
+    )
+    .map_err(|e| anyhow!("get ownership typed data: {}", e))?;
+    let signature = eth_signature(event.owner_signature.as_ref(), &ownership_typed_data)?;
+    if signature.eth_address != event.claimer.into_array() {
+        return Err(anyhow!(
+            "Ownership signature of {} doesn't authorize a claim to {}",
+            event.claimer,
+            destination_owner
+        ));
+    }
+    Ok(signature)
+}
+
+/// Finds the mint an intent was vamped or topped up into. Top-ups reuse the mint created
+/// for the first intent, so it can't be derived from the claimed intent alone.
+async fn read_mint_account(cfg: &Cfg, intent_id: &[u8]) -> Result<Option<Pubkey>> {
+    let conn = create_db_conn(cfg)
+        .await
+        .map_err(|e| anyhow!("create DB connection: {}", e))?;
+    let row = sqlx::query(
+        r#"
+            SELECT mint_account_address
+            FROM clonings
+            WHERE intent_id = ?
+            ORDER BY ts DESC LIMIT 1
+        "#,
+    )
+    .bind(hex::encode(intent_id))
+    .fetch_optional(&conn)
+    .await
+    .context("fetch cloning")?;
+    row.map(|row| {
+        let mint_account = row.get::<&str, usize>(0);
+        mint_account
+            .parse()
+            .map_err(|e| anyhow!("Invalid mint account {}: {}", mint_account, e))
+    })
+    .transpose()
+}
+
 pub struct ClaimHandler {
     pub cfg: Arc<Cfg>,
 }
@@ -40,16 +141,22 @@ impl ClaimHandler {
     pub async fn handle(&self, event: ClaimToken) -> Result<()> {
         let solana_payer_keypair =
             Arc::new(Keypair::from_base58_string(&self.cfg.solana_private_key));
-        let (mint_account, _) = Pubkey::find_program_address(
-            &[
-                b"mint",
-                solana_payer_keypair.pubkey().as_ref(),
-                fold_intent_id(event.intent_id.as_slice())?
-                    .to_le_bytes()
-                    .as_ref(),
-            ],
-            &solana_vamp_program::ID,
-        );
+        let mint_account = match read_mint_account(&self.cfg, event.intent_id.as_slice()).await? {
+            Some(mint_account) => mint_account,
+            None => {
+                Pubkey::find_program_address(
+                    &[
+                        b"mint",
+                        solana_payer_keypair.pubkey().as_ref(),
+                        fold_intent_id(event.intent_id.as_slice())?
+                            .to_le_bytes()
+                            .as_ref(),
+                    ],
+                    &solana_vamp_program::ID,
+                )
+                .0
+            }
+        };
 
         let (vamp_state, _) = Pubkey::find_program_address(
             &[b"vamp", mint_account.as_ref()],
@@ -66,50 +173,130 @@ impl ClaimHandler {
             &solana_vamp_program::ID,
         );
 
-        let (claim_state, _) = Pubkey::find_program_address(
-            &[b"claim", vamp_state.as_ref()],
+        let (global_config, _) = Pubkey::find_program_address(
+            &[b"config"],
             &solana_vamp_program::ID,
         );
 
         let claimer_token_account = Pubkey::new_from_array(event.claimer_solana.0);
 
+        let solana_url = if self.cfg.default_solana_cluster == "DEVNET" {
+            self.cfg.solana_devnet_url.clone()
+        } else {
+            self.cfg.solana_mainnet_url.clone()
+        };
+
+        let solana = SolanaTransaction::new(solana_url);
+        let vamp_state_account = solana.get_vamp_state(&vamp_state).await?;
+
+        // EVM claims carry signatures, not the Merkle leaf that bitmap tracking is indexed by
+        if matches!(vamp_state_account.claim_tracking, ClaimTracking::Bitmap) {
+            return Err(anyhow!(
+                "Vamp {} tracks claims with bitmaps, its holders claim with a Merkle proof",
+                vamp_state
+            ));
+        }
+
+        // Claims are tracked per snapshot epoch, a top-up opens a new one. Claims attested for
+        // an epoch a top-up closed are made with its archive.
+        let (epoch_snapshot, snapshot_epoch) =
+            if event.intent_id.as_slice() == vamp_state_account.intent_id.as_slice() {
+                (None, vamp_state_account.snapshot_epoch)
+            } else {
+                let (epoch_snapshot, snapshot_epoch) = solana
+                    .find_epoch_snapshot(
+                        &vamp_state,
+                        vamp_state_account.snapshot_epoch,
+                        event.intent_id.as_slice(),
+                    )
+                    .await?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Vamp {} has no snapshot epoch for intent {}",
+                            vamp_state,
+                            hex::encode(event.intent_id.as_slice())
+                        )
+                    })?;
+                (Some(epoch_snapshot), snapshot_epoch)
+            };
+        let eth_address = event.claimer.into_array();
+        let claim_state = claim_state_address(&vamp_state, &eth_address, snapshot_epoch);
+
+        // Vamps are created under either token program, the claim transfers with the mint's one
+        let token_program = solana.get_mint_token_program(&mint_account).await?;
+
+        // Vamps with a vesting schedule keep the locked part of the claim in a vesting account
+        let vesting_state = vamp_state_account
+            .vesting
+            .map(|_| vesting_state_address(&vamp_state, &eth_address, snapshot_epoch));
+
+        // The protocol treasury receives its share of paid claims while the protocol fee is set
+        let global_config_account = solana.get_global_config().await?;
+        let protocol_treasury = (global_config_account.protocol_fee_bps > 0)
+            .then_some(global_config_account.protocol_treasury);
+
         let transaction_accounts = accounts::Claim {
             authority: solana_payer_keypair.pubkey(),
             vamp_state,
-            claim_state,
+            global_config,
+            epoch_snapshot,
+            claim_state: Some(claim_state),
             vault,
             sol_vault,
             claimer_token_account,
             mint_account,
-            token_program: TOKEN_PROGRAM_ID,
+            token_program,
             system_program: system_program::ID,
+            instructions_sysvar: sysvar::instructions::ID,
+            vesting_state,
+            claim_bitmap: None,
+            protocol_treasury,
+            // Relayed claims carry no referrer
+            referrer: None,
         };
 
         let balance = convert_to_sol_with_dec(&event.amount, event.decimals)?;
 
+        let destination_owner = solana
+            .get_token_account_owner(&claimer_token_account)
+            .await?;
+        // The source chain of the vamped token is part of the EIP-712 signing domain
+        let source_chain_id = vamp_state_account.source_chain_id;
+        let ownership_signature =
+            verify_ownership_signature(&event, balance, &destination_owner, source_chain_id)?;
+
+        // The solver and validators sign the balance, the program checks the recovered
+        // addresses against the vamp's signer keys
+        let balance_typed_data = balance_claim_typed_data(
+            &event,
+            balance,
+            source_chain_id,
+            vamp_state_account.signer_epoch,
+            &vamp_state_account.solver_public_key,
+        )?;
+        let mut signatures = vec![
+            ownership_signature,
+            eth_signature(event.solver_signature.as_ref(), &balance_typed_data)?,
+        ];
+        // The EVM claim carries the validator signatures concatenated
+        for validator_signature in event.validator_signature.chunks(65) {
+            signatures.push(eth_signature(validator_signature, &balance_typed_data)?);
+        }
+
         let transaction_args = args::Claim {
             eth_address: event.claimer.into_array(),
             balance,
-            ownership_sig: vec2array::<_, 65>(event.owner_signature.to_vec())?,
-            solver_individual_balance_sig: vec2array::<_, 65>(event.solver_signature.to_vec())?,
-            validator_individual_balance_sig: vec2array::<_, 65>(event.validator_signature.to_vec())?,
+            // The relayer pays the claim cost, the slippage bound is up to the claimer's own client
+            max_total_cost: None,
         };
 
-        let solana_url = if self.cfg.default_solana_cluster == "DEVNET" {
-            self.cfg.solana_devnet_url.clone()
-        } else {
-            self.cfg.solana_mainnet_url.clone()
-        };
-
-        let solana = SolanaTransaction::new(solana_url);
         let solana_program = Arc::new(get_program_instance(solana_payer_keypair.clone())?);
 
-        let (transaction, _, _) = solana
-            .prepare(
+        let transaction = solana
+            .prepare_with_signatures(
                 solana_payer_keypair.clone(),
                 solana_program.clone(),
-                mint_account,
-                vamp_state,
+                &signatures,
                 transaction_accounts,
                 transaction_args,
             )
@@ -17,6 +17,7 @@ mod cfg;
 mod event_handler;
 mod event_subscriber;
 mod events;
+mod mysql_conn;
 
 #[tokio::main]
 async fn main() -> Result<()> {
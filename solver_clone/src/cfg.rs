@@ -1,4 +1,5 @@
 use alloy::signers::local::PrivateKeySigner;
+use alloy_primitives::Address;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
@@ -58,6 +59,46 @@ pub struct Cfg {
     #[arg(long, env = "FLAT_PRICE_PER_TOKEN", default_value_t = 1)]
     pub flat_price_per_token: u64,
 
+    /// Commit the snapshot as a Merkle root instead of signing every holder entry
+    #[arg(long, env = "USE_MERKLE_CLAIMING", default_value_t = false, num_args(0..=1), value_parser = clap::value_parser!(bool))]
+    pub use_merkle_claiming: bool,
+
+    /// Track claims of Merkle vamps in bitmap pages instead of an account per holder, needs USE_MERKLE_CLAIMING
+    #[arg(long, env = "USE_CLAIM_BITMAP", default_value_t = false, num_args(0..=1), value_parser = clap::value_parser!(bool))]
+    pub use_claim_bitmap: bool,
+
+    /// Seconds the claim window stays open after the vamp is created, unlimited when unset
+    #[arg(long, env = "CLAIM_WINDOW_SECS")]
+    pub claim_window_secs: Option<u64>,
+
+    /// Comma separated Ethereum addresses of the vamp validators, the solver's own address when unset
+    #[arg(long, env = "VALIDATOR_ADDRESSES", value_delimiter = ',')]
+    pub validator_addresses: Vec<Address>,
+
+    /// Distinct validator signatures a claim needs
+    #[arg(long, env = "VALIDATOR_THRESHOLD", default_value_t = 1)]
+    pub validator_threshold: u8,
+
+    /// Create new vamps under Token-2022 with the metadata in the mint, classic SPL Token with Metaplex metadata otherwise
+    #[arg(long, env = "USE_TOKEN_2022", default_value_t = false, num_args(0..=1), value_parser = clap::value_parser!(bool))]
+    pub use_token_2022: bool,
+
+    /// Consecutive failures of an indexing job before it's given up
+    #[arg(long, env = "INDEXING_MAX_ATTEMPTS", default_value_t = 8)]
+    pub indexing_max_attempts: u32,
+
+    /// Seconds before the first retry of a failed block range, doubled on every further failure
+    #[arg(long, env = "INDEXING_RETRY_DELAY_SECS", default_value_t = 5)]
+    pub indexing_retry_delay_secs: u64,
+
+    /// Upper bound of the delay between indexing retries
+    #[arg(long, env = "INDEXING_MAX_RETRY_DELAY_SECS", default_value_t = 600)]
+    pub indexing_max_retry_delay_secs: u64,
+
+    /// Seconds between checkpoints of an indexing job, a restart indexes the blocks after the last one again
+    #[arg(long, env = "INDEXING_CHECKPOINT_SECS", default_value_t = 30)]
+    pub indexing_checkpoint_secs: u64,
+
     // RabbitMQ queue params
     #[arg(long, env = "AMQP_HOST")]
     pub amqp_host: String,
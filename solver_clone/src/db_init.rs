@@ -127,6 +127,15 @@ fn migrations() -> Vec<Migration> {
         Migration::new("005_add_token_ts_iundex", "Add tokens timestamp index", |db| {
             Box::pin(async move { migration_005_add_token_ts_iundex(db).await })
         }),
+        Migration::new("006_add_merkle_proof_to_tokens", "Add Merkle proofs to tokens", |db| {
+            Box::pin(async move { migration_006_add_merkle_proof_to_tokens(db).await })
+        }),
+        Migration::new("007_add_claim_amount_to_tokens", "Add top-up claim amounts to tokens", |db| {
+            Box::pin(async move { migration_007_add_claim_amount_to_tokens(db).await })
+        }),
+        Migration::new("008_create_indexing_jobs_table", "Create indexing jobs table", |db| {
+            Box::pin(async move { migration_008_create_indexing_jobs(db).await })
+        }),
     ]
 }
 
@@ -458,3 +467,43 @@ async fn migration_005_add_token_ts_iundex(db: &MySqlPool) -> Result<()> {
     ).await?;
     Ok(())
 }
+
+/// Migration 006: Add the snapshot tree index and proof to tokens table
+async fn migration_006_add_merkle_proof_to_tokens(db: &MySqlPool) -> Result<()> {
+    add_column_if_not_exists(db, "tokens", "merkle_index", "BIGINT UNSIGNED NULL").await?;
+    add_column_if_not_exists(db, "tokens", "merkle_proof", "TEXT NULL").await?;
+    Ok(())
+}
+
+/// Migration 007: Add the amount attested by a top-up, NULL when the whole holder amount is claimable
+async fn migration_007_add_claim_amount_to_tokens(db: &MySqlPool) -> Result<()> {
+    add_column_if_not_exists(db, "tokens", "claim_amount", "VARCHAR(78) NULL").await
+}
+
+/// Migration 008: Create indexing jobs table, the progress of every snapshot being indexed
+async fn migration_008_create_indexing_jobs(db: &MySqlPool) -> Result<()> {
+    create_table_if_not_exists(
+        db,
+        "indexing_jobs",
+        r#"(
+            id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+            intent_id VARCHAR(255) NOT NULL,
+            chain_id BIGINT UNSIGNED NOT NULL,
+            erc20_address CHAR(42) NOT NULL,
+            request_data TEXT NOT NULL,
+            first_block BIGINT UNSIGNED NOT NULL,
+            last_block BIGINT UNSIGNED NULL,
+            previous_snapshot LONGTEXT NOT NULL,
+            token_supply LONGTEXT NOT NULL,
+            total_amount VARCHAR(78) NOT NULL,
+            status VARCHAR(16) NOT NULL,
+            attempts INT UNSIGNED NOT NULL DEFAULT 0,
+            last_error TEXT NULL,
+            ts TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_ts TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+            UNIQUE INDEX intent_id_idx(intent_id),
+            INDEX status_idx(status)
+        )"#,
+    )
+    .await
+}
@@ -28,6 +28,8 @@ pub struct TokenClaimData {
     pub mint_account_address: String,
     pub token_spl_address: String,
     pub intent_id: String,
+    pub merkle_index: Option<u64>,
+    pub merkle_proof: Vec<String>,
 }
 
 pub async fn handle_get_claim_amount(
@@ -73,11 +75,13 @@ pub async fn handle_get_claim_amount(
         mint_account_address: "".to_string(),
         token_spl_address: "".to_string(),
         intent_id: "".to_string(),
+        merkle_index: None,
+        merkle_proof: Vec::new(),
     };
 
     let rows = sqlx::query(
         r#"
-            SELECT holder_amount, signature
+            SELECT COALESCE(claim_amount, holder_amount), signature, merkle_index, merkle_proof
             FROM tokens
             WHERE intent_id = ?
               AND holder_address = ?
@@ -107,6 +111,15 @@ pub async fn handle_get_claim_amount(
     claim_data.solver_signature = solver_signature.to_string();
     // Temporary duplication of the solver signature, the validator signature will be added later
     claim_data.validator_signature = solver_signature.to_string();
+    // Set for the vamps committed as a Merkle root, the proof is stored as concatenated 32-byte nodes
+    claim_data.merkle_index = row.get::<Option<u64>, usize>(2);
+    if let Some(merkle_proof) = row.get::<Option<&str>, usize>(3) {
+        claim_data.merkle_proof = merkle_proof
+            .as_bytes()
+            .chunks(64)
+            .map(|node| format!("0x{}", String::from_utf8_lossy(node)))
+            .collect();
+    }
 
     let rows = sqlx::query(
         r#"
@@ -0,0 +1,322 @@
+use std::{cmp::min, collections::HashMap, time::Duration};
+
+use alloy_primitives::{Address, U256};
+use anyhow::{Context, Result, anyhow};
+use sqlx::{MySqlPool, Row, mysql::MySqlRow};
+use tracing::warn;
+
+use crate::snapshot_indexer::{TokenAmount, TokenRequestData};
+
+/// Where a job is, only `Indexing` jobs are resumed on startup
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    Indexing,
+    Sending,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Indexing => "indexing",
+            JobStatus::Sending => "sending",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+/// A snapshot being indexed, checkpointed periodically so a restart or an RPC failure
+/// continues from the last checkpointed block
+#[derive(Clone)]
+pub struct IndexingJob {
+    pub id: u64,
+    pub request_data: TokenRequestData,
+    pub first_block: u64,
+    pub last_block: Option<u64>,
+    /// Balances already vamped, a top-up only attests what holders gained since
+    pub previous_snapshot: HashMap<Address, TokenAmount>,
+    pub token_supply: HashMap<Address, TokenAmount>,
+    pub total_amount: U256,
+    /// Consecutive failures, reset whenever a block range gets through
+    pub attempts: u32,
+}
+
+impl IndexingJob {
+    pub fn next_block(&self) -> u64 {
+        self.last_block.map_or(self.first_block, |block| block + 1)
+    }
+
+    /// Applies a `Transfer` to the balances. Mints come from the zero address and burns go to it,
+    /// a sender whose balance doesn't cover the value is truncated to zero.
+    pub fn apply_transfer(&mut self, from: Address, to: Address, value: U256) -> Result<()> {
+        if to != Address::ZERO {
+            let balance = self.token_supply.entry(to).or_default();
+            balance.amount = balance
+                .amount
+                .checked_add(value)
+                .ok_or_else(|| anyhow!("balance of {:?} overflows", to))?;
+        }
+        if from != Address::ZERO
+            && let Some(balance) = self.token_supply.get_mut(&from)
+        {
+            balance.amount = balance.amount.checked_sub(value).unwrap_or_else(|| {
+                warn!(
+                    "Token amount for address {:?} = {} is less than the deducted value {}. Setting to zero.",
+                    from, balance.amount, value
+                );
+                U256::ZERO
+            });
+        }
+        self.total_amount = self
+            .total_amount
+            .checked_add(value)
+            .ok_or_else(|| anyhow!("total amount overflows"))?;
+        Ok(())
+    }
+}
+
+/// Splits the blocks from `next_block` up to `last_block` into ranges of at most `step` blocks
+pub fn block_ranges(next_block: u64, last_block: u64, step: u64) -> impl Iterator<Item = (u64, u64)> {
+    (next_block..=last_block)
+        .step_by(step as usize)
+        .map(move |from| (from, min(from + step - 1, last_block)))
+}
+
+/// Records the intent to index, `None` when the intent already has a job
+pub async fn create_job(
+    db: &MySqlPool,
+    request_data: TokenRequestData,
+    first_block: u64,
+    previous_snapshot: HashMap<Address, TokenAmount>,
+    total_amount: U256,
+) -> Result<Option<IndexingJob>> {
+    let result = sqlx::query(
+        r#"
+            INSERT IGNORE INTO indexing_jobs (
+                intent_id,
+                chain_id,
+                erc20_address,
+                request_data,
+                first_block,
+                previous_snapshot,
+                token_supply,
+                total_amount,
+                status
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(hex::encode(&request_data.intent_id))
+    .bind(request_data.chain_id)
+    .bind(format!("{:#x}", request_data.erc20_address))
+    .bind(serde_json::to_string(&request_data)?)
+    .bind(first_block)
+    .bind(serde_json::to_string(&previous_snapshot)?)
+    .bind(serde_json::to_string(&previous_snapshot)?)
+    .bind(total_amount.to_string())
+    .bind(JobStatus::Indexing.as_str())
+    .execute(db)
+    .await
+    .context("insert indexing job")?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(IndexingJob {
+        id: result.last_insert_id(),
+        request_data,
+        first_block,
+        last_block: None,
+        token_supply: previous_snapshot.clone(),
+        previous_snapshot,
+        total_amount,
+        attempts: 0,
+    }))
+}
+
+/// Jobs a previous run left in the middle of indexing
+pub async fn read_unfinished_jobs(db: &MySqlPool) -> Result<Vec<IndexingJob>> {
+    let rows = sqlx::query(
+        r#"
+            SELECT id, request_data, first_block, last_block, previous_snapshot, token_supply, total_amount, attempts
+            FROM indexing_jobs
+            WHERE status = ?
+            ORDER BY id
+        "#,
+    )
+    .bind(JobStatus::Indexing.as_str())
+    .fetch_all(db)
+    .await
+    .context("fetch unfinished indexing jobs")?;
+
+    rows.iter().map(read_job).collect()
+}
+
+fn read_job(row: &MySqlRow) -> Result<IndexingJob> {
+    let total_amount = row.try_get::<String, _>("total_amount")?;
+    Ok(IndexingJob {
+        id: row.try_get("id")?,
+        request_data: serde_json::from_str(&row.try_get::<String, _>("request_data")?)?,
+        first_block: row.try_get("first_block")?,
+        last_block: row.try_get("last_block")?,
+        previous_snapshot: serde_json::from_str(&row.try_get::<String, _>("previous_snapshot")?)?,
+        token_supply: serde_json::from_str(&row.try_get::<String, _>("token_supply")?)?,
+        total_amount: U256::from_str_radix(&total_amount, 10)
+            .map_err(|e| anyhow!("parse total amount {}: {}", total_amount, e))?,
+        attempts: row.try_get("attempts")?,
+    })
+}
+
+/// Checkpoints the balances up to the last fully processed block
+pub async fn save_progress(db: &MySqlPool, job: &IndexingJob) -> Result<()> {
+    sqlx::query(
+        r#"
+            UPDATE indexing_jobs
+            SET last_block = ?, token_supply = ?, total_amount = ?, attempts = ?, last_error = NULL
+            WHERE id = ?
+        "#,
+    )
+    .bind(job.last_block)
+    .bind(serde_json::to_string(&job.token_supply)?)
+    .bind(job.total_amount.to_string())
+    .bind(job.attempts)
+    .bind(job.id)
+    .execute(db)
+    .await
+    .context("save indexing progress")?;
+    Ok(())
+}
+
+pub async fn record_failure(db: &MySqlPool, job: &IndexingJob, error: &str) -> Result<()> {
+    sqlx::query("UPDATE indexing_jobs SET attempts = ?, last_error = ? WHERE id = ?")
+        .bind(job.attempts)
+        .bind(error)
+        .bind(job.id)
+        .execute(db)
+        .await
+        .context("record indexing failure")?;
+    Ok(())
+}
+
+pub async fn set_status(
+    db: &MySqlPool,
+    job_id: u64,
+    status: JobStatus,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query("UPDATE indexing_jobs SET status = ?, last_error = COALESCE(?, last_error) WHERE id = ?")
+        .bind(status.as_str())
+        .bind(error)
+        .bind(job_id)
+        .execute(db)
+        .await
+        .context("update indexing job status")?;
+    Ok(())
+}
+
+/// Fails the jobs a previous run stopped while sending to Solana. Sending again could create
+/// the vamp twice, so they are left for an operator to check.
+pub async fn fail_interrupted_sends(db: &MySqlPool) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE indexing_jobs SET status = ?, last_error = 'Interrupted while sending to Solana' WHERE status = ?",
+    )
+    .bind(JobStatus::Failed.as_str())
+    .bind(JobStatus::Sending.as_str())
+    .execute(db)
+    .await
+    .context("fail interrupted indexing jobs")?;
+    Ok(result.rows_affected())
+}
+
+/// Exponential backoff, the delay doubles with every consecutive failure up to `max_delay`
+pub fn retry_delay(attempts: u32, base_delay: Duration, max_delay: Duration) -> Duration {
+    let factor = 1u32.checked_shl(attempts.saturating_sub(1)).unwrap_or(u32::MAX);
+    base_delay.saturating_mul(factor).min(max_delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(first_block: u64, last_block: Option<u64>) -> IndexingJob {
+        IndexingJob {
+            id: 1,
+            request_data: TokenRequestData {
+                block_number: 100,
+                ..Default::default()
+            },
+            first_block,
+            last_block,
+            previous_snapshot: HashMap::new(),
+            token_supply: HashMap::new(),
+            total_amount: U256::ZERO,
+            attempts: 0,
+        }
+    }
+
+    fn balance(job: &IndexingJob, holder: Address) -> U256 {
+        job.token_supply[&holder].amount
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let base = Duration::from_secs(5);
+        let max = Duration::from_secs(600);
+        assert_eq!(retry_delay(0, base, max), base);
+        assert_eq!(retry_delay(1, base, max), base);
+        assert_eq!(retry_delay(2, base, max), Duration::from_secs(10));
+        assert_eq!(retry_delay(4, base, max), Duration::from_secs(40));
+        assert_eq!(retry_delay(8, base, max), max);
+        // The factor saturates instead of overflowing
+        assert_eq!(retry_delay(40, base, max), max);
+        assert_eq!(retry_delay(u32::MAX, base, Duration::MAX), base * u32::MAX);
+    }
+
+    #[test]
+    fn test_next_block() {
+        // A new job starts after the previous snapshot, a resumed one after its checkpoint
+        assert_eq!(job(11, None).next_block(), 11);
+        assert_eq!(job(11, Some(40)).next_block(), 41);
+    }
+
+    #[test]
+    fn test_block_ranges() {
+        assert_eq!(
+            block_ranges(11, 100, 40).collect::<Vec<_>>(),
+            vec![(11, 50), (51, 90), (91, 100)]
+        );
+
+        // Resuming from a checkpoint indexes exactly the blocks after it
+        let resumed = job(11, Some(50));
+        assert_eq!(
+            block_ranges(resumed.next_block(), 100, 40).collect::<Vec<_>>(),
+            vec![(51, 90), (91, 100)]
+        );
+
+        let done = job(11, Some(100));
+        assert_eq!(block_ranges(done.next_block(), 100, 40).count(), 0);
+        assert_eq!(block_ranges(7, 7, 40).collect::<Vec<_>>(), vec![(7, 7)]);
+    }
+
+    #[test]
+    fn test_apply_transfer() {
+        let mut job = job(1, None);
+        let alice = Address::repeat_byte(1);
+        let bob = Address::repeat_byte(2);
+
+        job.apply_transfer(Address::ZERO, alice, U256::from(100)).unwrap();
+        job.apply_transfer(alice, bob, U256::from(30)).unwrap();
+        assert_eq!(balance(&job, alice), U256::from(70));
+        assert_eq!(balance(&job, bob), U256::from(30));
+
+        // Burns leave the zero address out, overdrafts are truncated
+        job.apply_transfer(bob, Address::ZERO, U256::from(50)).unwrap();
+        assert_eq!(balance(&job, bob), U256::ZERO);
+        assert!(!job.token_supply.contains_key(&Address::ZERO));
+        assert_eq!(job.total_amount, U256::from(180));
+
+        assert!(job.apply_transfer(Address::ZERO, alice, U256::MAX).is_err());
+    }
+}
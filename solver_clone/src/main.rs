@@ -27,6 +27,7 @@ mod event_handler;
 mod event_subscriber;
 mod events;
 mod http_handler;
+mod indexing_job;
 mod mysql_conn;
 mod snapshot_indexer;
 mod snapshot_processor;
@@ -51,6 +52,10 @@ async fn main() -> Result<()> {
     let indexer = Arc::new(SnapshotIndexer::new(args.clone()).await?);
 
     let indexing_stats = Arc::new(RwLock::new(IndexerProcesses::new()));
+
+    // Jobs a previous run didn't finish continue from their last processed block
+    indexer.resume_jobs(indexing_stats.clone()).await?;
+
     let deploy_token_handler = Arc::new(CloneEventHandler::new(
         args.clone(),
         indexer.clone(),
@@ -1,8 +1,9 @@
 use std::{
-    cmp::{max, min},
+    cmp::max,
     collections::HashMap,
     str::FromStr,
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use alloy::{
@@ -13,15 +14,16 @@ use alloy::{
 use alloy_primitives::{Address, U256};
 use anyhow::{Context, Result, anyhow};
 use chrono::Utc;
-use sqlx::Row;
-use tokio::spawn;
+use serde::{Deserialize, Serialize};
+use sqlx::{MySqlPool, Row};
+use tokio::{spawn, time::sleep};
 use tracing::{error, info, warn};
 
 use crate::{
-    cfg::Cfg, chain_info::{ChainInfo, fetch_chains, get_quicknode_mapping}, events::Transfer, mysql_conn::create_db_conn, snapshot_processor::process_and_send_snapshot, stats::{IndexerProcesses, IndexerStats, VampingStatus}
+    cfg::Cfg, chain_info::{ChainInfo, fetch_chains, get_quicknode_mapping}, events::Transfer, indexing_job::{IndexingJob, JobStatus, block_ranges, create_job, fail_interrupted_sends, read_unfinished_jobs, record_failure, retry_delay, save_progress, set_status}, mysql_conn::create_db_conn, snapshot_processor::process_and_send_snapshot, stats::{IndexerProcesses, IndexerStats, VampingStatus}
 };
 
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct TokenRequestData {
     pub chain_id: u64,
     pub erc20_address: Address,
@@ -40,7 +42,7 @@ pub struct TokenRequestData {
     pub flat_price_per_token: u64,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TokenAmount {
     pub amount: U256,
     pub signature: Vec<u8>,
@@ -48,6 +50,7 @@ pub struct TokenAmount {
 
 pub struct SnapshotIndexer {
     cfg: Arc<Cfg>,
+    db: MySqlPool,
     chain_info: HashMap<u64, ChainInfo>,
     quicknode_chains: HashMap<u64, String>,
 }
@@ -65,16 +68,21 @@ impl SnapshotIndexer {
         } else {
             HashMap::new()
         };
+        let db = create_db_conn(&cfg)
+            .await
+            .map_err(|e| anyhow!("Error creating DB connection: {}", e))?;
         let res = Self {
             cfg: cfg.clone(),
+            db,
             chain_info,
             quicknode_chains,
         };
         Ok(res)
     }
 
+    /// Records an indexing job for the request and runs it in the background
     pub async fn index_snapshot(
-        &self,
+        self: &Arc<Self>,
         request_data: TokenRequestData,
         stats: Arc<RwLock<IndexerProcesses>>,
     ) -> Result<()> {
@@ -83,141 +91,204 @@ impl SnapshotIndexer {
             request_data.erc20_address, request_data.block_number
         );
 
-        let provider = Arc::new(self.connect_chain(request_data.chain_id).await?);
-
-        let (mut token_supply, prev_block_number) = self
+        let (token_supply, prev_block_number) = self
             .read_token_supply(request_data.chain_id, request_data.erc20_address)
             .await?;
 
-        let mut total_amount = token_supply
-            .iter()
-            .map(|(_, v)| v.amount)
-            .fold(U256::ZERO, |acc, x| acc.checked_add(x).unwrap_or_default());
+        let total_amount = token_supply
+            .values()
+            .try_fold(U256::ZERO, |acc, v| acc.checked_add(v.amount))
+            .ok_or_else(|| anyhow!("Total amount of the previous snapshot overflows"))?;
+
+        let intent_id = hex::encode(&request_data.intent_id);
+        let first_block = prev_block_number.unwrap_or(0) + 1;
+        match create_job(&self.db, request_data, first_block, token_supply, total_amount).await? {
+            Some(job) => self.spawn_job(job, stats),
+            // A redelivered message, the job already runs or ran
+            None => warn!("Indexing job for intent_id 0x{} already exists", intent_id),
+        }
+        Ok(())
+    }
+
+    /// Picks up the jobs a previous run didn't finish indexing
+    pub async fn resume_jobs(self: &Arc<Self>, stats: Arc<RwLock<IndexerProcesses>>) -> Result<()> {
+        let interrupted = fail_interrupted_sends(&self.db).await?;
+        if interrupted > 0 {
+            warn!("{} indexing jobs were interrupted while sending to Solana", interrupted);
+        }
+
+        for job in read_unfinished_jobs(&self.db).await? {
+            info!(
+                "Resuming indexing of token address: {:?} from block {}",
+                job.request_data.erc20_address,
+                job.next_block()
+            );
+            self.spawn_job(job, stats.clone());
+        }
+        Ok(())
+    }
 
+    fn spawn_job(self: &Arc<Self>, job: IndexingJob, stats: Arc<RwLock<IndexerProcesses>>) {
         {
             if let Ok(mut stats) = stats.write() {
                 let mut item = IndexerStats::default();
-                item.chain_id = request_data.chain_id;
-                item.token_address = request_data.erc20_address;
+                item.chain_id = job.request_data.chain_id;
+                item.token_address = job.request_data.erc20_address;
                 item.status = VampingStatus::Indexing;
                 item.start_timestamp = Utc::now().timestamp();
                 item.current_timestamp = Utc::now().timestamp();
-                stats.insert((request_data.chain_id, request_data.erc20_address), item);
+                item.start_block = job.first_block;
+                item.end_block = max(job.request_data.block_number, job.first_block);
+                item.blocks_done = job.last_block.unwrap_or_default();
+                stats.insert((job.request_data.chain_id, job.request_data.erc20_address), item);
             }
         }
-        let cfg = self.cfg.clone();
 
+        let indexer = self.clone();
         spawn(async move {
-            let first_block = prev_block_number.unwrap_or(0) + 1;
-            let latest_block = request_data.block_number;
-            {
-                if let Ok(mut stats) = stats.write() {
-                    let item = stats
-                        .get_mut(&(request_data.chain_id, request_data.erc20_address))
-                        .unwrap();
-                    item.current_timestamp = Utc::now().timestamp();
-                    item.start_block = first_block as u64;
-                    item.end_block = max(latest_block, first_block) as u64;
+            indexer.run_job(job, stats).await;
+        });
+    }
+
+    /// Indexes the remaining blocks, retrying from the last checkpoint with backoff, then sends the snapshot
+    async fn run_job(&self, mut job: IndexingJob, stats: Arc<RwLock<IndexerProcesses>>) {
+        let db = &self.db;
+        let chain_id = job.request_data.chain_id;
+        let erc20_address = job.request_data.erc20_address;
+        let set_failure = |message: String| {
+            if let Ok(mut stats) = stats.write() {
+                if let Some(item) = stats.get_mut(&(chain_id, erc20_address)) {
+                    item.status = VampingStatus::Failure;
+                    item.message = message;
                 }
             }
+        };
 
-            for b in (first_block..latest_block + 1).step_by(BLOCK_STEP as usize) {
-                let block_from = b;
-                let block_to = min(b + BLOCK_STEP - 1, latest_block);
-                info!("Processing blocks from {} to {}", block_from, block_to);
-                // Creating a filter for the Transfer event
-                let filter = Filter::new()
-                    .from_block(block_from)
-                    .to_block(block_to)
-                    .event_signature(Transfer::SIGNATURE_HASH)
-                    .address(request_data.erc20_address);
-
-                let logs = provider.get_logs(&filter).await;
-                if let Err(err) = logs {
-                    error!("Failed to get logs: {:?}", err);
-                    return;
-                }
-                let logs = logs.unwrap();
-                info!("Processing {} transfers", logs.len());
-                for log in logs {
-                    let from = log.topics()[1];
-                    let to = log.topics()[2];
-                    let value = U256::from_be_slice(log.data().data.as_ref());
-                    let from_address = Address::from_slice(&from[12..]);
-                    let to_address = Address::from_slice(&to[12..]);
-                    if to_address != Address::ZERO {
-                        match token_supply.get_mut(&to_address) {
-                            Some(v) => {
-                                v.amount = v.amount.checked_add(value).unwrap();
-                            }
-                            None => {
-                                // Fix: Create new entry with the transfer amount instead of zero
-                                token_supply.insert(
-                                    to_address,
-                                    TokenAmount {
-                                        amount: value,
-                                        signature: Vec::new(),
-                                    },
-                                );
-                            }
-                        }
-                    }
-                    if from_address != Address::ZERO {
-                        if let Some(v) = token_supply.get_mut(&from_address) {
-                            // Checking the substraction. If None then truncate the result to 0
-                            if let Some(new_amount) = v.amount.checked_sub(value) {
-                                v.amount = new_amount;
-                            } else {
-                                // If the amount is less than the value, set it to zero
-                                warn!(
-                                    "Token amount for address {:?} = {} is less than the deducted value {}. Setting to zero.",
-                                    from_address, v.amount, value
-                                );
-                                v.amount = U256::ZERO;
-                            }
-                        }
-                    }
-                    total_amount = total_amount.checked_add(value).unwrap();
-                }
-                // Update stats
-                {
-                    if let Ok(mut stats) = stats.write() {
-                        let item = stats
-                            .get_mut(&(request_data.chain_id, request_data.erc20_address))
-                            .unwrap();
-                        item.current_timestamp = Utc::now().timestamp();
-                        item.blocks_done = block_to as u64;
-                    }
+        while let Err(err) = self.index_blocks(db, &mut job, &stats).await {
+            job.attempts += 1;
+            if job.attempts >= self.cfg.indexing_max_attempts {
+                error!(
+                    "Giving up indexing token address {:?} after {} attempts: {:?}",
+                    erc20_address, job.attempts, err
+                );
+                if let Err(err) = set_status(db, job.id, JobStatus::Failed, Some(&err.to_string())).await {
+                    error!("Failed to update the indexing job: {:?}", err);
                 }
+                set_failure(err.to_string());
+                return;
             }
 
-            info!(
-                "Successfully indexed snapshot for token address: {:?}",
-                request_data.erc20_address
+            let delay = retry_delay(
+                job.attempts,
+                Duration::from_secs(self.cfg.indexing_retry_delay_secs),
+                Duration::from_secs(self.cfg.indexing_max_retry_delay_secs),
+            );
+            warn!(
+                "Indexing token address {:?} failed, attempt {} retries in {:?}: {:?}",
+                erc20_address, job.attempts, delay, err
             );
+            if let Err(err) = record_failure(db, &job, &err.to_string()).await {
+                error!("Failed to update the indexing job: {:?}", err);
+            }
+            if let Ok(mut stats) = stats.write() {
+                if let Some(item) = stats.get_mut(&(chain_id, erc20_address)) {
+                    item.current_timestamp = Utc::now().timestamp();
+                    item.message = err.to_string();
+                }
+            }
+            sleep(delay).await;
+        }
 
-            // Sending the token supply to processor
-            let chain_id = request_data.chain_id.clone();
-            let erc20_address = request_data.erc20_address.clone();
-
-            if let Err(err) = process_and_send_snapshot(
-                cfg,
-                request_data,
-                total_amount,
-                token_supply,
-                stats.clone(),
-            )
-            .await
-            {
+        info!(
+            "Successfully indexed snapshot for token address: {:?}",
+            erc20_address
+        );
+
+        if let Err(err) = set_status(db, job.id, JobStatus::Sending, None).await {
+            error!("Failed to update the indexing job: {:?}", err);
+            set_failure(err.to_string());
+            return;
+        }
+
+        // Sending the token supply to processor
+        let status = match process_and_send_snapshot(
+            self.cfg.clone(),
+            job.request_data,
+            job.total_amount,
+            job.previous_snapshot,
+            job.token_supply,
+            stats.clone(),
+        )
+        .await
+        {
+            Ok(()) => set_status(db, job.id, JobStatus::Done, None).await,
+            Err(err) => {
                 error!("Failed to process and send snapshot: {:?}", err);
+                set_failure(err.to_string());
+                set_status(db, job.id, JobStatus::Failed, Some(&err.to_string())).await
+            }
+        };
+        if let Err(err) = status {
+            error!("Failed to update the indexing job: {:?}", err);
+        }
+    }
+
+    /// Applies the transfers of the blocks left to index. The progress is checkpointed every
+    /// `indexing_checkpoint_secs` and after the last range, on an error `job` is left at the
+    /// last checkpoint so the retry doesn't apply a range twice.
+    async fn index_blocks(
+        &self,
+        db: &MySqlPool,
+        job: &mut IndexingJob,
+        stats: &Arc<RwLock<IndexerProcesses>>,
+    ) -> Result<()> {
+        let provider = self.connect_chain(job.request_data.chain_id).await?;
+        let latest_block = job.request_data.block_number;
+        let checkpoint_interval = Duration::from_secs(self.cfg.indexing_checkpoint_secs);
+        let mut last_checkpoint = Instant::now();
+        let mut progress = job.clone();
+
+        for (block_from, block_to) in block_ranges(job.next_block(), latest_block, BLOCK_STEP) {
+            info!("Processing blocks from {} to {}", block_from, block_to);
+            // Creating a filter for the Transfer event
+            let filter = Filter::new()
+                .from_block(block_from)
+                .to_block(block_to)
+                .event_signature(Transfer::SIGNATURE_HASH)
+                .address(job.request_data.erc20_address);
+
+            let logs = provider.get_logs(&filter).await.context("get logs")?;
+            info!("Processing {} transfers", logs.len());
+            for log in logs {
+                let from = log.topics()[1];
+                let to = log.topics()[2];
+                let value = U256::from_be_slice(log.data().data.as_ref());
+                progress.apply_transfer(
+                    Address::from_slice(&from[12..]),
+                    Address::from_slice(&to[12..]),
+                    value,
+                )?;
+            }
+
+            progress.last_block = Some(block_to);
+            progress.attempts = 0;
+            if block_to == latest_block || last_checkpoint.elapsed() >= checkpoint_interval {
+                save_progress(db, &progress).await?;
+                *job = progress.clone();
+                last_checkpoint = Instant::now();
+            }
+
+            // Update stats
+            {
                 if let Ok(mut stats) = stats.write() {
-                    let item = stats.get_mut(&(chain_id, erc20_address)).unwrap();
-                    item.status = VampingStatus::Failure;
-                    item.message = err.to_string();
+                    if let Some(item) = stats.get_mut(&(job.request_data.chain_id, job.request_data.erc20_address)) {
+                        item.current_timestamp = Utc::now().timestamp();
+                        item.blocks_done = block_to;
+                        item.message = String::new();
+                    }
                 }
             }
-        });
-
+        }
         Ok(())
     }
 
@@ -263,25 +334,30 @@ impl SnapshotIndexer {
         erc20_address: Address,
     ) -> Result<(HashMap<Address, TokenAmount>, Option<u64>)> {
         let mut token_supply = HashMap::new();
-        let conn = create_db_conn(&self.cfg)
-            .await
-            .map_err(|e| anyhow!("Error creating DB connection: {}", e))?;
+        let conn = &self.db;
 
         let addr_str = format!("{:#x}", erc20_address);
-        // Reading the latest intent ID for a given token
+        // Reading the latest intent ID for a given token, other tokens may have been indexed since
         let row = sqlx::query(
             r#"
                 SELECT intent_id
                 FROM tokens
                 WHERE chain_id = ?
                   AND erc20_address = ?
-                  AND ts = (SELECT MAX(ts) FROM tokens)
+                  AND ts = (
+                    SELECT MAX(ts)
+                    FROM tokens
+                    WHERE chain_id = ?
+                      AND erc20_address = ?
+                  )
                 LIMIT 1
             "#
         )
         .bind(&chain_id)
         .bind(&addr_str)
-        .fetch_optional(&conn)
+        .bind(&chain_id)
+        .bind(&addr_str)
+        .fetch_optional(conn)
         .await
         .context("fetch the latest intent")?;
         if row.is_none() {
@@ -299,7 +375,7 @@ impl SnapshotIndexer {
             "#,
         )
         .bind(&intent_id)
-        .fetch_all(&conn)
+        .fetch_all(conn)
         .await
         .context("fetch token supply")?;
 
@@ -331,7 +407,7 @@ impl SnapshotIndexer {
         )
         .bind(&chain_id)
         .bind(&addr_str)
-        .fetch_optional(&conn)
+        .fetch_optional(conn)
         .await
         .context("Fetch latest block")?;
 
@@ -1,14 +1,18 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 
 use alloy::signers::Signer;
-use alloy_primitives::{Address, U256};
+use alloy_primitives::{Address, U256, keccak256};
 use anchor_client::{Client as AnchorClient, Cluster, Program};
 use anchor_lang::declare_program;
 use anyhow::{Context, Result, anyhow};
-use balance_util::{convert_to_sol, get_balance_hash};
+use balance_util::{
+    convert_to_sol, convert_to_sol_with_dec, get_balance_claim_digest, get_snapshot_root_typed_data,
+};
 use chrono::Utc;
 use intent_id_util::fold_intent_id;
+use merkle_tree::{Leaf, MerkleTree};
 use mpl_token_metadata::ID as TOKEN_METADATA_PROGRAM_ID;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::{
@@ -16,11 +20,17 @@ use solana_sdk::{
     system_program, sysvar,
 };
 use solana_transaction_util::{
-    SolanaTransaction,
-    solana_vamp_program::client::{accounts, args},
+    AttestedInstruction, CLAIM_BITMAP_BITS, SolanaTransaction, claim_bitmap_address, epoch_snapshot_address,
+    secp256k1::EthSignature,
+    solana_vamp_program::{
+        client::{accounts, args},
+        types::{ClaimTracking, CurveKind, VampingParams},
+    },
 };
 use spl_associated_token_account::ID as ASSOCIATED_TOKEN_PROGRAM_ID;
 use spl_token::ID as TOKEN_PROGRAM_ID;
+use spl_token_2022::ID as TOKEN_2022_PROGRAM_ID;
+use sqlx::Row;
 use tracing::info;
 
 use crate::cfg::Cfg;
@@ -40,6 +50,7 @@ pub async fn process_and_send_snapshot(
     cfg: Arc<Cfg>,
     request_data: TokenRequestData,
     amount: U256,
+    previous_snapshot: HashMap<Address, TokenAmount>,
     original_snapshot: HashMap<Address, TokenAmount>,
     indexing_stats: Arc<RwLock<IndexerProcesses>>,
 ) -> Result<()> {
@@ -59,16 +70,264 @@ pub async fn process_and_send_snapshot(
     // Convert the amount into a Solana format
     let (amount, decimals) = convert_to_sol(&amount)?;
 
-    // Determine final vamping params with precedence: overrides > frontend/EVM (request_data) > solver defaults
-    let final_paid_claiming_enabled = request_data.paid_claiming_enabled;
-    let final_use_bonding_curve = request_data.use_bonding_curve;
-    let final_curve_slope = request_data.curve_slope;
-    let final_base_price = request_data.base_price;
-    let final_max_price = 0;
-    let final_flat_price_per_token = request_data.flat_price_per_token;
+    let claim_deadline = cfg
+        .claim_window_secs
+        .map(|secs| Utc::now().timestamp() + secs as i64);
+
+    // A token vamped before gets its existing mint topped up with what holders gained since
+    let existing_mint_account =
+        read_mint_account(&cfg, request_data.chain_id, request_data.erc20_address).await?;
+    let mut claim_snapshot = if existing_mint_account.is_some() {
+        snapshot_deltas(&previous_snapshot, &original_snapshot)
+    } else {
+        original_snapshot.clone()
+    };
 
-    let vamp_identifier = fold_intent_id(&request_data.intent_id)?;
     let solana_payer_keypair = Arc::new(Keypair::from_base58_string(&cfg.solana_private_key));
+    let solana_url = if cfg.default_solana_cluster == "DEVNET" {
+        cfg.solana_devnet_url.clone()
+    } else {
+        cfg.solana_mainnet_url.clone()
+    };
+    let solana = SolanaTransaction::new(solana_url);
+    let solana_program = Arc::new(get_program_instance(solana_payer_keypair.clone())?);
+
+    // A top-up mints into the existing mint, whatever decimals the grown supply would get now
+    let decimals = match existing_mint_account {
+        Some(mint_account) => solana.get_mint_decimals(&mint_account).await?,
+        None => decimals,
+    };
+
+    // In the Merkle mode the program verifies inclusion proofs against the root instead of signatures
+    let merkle_proofs = if cfg.use_merkle_claiming {
+        Some(build_merkle_proofs(&claim_snapshot, decimals)?)
+    } else {
+        None
+    };
+    let merkle_root = merkle_proofs.as_ref().map(|(root, _)| *root);
+
+    // Attestations are signed in the signer epoch of the vamp's keys, a new vamp starts at 0
+    let signer_epoch = match existing_mint_account {
+        Some(mint_account) => {
+            let (vamp_state, _) = Pubkey::find_program_address(
+                &[b"vamp", mint_account.as_ref()],
+                &solana_vamp_program::ID,
+            );
+            solana.get_vamp_state(&vamp_state).await?.signer_epoch
+        }
+        None => 0,
+    };
+    let root_signatures =
+        snapshot_root_signatures(&cfg, &request_data, merkle_root, signer_epoch).await?;
+
+    // Mint of the vamp when this snapshot opened a claim epoch on it
+    let vamped_mint_account = if let Some(mint_account) = existing_mint_account {
+        let mut top_up_amount = 0u64;
+        for supply in claim_snapshot.values() {
+            let amount = mint_amount(&supply.amount, decimals)?;
+            top_up_amount = top_up_amount
+                .checked_add(amount)
+                .ok_or(anyhow!("Top-up amount overflows"))?;
+        }
+
+        // Without new balances there is no epoch to open, the snapshot is only kept for indexing
+        if top_up_amount > 0 {
+            top_up_vamp(
+                &cfg,
+                &solana,
+                solana_payer_keypair.clone(),
+                solana_program.clone(),
+                &request_data,
+                mint_account,
+                top_up_amount,
+                merkle_root,
+                &root_signatures,
+                claim_deadline,
+            )
+            .await?;
+            Some(mint_account)
+        } else {
+            info!(
+                "No holder gained tokens since the previous snapshot of {:#x}",
+                request_data.erc20_address
+            );
+            None
+        }
+    } else {
+        Some(
+            create_vamp(
+                &cfg,
+                &solana,
+                solana_payer_keypair.clone(),
+                solana_program.clone(),
+                &request_data,
+                amount,
+                decimals,
+                merkle_root,
+                &root_signatures,
+                claim_deadline,
+            )
+            .await?,
+        )
+    };
+
+    // Holders of a bitmap tracked vamp can claim once the pages of the new epoch exist
+    if let Some(mint_account) = vamped_mint_account {
+        create_claim_bitmaps(
+            &solana,
+            solana_payer_keypair,
+            solana_program,
+            mint_account,
+            claim_snapshot.len(),
+        )
+        .await?;
+    }
+
+    // Truncate values that are < 1 Gwei, compute signatures
+    // Holders of a Merkle vamp are covered by the root and don't need individual signatures
+    if merkle_proofs.is_none() {
+        for (address, supply) in claim_snapshot.iter_mut() {
+            let amount = mint_amount(&supply.amount, decimals)?;
+            // EIP-712 BalanceClaim, so wallets can show what is being attested
+            let balance_digest = get_balance_claim_digest(
+                address.as_slice(),
+                amount,
+                &request_data.intent_id,
+                signer_epoch,
+                request_data.chain_id,
+                &solana_vamp_program::ID.to_bytes(),
+            )
+            .map_err(|e| anyhow!("get balance claim digest: {}", e))?;
+            let signature = cfg
+                .ethereum_private_key
+                .sign_hash(&balance_digest.into())
+                .await?;
+            supply.signature = signature.as_bytes().to_vec();
+        }
+    }
+
+    // Writing the token supply to the database, full balances are kept for the next indexing
+    write_token_supply(
+        &cfg,
+        request_data.chain_id,
+        request_data.erc20_address,
+        request_data.block_number,
+        &original_snapshot,
+        &claim_snapshot,
+        existing_mint_account.is_some(),
+        merkle_proofs.as_ref().map(|(_, proofs)| proofs),
+        &hex::encode(&request_data.intent_id),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// A holder amount in base units of a mint with `decimals`. The amounts of a snapshot are all
+/// converted to the decimals of its mint, [`convert_to_sol`] picks them for the whole supply.
+fn mint_amount(amount: &U256, decimals: u8) -> Result<u64> {
+    let truncated_decimals = 9u8
+        .checked_sub(decimals)
+        .ok_or(anyhow!("Vamp mints have at most 9 decimals, got {}", decimals))?;
+    convert_to_sol_with_dec(amount, truncated_decimals)
+}
+
+/// What holders gained since the previous snapshot, holders whose balance didn't grow are left out
+fn snapshot_deltas(
+    previous_snapshot: &HashMap<Address, TokenAmount>,
+    snapshot: &HashMap<Address, TokenAmount>,
+) -> HashMap<Address, TokenAmount> {
+    snapshot
+        .iter()
+        .filter_map(|(address, supply)| {
+            let previous_amount = previous_snapshot
+                .get(address)
+                .map(|previous| previous.amount)
+                .unwrap_or_default();
+            let delta = supply.amount.saturating_sub(previous_amount);
+            (delta > U256::ZERO).then(|| {
+                (
+                    *address,
+                    TokenAmount {
+                        amount: delta,
+                        signature: Vec::new(),
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+/// The solver's signature over the snapshot root, the program only commits a root attested by
+/// the solver and `validator_threshold` validators. The solver can only stand in for itself,
+/// so Merkle claiming needs the solver to be the only validator the threshold requires.
+async fn snapshot_root_signatures(
+    cfg: &Cfg,
+    request_data: &TokenRequestData,
+    merkle_root: Option<[u8; 32]>,
+    signer_epoch: u32,
+) -> Result<Vec<EthSignature>> {
+    let Some(merkle_root) = merkle_root else {
+        return Ok(Vec::new());
+    };
+    let solver_address = cfg.ethereum_private_key.address();
+    let solver_validates =
+        cfg.validator_addresses.is_empty() || cfg.validator_addresses.contains(&solver_address);
+    if !solver_validates || cfg.validator_threshold > 1 {
+        return Err(anyhow!(
+            "Snapshot roots need {} validator signatures, the solver can only provide its own",
+            cfg.validator_threshold
+        ));
+    }
+
+    let typed_data = get_snapshot_root_typed_data(
+        &merkle_root,
+        &request_data.intent_id,
+        signer_epoch,
+        request_data.chain_id,
+        &solana_vamp_program::ID.to_bytes(),
+    );
+    let signature = cfg
+        .ethereum_private_key
+        .sign_hash(&keccak256(&typed_data))
+        .await?;
+    // One signature covers the solver and its own validator entry
+    Ok(vec![EthSignature {
+        eth_address: solver_address.into_array(),
+        signature: signature.as_bytes(),
+        message: typed_data,
+    }])
+}
+
+/// Creates the mint and vamp state for the first snapshot of a token
+async fn create_vamp(
+    cfg: &Cfg,
+    solana: &SolanaTransaction,
+    solana_payer_keypair: Arc<Keypair>,
+    solana_program: Arc<Program<Arc<Keypair>>>,
+    request_data: &TokenRequestData,
+    amount: u64,
+    decimals: u8,
+    merkle_root: Option<[u8; 32]>,
+    root_signatures: &[EthSignature],
+    claim_deadline: Option<i64>,
+) -> Result<Pubkey> {
+    // Determine final vamping params with precedence: overrides > frontend/EVM (request_data) > solver defaults.
+    // The EVM request carries no price cap or curve shape yet, so vamps keep the uncapped linear curve.
+    let vamping_params = VampingParams {
+        paid_claiming_enabled: request_data.paid_claiming_enabled,
+        use_bonding_curve: request_data.use_bonding_curve,
+        curve_slope: request_data.curve_slope,
+        base_price: request_data.base_price,
+        max_price: None,
+        flat_price_per_token: request_data.flat_price_per_token,
+        curve_kind: CurveKind::Linear,
+        curve_exponent: 2,
+        curve_step_size: 0,
+        redeem_spread_bps: 0,
+    };
+
+    let vamp_identifier = fold_intent_id(&request_data.intent_id)?;
     let (mint_account, _) = Pubkey::find_program_address(
         &[
             b"mint",
@@ -77,15 +336,6 @@ pub async fn process_and_send_snapshot(
         ],
         &solana_vamp_program::ID,
     );
-    let (metadata_account, _bump) = Pubkey::find_program_address(
-        &[
-            b"metadata",
-            TOKEN_METADATA_PROGRAM_ID.as_ref(),
-            mint_account.as_ref(),
-        ],
-        &TOKEN_METADATA_PROGRAM_ID,
-    );
-
     let (vamp_state, _) =
         Pubkey::find_program_address(&[b"vamp", mint_account.as_ref()], &solana_vamp_program::ID);
 
@@ -97,66 +347,132 @@ pub async fn process_and_send_snapshot(
         &solana_vamp_program::ID,
     );
 
-    let transaction_accounts = accounts::CreateTokenMint {
-        authority: solana_payer_keypair.pubkey(),
-        // mint_account: destination_token_address,
-        mint_account,
-        metadata_account,
-        vamp_state,
-        vault,
-        sol_vault,
-        token_program: TOKEN_PROGRAM_ID,
-        token_metadata_program: TOKEN_METADATA_PROGRAM_ID,
-        system_program: system_program::ID,
-        associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
-        rent: sysvar::rent::ID,
-    };
-
-    let transaction_args = args::CreateTokenMint {
-        vamp_identifier: fold_intent_id(&request_data.intent_id)?,
-        token_decimals: decimals,
-        token_name: request_data.token_full_name,
-        token_symbol: request_data.token_symbol_name,
-        token_erc20_address: request_data.erc20_address.as_slice().to_vec(),
-        token_uri: request_data.token_uri,
-        amount,
-        solver_public_key: cfg.ethereum_private_key.address().as_slice().to_vec(),
-        validator_public_key: cfg.ethereum_private_key.address().as_slice().to_vec(),
-        intent_id: request_data.intent_id.clone(),
-        paid_claiming_enabled: final_paid_claiming_enabled,
-        use_bonding_curve: final_use_bonding_curve,
-        curve_slope: final_curve_slope,
-        base_price: final_base_price,
-        max_price: final_max_price,
-        flat_price_per_token: final_flat_price_per_token,
+    // Without a configured validator set the solver acts as the single validator
+    let validator_set: Vec<[u8; 20]> = if cfg.validator_addresses.is_empty() {
+        vec![cfg.ethereum_private_key.address().0.0]
+    } else {
+        cfg.validator_addresses.iter().map(|address| address.0.0).collect()
     };
 
-    let solana_url = if cfg.default_solana_cluster == "DEVNET" {
-        cfg.solana_devnet_url.clone()
+    // Bitmap pages are indexed by the Merkle leaf, the program requires a root for them
+    let claim_tracking = if cfg.use_claim_bitmap {
+        ClaimTracking::Bitmap
     } else {
-        cfg.solana_mainnet_url.clone()
+        ClaimTracking::Accounts
     };
 
-    let solana = SolanaTransaction::new(solana_url);
+    let (transaction, mint_account, vamp_state) = if cfg.use_token_2022 {
+        // Token-2022 mints carry their metadata, no Metaplex account is created
+        let transaction_accounts = accounts::CreateTokenExtensionsMint {
+            authority: solana_payer_keypair.pubkey(),
+            mint_account,
+            vamp_state,
+            vault,
+            sol_vault,
+            token_program: TOKEN_2022_PROGRAM_ID,
+            system_program: system_program::ID,
+            instructions_sysvar: sysvar::instructions::ID,
+        };
 
-    let solana_program = Arc::new(get_program_instance(solana_payer_keypair.clone())?);
+        let transaction_args = args::CreateTokenExtensionsMint {
+            vamp_identifier,
+            token_decimals: decimals,
+            token_name: request_data.token_full_name.clone(),
+            token_symbol: request_data.token_symbol_name.clone(),
+            token_erc20_address: request_data.erc20_address.as_slice().to_vec(),
+            token_uri: request_data.token_uri.clone(),
+            amount,
+            solver_public_key: cfg.ethereum_private_key.address().as_slice().to_vec(),
+            validator_set,
+            validator_threshold: cfg.validator_threshold,
+            intent_id: request_data.intent_id.clone(),
+            source_chain_id: request_data.chain_id,
+            merkle_root,
+            claim_deadline,
+            vesting: None,
+            claim_tracking,
+            vamping_params: Some(vamping_params),
+        };
 
-    let (transaction, mint_account, vamp_state) = solana
-        .prepare(
-            solana_payer_keypair.clone(),
-            solana_program.clone(),
+        solana
+            .prepare_attested(
+                solana_payer_keypair,
+                solana_program,
+                mint_account,
+                vamp_state,
+                AttestedInstruction {
+                    signatures: root_signatures,
+                    accounts: transaction_accounts,
+                    args: transaction_args,
+                },
+            )
+            .await?
+    } else {
+        let (metadata_account, _bump) = Pubkey::find_program_address(
+            &[
+                b"metadata",
+                TOKEN_METADATA_PROGRAM_ID.as_ref(),
+                mint_account.as_ref(),
+            ],
+            &TOKEN_METADATA_PROGRAM_ID,
+        );
+
+        let transaction_accounts = accounts::CreateTokenMint {
+            authority: solana_payer_keypair.pubkey(),
+            // mint_account: destination_token_address,
             mint_account,
+            metadata_account,
             vamp_state,
-            transaction_accounts,
-            transaction_args,
-        )
-        .await?;
+            vault,
+            sol_vault,
+            token_program: TOKEN_PROGRAM_ID,
+            token_metadata_program: TOKEN_METADATA_PROGRAM_ID,
+            system_program: system_program::ID,
+            associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+            rent: sysvar::rent::ID,
+            instructions_sysvar: sysvar::instructions::ID,
+        };
+
+        let transaction_args = args::CreateTokenMint {
+            vamp_identifier,
+            token_decimals: decimals,
+            token_name: request_data.token_full_name.clone(),
+            token_symbol: request_data.token_symbol_name.clone(),
+            token_erc20_address: request_data.erc20_address.as_slice().to_vec(),
+            token_uri: request_data.token_uri.clone(),
+            amount,
+            solver_public_key: cfg.ethereum_private_key.address().as_slice().to_vec(),
+            validator_set,
+            validator_threshold: cfg.validator_threshold,
+            intent_id: request_data.intent_id.clone(),
+            source_chain_id: request_data.chain_id,
+            merkle_root,
+            claim_deadline,
+            vesting: None,
+            claim_tracking,
+            vamping_params: Some(vamping_params),
+        };
+
+        solana
+            .prepare_attested(
+                solana_payer_keypair,
+                solana_program,
+                mint_account,
+                vamp_state,
+                AttestedInstruction {
+                    signatures: root_signatures,
+                    accounts: transaction_accounts,
+                    args: transaction_args,
+                },
+            )
+            .await?
+    };
 
     let solana_txid = solana.submit_transaction(transaction).await?;
 
     info!("Solution transaction submitted: {}", solana_txid);
     write_cloning(
-        &cfg,
+        cfg,
         request_data.chain_id,
         request_data.erc20_address,
         solana_txid.to_string(),
@@ -165,35 +481,197 @@ pub async fn process_and_send_snapshot(
         &hex::encode(&request_data.intent_id),
     )
     .await?;
+    Ok(mint_account)
+}
 
-    let mut ethereum_snapshot = original_snapshot.clone();
-    // Truncate values that are < 1 Gwei, compute signatures
-    for (address, supply) in ethereum_snapshot.iter_mut() {
-        let (amount, _) = convert_to_sol(&supply.amount)?;
-        let balance_hash = get_balance_hash(
-            &address.as_slice().to_vec(),
-            amount,
-            &request_data.intent_id,
+/// Mints the supply gained since the previous snapshot into the existing vault,
+/// opening a new claim epoch for the request intent
+async fn top_up_vamp(
+    cfg: &Cfg,
+    solana: &SolanaTransaction,
+    solana_payer_keypair: Arc<Keypair>,
+    solana_program: Arc<Program<Arc<Keypair>>>,
+    request_data: &TokenRequestData,
+    mint_account: Pubkey,
+    amount: u64,
+    merkle_root: Option<[u8; 32]>,
+    root_signatures: &[EthSignature],
+    claim_deadline: Option<i64>,
+) -> Result<()> {
+    let (vamp_state, _) =
+        Pubkey::find_program_address(&[b"vamp", mint_account.as_ref()], &solana_vamp_program::ID);
+
+    let (vault, _) =
+        Pubkey::find_program_address(&[b"vault", mint_account.as_ref()], &solana_vamp_program::ID);
+
+    // The top-up mints under the token program the vamp was created with
+    let token_program = solana.get_mint_token_program(&mint_account).await?;
+
+    // The top-up archives the epoch it closes, its holders keep claiming with its attestations
+    let snapshot_epoch = solana.get_vamp_state(&vamp_state).await?.snapshot_epoch;
+
+    let transaction_accounts = accounts::TopUpVamp {
+        authority: solana_payer_keypair.pubkey(),
+        vamp_state,
+        mint_account,
+        vault,
+        token_program,
+        instructions_sysvar: sysvar::instructions::ID,
+        epoch_snapshot: epoch_snapshot_address(&vamp_state, snapshot_epoch),
+        system_program: system_program::ID,
+    };
+
+    let transaction_args = args::TopUpVamp {
+        amount,
+        intent_id: request_data.intent_id.clone(),
+        merkle_root,
+        claim_deadline,
+    };
+
+    let (transaction, mint_account, vamp_state) = solana
+        .prepare_attested(
+            solana_payer_keypair,
+            solana_program,
+            mint_account,
+            vamp_state,
+            AttestedInstruction {
+                signatures: root_signatures,
+                accounts: transaction_accounts,
+                args: transaction_args,
+            },
         )
-        .map_err(|e| anyhow!("get balance hash: {}", e))?;
-        let signature = cfg.ethereum_private_key.sign_message(&balance_hash).await?;
-        supply.signature = signature.as_bytes().to_vec();
-    }
+        .await?;
 
-    // Writing the token supply to the database
-    write_token_supply(
-        &cfg,
+    let solana_txid = solana.submit_transaction(transaction).await?;
+
+    info!("Top-up transaction submitted: {}", solana_txid);
+    // The new intent points to the same mint, claims of the new epoch are looked up by it
+    write_cloning(
+        cfg,
         request_data.chain_id,
         request_data.erc20_address,
-        request_data.block_number,
-        &ethereum_snapshot,
+        solana_txid.to_string(),
+        &mint_account.to_string(),
+        &vamp_state.to_string(),
         &hex::encode(&request_data.intent_id),
     )
-    .await?;
+    .await
+}
+
+/// Creates the bitmap pages covering the holders of the vamp's current snapshot epoch,
+/// vamps tracking claims with an account per holder need none
+async fn create_claim_bitmaps(
+    solana: &SolanaTransaction,
+    solana_payer_keypair: Arc<Keypair>,
+    solana_program: Arc<Program<Arc<Keypair>>>,
+    mint_account: Pubkey,
+    holders: usize,
+) -> Result<()> {
+    let (vamp_state, _) =
+        Pubkey::find_program_address(&[b"vamp", mint_account.as_ref()], &solana_vamp_program::ID);
+    let vamp_state_account = solana.get_vamp_state(&vamp_state).await?;
+    if !matches!(vamp_state_account.claim_tracking, ClaimTracking::Bitmap) {
+        return Ok(());
+    }
+
+    let holders = holders as u64;
+    for page in 0..holders.div_ceil(CLAIM_BITMAP_BITS) {
+        // The leaf count bounds the last page, the padding leaves of the tree can't be claimed
+        let page_holders = (holders - page * CLAIM_BITMAP_BITS).min(CLAIM_BITMAP_BITS);
+        let page = u32::try_from(page).map_err(|_| anyhow!("Too many claim bitmap pages"))?;
+
+        let transaction_accounts = accounts::CreateClaimBitmap {
+            authority: solana_payer_keypair.pubkey(),
+            vamp_state,
+            claim_bitmap: claim_bitmap_address(&vamp_state, page, vamp_state_account.snapshot_epoch),
+            mint_account,
+            system_program: system_program::ID,
+        };
 
+        let transaction_args = args::CreateClaimBitmap {
+            page,
+            holders: page_holders as u32,
+        };
+
+        let (transaction, _, _) = solana
+            .prepare(
+                solana_payer_keypair.clone(),
+                solana_program.clone(),
+                mint_account,
+                vamp_state,
+                transaction_accounts,
+                transaction_args,
+            )
+            .await?;
+
+        let solana_txid = solana.submit_transaction(transaction).await?;
+        info!("Claim bitmap page {} transaction submitted: {}", page, solana_txid);
+    }
     Ok(())
 }
 
+/// Returns the mint a token was vamped into, if it was vamped before
+async fn read_mint_account(
+    cfg: &Cfg,
+    chain_id: u64,
+    erc20_address: Address,
+) -> Result<Option<Pubkey>> {
+    let conn = create_db_conn(cfg)
+        .await
+        .map_err(|e| anyhow!("create DB connection: {}", e))?;
+    let addr_str = format!("{:#x}", erc20_address);
+
+    let row = sqlx::query(
+        r#"
+            SELECT mint_account_address
+            FROM clonings
+            WHERE chain_id = ?
+              AND erc20_address = ?
+            ORDER BY ts DESC LIMIT 1
+        "#,
+    )
+    .bind(&chain_id)
+    .bind(&addr_str)
+    .fetch_optional(&conn)
+    .await
+    .context("fetch cloning")?;
+
+    row.map(|row| {
+        let mint_account = row.get::<&str, usize>(0);
+        Pubkey::from_str(mint_account)
+            .map_err(|e| anyhow!("Invalid mint account {}: {}", mint_account, e))
+    })
+    .transpose()
+}
+
+/// Builds the snapshot tree over holders ordered by address, so the leaf indices are reproducible.
+/// Returns the root and the (index, proof) pair of every holder.
+fn build_merkle_proofs(
+    snapshot: &HashMap<Address, TokenAmount>,
+    decimals: u8,
+) -> Result<([u8; 32], HashMap<Address, (u64, Vec<[u8; 32]>)>)> {
+    let mut holders: Vec<&Address> = snapshot.keys().collect();
+    holders.sort();
+
+    let mut leaves = Vec::with_capacity(holders.len());
+    for address in &holders {
+        let amount = mint_amount(&snapshot[*address].amount, decimals)?;
+        leaves.push(Leaf {
+            account: address.0.0,
+            amount,
+            decimals,
+        });
+    }
+    let tree = MerkleTree::new(&leaves);
+
+    let proofs = holders
+        .into_iter()
+        .enumerate()
+        .map(|(index, address)| (*address, (index as u64, tree.generate_proof(index))))
+        .collect();
+    Ok((tree.root, proofs))
+}
+
 async fn write_cloning(
     cfg: &Cfg,
     chain_id: u64,
@@ -239,6 +717,9 @@ async fn write_token_supply(
     erc20_address: Address,
     block_number: u64,
     token_supply: &HashMap<Address, TokenAmount>,
+    claim_snapshot: &HashMap<Address, TokenAmount>,
+    top_up: bool,
+    merkle_proofs: Option<&HashMap<Address, (u64, Vec<[u8; 32]>)>>,
     intent_id: &str,
 ) -> Result<()> {
     let conn = create_db_conn(cfg)
@@ -247,10 +728,23 @@ async fn write_token_supply(
     let mut tx = conn.begin().await.context("begin tx")?;
     let str_address = format!("{:#x}", erc20_address);
 
-    // Insert new supplies
+    // Insert new supplies, a top-up attests only the gained balance stored as claim_amount
     for (token_address, supply) in token_supply {
         let addr_str = format!("{:#x}", erc20_address);
         let token_addr_str = format!("{:#x}", token_address);
+        let claim = claim_snapshot.get(token_address);
+        let claim_amount = top_up.then(|| {
+            claim
+                .map(|claim| claim.amount)
+                .unwrap_or_default()
+                .to_string()
+        });
+        let signature = claim.map(|claim| hex::encode(&claim.signature)).unwrap_or_default();
+        let (merkle_index, merkle_proof) =
+            match merkle_proofs.and_then(|proofs| proofs.get(token_address)) {
+                Some((index, proof)) => (Some(*index), Some(hex::encode(proof.concat()))),
+                None => (None, None),
+            };
         sqlx::query(
             r#"
                 INSERT INTO tokens (
@@ -259,17 +753,23 @@ async fn write_token_supply(
                     holder_address,
                     holder_amount,
                     signature,
-                    intent_id
+                    intent_id,
+                    merkle_index,
+                    merkle_proof,
+                    claim_amount
                 )
-                VALUES (?, ?, ?, ?, ?, ?)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&chain_id)
         .bind(&addr_str)
         .bind(&token_addr_str)
         .bind(supply.amount.to_string().as_str())
-        .bind(hex::encode(&supply.signature).as_str())
+        .bind(signature.as_str())
         .bind(intent_id)
+        .bind(merkle_index)
+        .bind(merkle_proof)
+        .bind(claim_amount)
         .execute(&mut *tx)
         .await
         .context("insert token supply")?;
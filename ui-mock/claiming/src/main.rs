@@ -1,11 +1,13 @@
 use anyhow::{anyhow, Result};
 use clap::Parser;
-use libsecp256k1::{sign, Message, SecretKey};
+use libsecp256k1::{recover, sign, Message, RecoveryId, SecretKey, Signature};
 use reqwest;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sha3::{Digest, Keccak256};
+use base64::Engine;
 use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
     pubkey::Pubkey,
@@ -13,13 +15,24 @@ use solana_sdk::{
     signer::Signer as SolanaSigner,
     transaction::Transaction,
 };
-use spl_associated_token_account::get_associated_token_address;
-use spl_token;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
 use std::str::FromStr;
 
 // Embed IDL for dynamic error decoding
 const VAMP_IDL_JSON: &str = include_str!("../../../idls/solana_vamp_program.json");
 
+const VAMP_PROGRAM_ID: &str = "FAyBECn6ppQgRwb5R4LryAzNic3XwsCuHakVpD1X7hFW";
+
+// EIP-712 types of the claim signatures, must match balance_util
+const EIP712_DOMAIN_TYPE: &[u8] =
+    b"EIP712Domain(string name,string version,uint256 chainId,bytes32 salt)";
+const EIP712_DOMAIN_NAME: &[u8] = b"vamp.fun";
+const EIP712_DOMAIN_VERSION: &[u8] = b"1";
+const BALANCE_CLAIM_TYPE: &[u8] =
+    b"BalanceClaim(address holder,uint256 amount,bytes intentId,uint32 signerEpoch)";
+const CLAIM_OWNERSHIP_TYPE: &[u8] =
+    b"ClaimOwnership(address holder,uint256 amount,bytes intentId,bytes32 recipient)";
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -56,6 +69,18 @@ struct Args {
 
     #[arg(long, default_value = "21363", help = "Chain ID")]
     chain_id: u64,
+
+    #[arg(
+        long,
+        help = "Maximum SOL cost in lamports the claim may charge (slippage bound)"
+    )]
+    max_total_cost: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Wallet that referred the claim, receives the referral fee paid on top of the claim cost"
+    )]
+    referrer: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -78,14 +103,23 @@ struct ClaimData {
     eth_address: [u8; 20],
     balance: u64,
     solver_signature: [u8; 65],
-    validator_signature: [u8; 65],
+    validator_signatures: Vec<[u8; 65]>,
+}
+
+/// An EIP-712 signature to be verified by the Secp256k1 program instruction
+#[derive(Debug)]
+struct EthSignature {
+    eth_address: [u8; 20],
+    signature: [u8; 65],
+    // The EIP-712 encoded typed data, the signature is over its keccak
+    message: Vec<u8>,
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 struct VampState {
     solver_public_key: Vec<u8>,
-    validator_public_key: Vec<u8>,
+    validator_set: Vec<[u8; 20]>,
     intent_id: Vec<u8>,
     total_claimed: u64,
     reserve_balance: u64,
@@ -98,6 +132,10 @@ struct VampState {
     use_bonding_curve: bool,
     flat_price_per_token: u64,
     paid_claiming_enabled: bool,
+    signer_epoch: u32,
+    source_chain_id: u64,
+    vesting_enabled: bool,
+    snapshot_epoch: u32,
 }
 
 fn main() -> Result<()> {
@@ -233,18 +271,14 @@ fn main() -> Result<()> {
     println!("📋 Parsing IPFS data...");
     let claim_data = parse_ipfs_data(&ipfs_data, &eth_address_array, &vamp_state.intent_id)?;
 
-    // Calculate expected claim cost using on-chain formula
-    println!("💰 Calculating expected claim cost...");
-    let human_amount = claim_data.balance / 10u64.pow(vamping_data.decimals as u32);
-    let expected_cost = calculate_expected_claim_cost(
-        human_amount,
-        vamp_state.total_claimed,
-        vamp_state.curve_slope,
-        vamp_state.base_price,
-        vamp_state.max_price,
-        vamp_state.use_bonding_curve,
-        vamp_state.flat_price_per_token,
-        vamp_state.paid_claiming_enabled,
+    // Ask the program for the claim cost, so the quote matches the on-chain pricing exactly
+    println!("💰 Quoting claim cost...");
+    let expected_cost = quote_claim_cost(
+        &client,
+        &solana_keypair.pubkey(),
+        &mint_pubkey,
+        claim_data.balance,
+        args.referrer.is_some(),
     )?;
 
     println!(
@@ -254,8 +288,14 @@ fn main() -> Result<()> {
     );
 
     println!("✍️  Generating ownership signature...");
-    let ownership_signature =
-        generate_ownership_signature(&eth_secret_key, &claim_data, &vamp_state.intent_id)?;
+    let ownership_signature = generate_ownership_signature(
+        &eth_secret_key,
+        &claim_data,
+        &vamp_state,
+        &solana_keypair.pubkey(),
+    )?;
+    let mut signatures = balance_signatures(&claim_data, &vamp_state)?;
+    signatures.push(ownership_signature);
 
     // Check SOL balance
     let balance = client.get_balance(&solana_keypair.pubkey())?;
@@ -269,13 +309,23 @@ fn main() -> Result<()> {
         println!("✅ Sufficient SOL balance for transaction");
     }
 
+    let referrer = args
+        .referrer
+        .as_deref()
+        .map(Pubkey::from_str)
+        .transpose()
+        .map_err(|e| anyhow!("Invalid referrer: {}", e))?;
+
     println!("🚀 Executing claim transaction...");
     let signature = execute_claim_transaction(
         &client,
         &solana_keypair,
         &mint_pubkey,
         &claim_data,
-        &ownership_signature,
+        &signatures,
+        args.max_total_cost,
+        &vamp_state,
+        referrer,
     )?;
 
     println!("✅ Claim transaction successful!");
@@ -328,162 +378,133 @@ fn fetch_vamping_data_from_solver(
 }
 
 fn fetch_vamp_state(client: &RpcClient, mint_pubkey: &Pubkey) -> Result<VampState> {
-    let program_id = Pubkey::from_str("FAyBECn6ppQgRwb5R4LryAzNic3XwsCuHakVpD1X7hFW")?;
+    let program_id = Pubkey::from_str(VAMP_PROGRAM_ID)?;
 
     // Derive PDA for VampState and fetch directly
     let (vamp_state_pda, _bump) =
         Pubkey::find_program_address(&[b"vamp", mint_pubkey.as_ref()], &program_id);
     let account = client.get_account(&vamp_state_pda)?;
+    parse_vamp_state(&account.data)
+}
+
+/// Reads the Borsh encoded fields of an account in order, every read is bounds checked
+struct AccountReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
 
-    // Parse account data
-    let data = account.data;
-    if data.len() < 8 + 1 + 32 {
-        return Err(anyhow!("VampState account data too short"));
+impl<'a> AccountReader<'a> {
+    fn bytes(&mut self, len: usize, field: &str) -> Result<&'a [u8]> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or_else(|| anyhow!("VampState account data too short for {}", field))?;
+        self.offset += len;
+        Ok(bytes)
     }
 
-    // Discriminator check (optional)
-    let discriminator = &data[0..8];
-    let expected_discriminator = [222, 91, 2, 48, 244, 96, 192, 196];
-    if discriminator != expected_discriminator {
-        return Err(anyhow!("Invalid VampState discriminator"));
+    fn array<const N: usize>(&mut self, field: &str) -> Result<[u8; N]> {
+        Ok(self.bytes(N, field)?.try_into().expect("N bytes were read"))
     }
 
-    let mut offset = 8; // Skip discriminator
-
-    // bump
-    let _bump = data[offset];
-    offset += 1;
-
-    // mint
-    let _mint =
-        Pubkey::try_from(&data[offset..offset + 32]).map_err(|_| anyhow!("Invalid mint pubkey"))?;
-    offset += 32;
-
-    // solver_public_key (Vec<u8>)
-    let solver_len = u32::from_le_bytes(
-        data[offset..offset + 4]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid solver key length"))?,
-    ) as usize;
-    offset += 4;
-    let solver_public_key = data[offset..offset + solver_len].to_vec();
-    offset += solver_len;
-
-    // validator_public_key (Vec<u8>)
-    let validator_len = u32::from_le_bytes(
-        data[offset..offset + 4]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid validator key length"))?,
-    ) as usize;
-    offset += 4;
-    let validator_public_key = data[offset..offset + validator_len].to_vec();
-    offset += validator_len;
-
-    // vamp_identifier
-    let _vamp_identifier = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid vamp_identifier"))?,
-    );
-    offset += 8;
-
-    // intent_id (Vec<u8>)
-    let intent_id_len = u32::from_le_bytes(
-        data[offset..offset + 4]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid intent_id length"))?,
-    ) as usize;
-    offset += 4;
-    let intent_id = data[offset..offset + intent_id_len].to_vec();
-    offset += intent_id_len;
-
-    // total_claimed
-    let total_claimed = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid total_claimed"))?,
-    );
-    offset += 8;
+    fn bool(&mut self, field: &str) -> Result<bool> {
+        Ok(self.array::<1>(field)?[0] != 0)
+    }
 
-    // reserve_balance
-    let reserve_balance = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid reserve_balance"))?,
-    );
-    offset += 8;
+    fn u32(&mut self, field: &str) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array(field)?))
+    }
 
-    // token_supply
-    let token_supply = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid token_supply"))?,
-    );
-    offset += 8;
+    fn u64(&mut self, field: &str) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array(field)?))
+    }
 
-    // curve_exponent
-    let curve_exponent = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid curve_exponent"))?,
-    );
-    offset += 8;
-
-    // sol_vault
-    let sol_vault =
-        Pubkey::try_from(&data[offset..offset + 32]).map_err(|_| anyhow!("Invalid sol_vault"))?;
-    offset += 32;
-
-    // curve_slope
-    let curve_slope = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid curve_slope"))?,
-    );
-    offset += 8;
+    fn pubkey(&mut self, field: &str) -> Result<Pubkey> {
+        Ok(Pubkey::new_from_array(self.array(field)?))
+    }
 
-    // base_price
-    let base_price = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid base_price"))?,
-    );
-    offset += 8;
-
-    // max_price (Option<u64>)
-    let max_price = if data[offset] != 0 {
-        offset += 1;
-        let v = u64::from_le_bytes(
-            data[offset..offset + 8]
-                .try_into()
-                .map_err(|_| anyhow!("Invalid max_price"))?,
-        );
-        offset += 8;
-        Some(v)
-    } else {
-        offset += 1;
-        None
-    };
+    /// A `Vec` of `item_len` byte items, returned as one slice
+    fn vec(&mut self, item_len: usize, field: &str) -> Result<&'a [u8]> {
+        let len = self.u32(field)? as usize;
+        let total = len
+            .checked_mul(item_len)
+            .ok_or_else(|| anyhow!("Invalid {} length", field))?;
+        self.bytes(total, field)
+    }
 
-    // use_bonding_curve (bool)
-    let use_bonding_curve = data[offset] != 0;
-    offset += 1;
+    /// An `Option` of a `len` byte value
+    fn option(&mut self, len: usize, field: &str) -> Result<Option<&'a [u8]>> {
+        if self.bool(field)? {
+            Ok(Some(self.bytes(len, field)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
 
-    // flat_price_per_token (u64)
-    let flat_price_per_token = u64::from_le_bytes(
-        data[offset..offset + 8]
-            .try_into()
-            .map_err(|_| anyhow!("Invalid flat_price_per_token"))?,
-    );
-    offset += 8;
+fn parse_vamp_state(data: &[u8]) -> Result<VampState> {
+    let mut reader = AccountReader { data, offset: 0 };
+
+    let discriminator: [u8; 8] = reader.array("discriminator")?;
+    if discriminator != [222, 91, 2, 48, 244, 96, 192, 196] {
+        return Err(anyhow!("Invalid VampState discriminator"));
+    }
 
-    // paid_claiming_enabled (bool)
-    let paid_claiming_enabled = data[offset] != 0;
-    // do not advance offset further; not needed and avoids unused assignment warning
+    // bump and mint
+    reader.bytes(1 + 32, "mint")?;
+    let solver_public_key = reader.vec(1, "solver_public_key")?.to_vec();
+    let validator_set = reader
+        .vec(20, "validator_set")?
+        .chunks_exact(20)
+        .map(|validator| validator.try_into().expect("chunks are 20 bytes"))
+        .collect();
+    reader.u64("vamp_identifier")?;
+    let intent_id = reader.vec(1, "intent_id")?.to_vec();
+    let total_claimed = reader.u64("total_claimed")?;
+    let reserve_balance = reader.u64("reserve_balance")?;
+    let token_supply = reader.u64("token_supply")?;
+    let curve_exponent = reader.u64("curve_exponent")?;
+    let sol_vault = reader.pubkey("sol_vault")?;
+    let curve_slope = reader.u64("curve_slope")?;
+    let base_price = reader.u64("base_price")?;
+    let max_price = reader
+        .option(8, "max_price")?
+        .map(|price| u64::from_le_bytes(price.try_into().expect("8 bytes were read")));
+    let use_bonding_curve = reader.bool("use_bonding_curve")?;
+    let flat_price_per_token = reader.u64("flat_price_per_token")?;
+    let paid_claiming_enabled = reader.bool("paid_claiming_enabled")?;
+
+    // authority, treasury, merkle_root and claim_deadline
+    reader.bytes(32 + 32, "treasury")?;
+    reader.option(32, "merkle_root")?;
+    reader.option(8, "claim_deadline")?;
+
+    // params_locked, curve_kind, curve_step_size, redeem_spread_bps and paused
+    reader.bytes(1 + 1 + 8 + 2 + 1, "paused")?;
+
+    // The balance signatures name the signer epoch they were made in
+    let signer_epoch = reader.u32("signer_epoch")?;
+
+    // The rotated out signers, their grace period and the validator threshold
+    reader.vec(1, "previous_solver_public_key")?;
+    reader.vec(20, "previous_validator_set")?;
+    reader.option(8, "previous_signers_valid_until")?;
+    reader.bytes(1, "validator_threshold")?;
+
+    // source_chain_id is part of the EIP-712 domain of the claim signatures
+    let source_chain_id = reader.u64("source_chain_id")?;
+
+    // vesting (Option<VestingSchedule>), claims of a vesting vamp need a vesting account
+    let vesting_enabled = reader.option(8 + 8 + 8, "vesting")?.is_some();
+    reader.u64("vesting_locked")?;
+
+    // Claims are tracked per snapshot epoch since top-ups
+    let snapshot_epoch = reader.u32("snapshot_epoch")?;
 
     Ok(VampState {
         solver_public_key,
-        validator_public_key,
+        validator_set,
         intent_id,
         total_claimed,
         reserve_balance,
@@ -496,6 +517,10 @@ fn fetch_vamp_state(client: &RpcClient, mint_pubkey: &Pubkey) -> Result<VampStat
         use_bonding_curve,
         flat_price_per_token,
         paid_claiming_enabled,
+        signer_epoch,
+        source_chain_id,
+        vesting_enabled,
+        snapshot_epoch,
     })
 }
 
@@ -520,6 +545,7 @@ fn parse_ipfs_data(
     let validator_sig_hex = data["vs"]
         .as_str()
         .ok_or_else(|| anyhow!("Missing validator signature"))?;
+    // One or more validator signatures, concatenated
     let validator_signature = hex::decode(
         validator_sig_hex
             .strip_prefix("0x")
@@ -533,7 +559,7 @@ fn parse_ipfs_data(
         ));
     }
 
-    if validator_signature.len() != 65 {
+    if validator_signature.is_empty() || validator_signature.len() % 65 != 0 {
         return Err(anyhow!(
             "Invalid validator signature length: {}",
             validator_signature.len()
@@ -543,47 +569,37 @@ fn parse_ipfs_data(
     let solver_signature: [u8; 65] = solver_signature
         .try_into()
         .map_err(|_| anyhow!("Invalid solver signature length"))?;
-    let validator_signature: [u8; 65] = validator_signature
-        .try_into()
-        .map_err(|_| anyhow!("Invalid validator signature length"))?;
+    let validator_signatures = validator_signature
+        .chunks_exact(65)
+        .map(|chunk| chunk.try_into().expect("chunk is 65 bytes"))
+        .collect();
 
     Ok(ClaimData {
         eth_address: *eth_address,
         balance,
         solver_signature,
-        validator_signature,
+        validator_signatures,
     })
 }
 
 fn generate_ownership_signature(
     eth_secret_key: &SecretKey,
     claim_data: &ClaimData,
-    intent_id: &[u8],
-) -> Result<[u8; 65]> {
-    // Create the message hash that needs to be signed
+    vamp_state: &VampState,
+    destination_owner: &Pubkey,
+) -> Result<EthSignature> {
+    // EIP-712 ClaimOwnership, mirrors balance_util::get_ownership_typed_data.
+    // It commits to the wallet owning the claimer token account so the claim can't be redirected.
     let mut hasher = Keccak256::new();
-    hasher.update(&claim_data.eth_address);
-    hasher.update(&claim_data.balance.to_le_bytes()); // Little-endian as expected by Solana
-    hasher.update(intent_id);
-    let message_hash = hasher.finalize();
-
-    // Add Ethereum message prefix like the Solana program does during verification
-    const PREFIX: &str = "\x19Ethereum Signed Message:\n";
-    let len = message_hash.len();
-    let len_string = len.to_string();
-
-    let mut eth_message = Vec::with_capacity(PREFIX.len() + len_string.len() + message_hash.len());
-    eth_message.extend_from_slice(PREFIX.as_bytes());
-    eth_message.extend_from_slice(len_string.as_bytes());
-    eth_message.extend_from_slice(&message_hash);
-
-    // Hash the message with prefix - this is what the Solana program will hash during verification
-    let mut final_hasher = Keccak256::new();
-    final_hasher.update(&eth_message);
-    let final_message_hash = final_hasher.finalize();
-
-    // Create Ethereum signature format - sign the message with prefix
-    let message = Message::parse_slice(&final_message_hash)?;
+    hasher.update(Keccak256::digest(CLAIM_OWNERSHIP_TYPE));
+    hasher.update(eip712_address(&claim_data.eth_address));
+    hasher.update(eip712_uint256(claim_data.balance));
+    hasher.update(Keccak256::digest(&vamp_state.intent_id));
+    hasher.update(destination_owner.as_ref());
+    let typed_data = eip712_typed_data(vamp_state.source_chain_id, &hasher.finalize())?;
+
+    // The keccak of the typed data is what eth_signTypedData signs and the Secp256k1 program verifies
+    let message = Message::parse_slice(&Keccak256::digest(&typed_data))?;
     let (signature, recovery_id) = sign(&message, eth_secret_key);
 
     // Convert to Ethereum signature format (r, s, v)
@@ -592,28 +608,212 @@ fn generate_ownership_signature(
     eth_signature[32..64].copy_from_slice(&signature.s.b32());
     eth_signature[64] = recovery_id.serialize() + 27; // Ethereum format
 
-    Ok(eth_signature)
+    Ok(EthSignature {
+        eth_address: claim_data.eth_address,
+        signature: eth_signature,
+        message: typed_data,
+    })
+}
+
+fn eip712_uint256(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn eip712_address(address: &[u8; 20]) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(address);
+    word
+}
+
+/// Encodes `"\x19\x01" || domainSeparator || structHash`, mirrors balance_util.
+/// The domain salt is the vamp program ID.
+fn eip712_typed_data(chain_id: u64, struct_hash: &[u8]) -> Result<Vec<u8>> {
+    let program_id = Pubkey::from_str(VAMP_PROGRAM_ID)?;
+    let mut hasher = Keccak256::new();
+    hasher.update(Keccak256::digest(EIP712_DOMAIN_TYPE));
+    hasher.update(Keccak256::digest(EIP712_DOMAIN_NAME));
+    hasher.update(Keccak256::digest(EIP712_DOMAIN_VERSION));
+    hasher.update(eip712_uint256(chain_id));
+    hasher.update(program_id.as_ref());
+    let domain_separator = hasher.finalize();
+
+    let mut typed_data = b"\x19\x01".to_vec();
+    typed_data.extend_from_slice(&domain_separator);
+    typed_data.extend_from_slice(struct_hash);
+    Ok(typed_data)
+}
+
+/// Recovers the solver and validator addresses from their balance signatures, over the EIP-712
+/// BalanceClaim of balance_util. The program checks the addresses against the vamp signer keys.
+/// Signatures made before the last key rotation are over the previous signer epoch.
+fn balance_signatures(claim_data: &ClaimData, vamp_state: &VampState) -> Result<Vec<EthSignature>> {
+    let signatures = epoch_balance_signatures(claim_data, vamp_state, vamp_state.signer_epoch)?;
+    let signed_by_solver = signatures
+        .first()
+        .is_some_and(|solver| solver.eth_address.as_slice() == vamp_state.solver_public_key);
+    match vamp_state.signer_epoch.checked_sub(1) {
+        Some(previous_epoch) if !signed_by_solver => {
+            epoch_balance_signatures(claim_data, vamp_state, previous_epoch)
+        }
+        _ => Ok(signatures),
+    }
+}
+
+/// The balance signatures over the BalanceClaim of `signer_epoch`, the solver's first
+fn epoch_balance_signatures(
+    claim_data: &ClaimData,
+    vamp_state: &VampState,
+    signer_epoch: u32,
+) -> Result<Vec<EthSignature>> {
+    let mut hasher = Keccak256::new();
+    hasher.update(Keccak256::digest(BALANCE_CLAIM_TYPE));
+    hasher.update(eip712_address(&claim_data.eth_address));
+    hasher.update(eip712_uint256(claim_data.balance));
+    hasher.update(Keccak256::digest(&vamp_state.intent_id));
+    hasher.update(eip712_uint256(signer_epoch.into()));
+    let typed_data = eip712_typed_data(vamp_state.source_chain_id, &hasher.finalize())?;
+    let message = Message::parse_slice(&Keccak256::digest(&typed_data))?;
+
+    std::iter::once(&claim_data.solver_signature)
+        .chain(claim_data.validator_signatures.iter())
+        .map(|signature| {
+            let recovery_id = RecoveryId::parse_rpc(signature[64])?;
+            let public_key = recover(
+                &message,
+                &Signature::parse_standard_slice(&signature[..64])?,
+                &recovery_id,
+            )?;
+            let mut eth_address = [0u8; 20];
+            eth_address.copy_from_slice(&Keccak256::digest(&public_key.serialize()[1..])[12..]);
+            Ok(EthSignature {
+                eth_address,
+                signature: *signature,
+                message: typed_data.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Builds one Secp256k1 program instruction verifying all the claim signatures.
+/// Mirrors solana_transaction_util::secp256k1::new_secp256k1_instruction.
+fn create_secp256k1_instruction(
+    instruction_index: u8,
+    signatures: &[EthSignature],
+) -> Result<solana_sdk::instruction::Instruction> {
+    const OFFSETS_SIZE: usize = 11;
+    const ENTRY_SIZE: usize = 20 + 65;
+
+    let mut messages: Vec<&[u8]> = Vec::new();
+    for signature in signatures {
+        if !messages.contains(&signature.message.as_slice()) {
+            messages.push(&signature.message);
+        }
+    }
+
+    let entries_start = 1 + signatures.len() * OFFSETS_SIZE;
+    let mut message_offsets = Vec::with_capacity(messages.len());
+    let mut offset = entries_start + signatures.len() * ENTRY_SIZE;
+    for message in &messages {
+        message_offsets.push(offset);
+        offset += message.len();
+    }
+
+    let mut data = vec![signatures.len() as u8];
+    let mut entries = Vec::new();
+    for (i, signature) in signatures.iter().enumerate() {
+        let eth_address_offset = entries_start + i * ENTRY_SIZE;
+        let message = signature.message.as_slice();
+        let message_index = messages.iter().position(|m| *m == message).unwrap();
+
+        data.extend_from_slice(&((eth_address_offset + 20) as u16).to_le_bytes()); // signature
+        data.push(instruction_index);
+        data.extend_from_slice(&(eth_address_offset as u16).to_le_bytes()); // eth address
+        data.push(instruction_index);
+        data.extend_from_slice(&(message_offsets[message_index] as u16).to_le_bytes()); // message
+        data.extend_from_slice(&(message.len() as u16).to_le_bytes());
+        data.push(instruction_index);
+
+        entries.extend_from_slice(&signature.eth_address);
+        entries.extend_from_slice(&signature.signature[..64]);
+        // The precompile expects the raw recovery id instead of Ethereum's 27/28
+        entries.push(RecoveryId::parse_rpc(signature.signature[64])?.serialize());
+    }
+    data.extend(entries);
+    for message in messages {
+        data.extend_from_slice(message);
+    }
+
+    Ok(solana_sdk::instruction::Instruction {
+        program_id: solana_sdk::secp256k1_program::id(),
+        accounts: vec![],
+        data,
+    })
+}
+
+/// Returns the protocol treasury from the global config while the protocol takes a fee on claims
+fn fetch_protocol_treasury(client: &RpcClient, global_config_pda: &Pubkey) -> Result<Option<Pubkey>> {
+    let data = client.get_account(global_config_pda)?.data;
+
+    // discriminator, bump, admin, emergency_freeze, protocol_treasury, protocol_fee_bps
+    let offset = 8 + 1 + 32 + 1;
+    if data.len() < offset + 32 + 2 {
+        return Err(anyhow!("GlobalConfig account data too short"));
+    }
+    let protocol_treasury = Pubkey::try_from(&data[offset..offset + 32])
+        .map_err(|_| anyhow!("Invalid protocol treasury pubkey"))?;
+    let protocol_fee_bps = u16::from_le_bytes([data[offset + 32], data[offset + 33]]);
+
+    Ok((protocol_fee_bps > 0).then_some(protocol_treasury))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn execute_claim_transaction(
     client: &RpcClient,
     solana_keypair: &Keypair,
     mint_pubkey: &Pubkey,
     claim_data: &ClaimData,
-    ownership_signature: &[u8; 65],
+    signatures: &[EthSignature],
+    max_total_cost: Option<u64>,
+    vamp_state: &VampState,
+    referrer: Option<Pubkey>,
 ) -> Result<String> {
-    let program_id = Pubkey::from_str("FAyBECn6ppQgRwb5R4LryAzNic3XwsCuHakVpD1X7hFW")?;
+    let program_id = Pubkey::from_str(VAMP_PROGRAM_ID)?;
 
     // Find VampState PDA
     let (vamp_state_pda, _bump) =
         Pubkey::find_program_address(&[b"vamp", mint_pubkey.as_ref()], &program_id);
 
+    // Find GlobalConfig PDA
+    let (global_config_pda, _bump) = Pubkey::find_program_address(&[b"config"], &program_id);
+    let protocol_treasury = fetch_protocol_treasury(client, &global_config_pda)?;
+
+    // Claims of a topped up vamp are tracked per snapshot epoch, the initial epoch has no seed for it
+    let epoch_seed = if vamp_state.snapshot_epoch == 0 {
+        vec![]
+    } else {
+        vamp_state.snapshot_epoch.to_le_bytes().to_vec()
+    };
+
     // Find ClaimState PDA
     let (claim_state_pda, _bump) = Pubkey::find_program_address(
-        &[b"claim", vamp_state_pda.as_ref(), &claim_data.eth_address],
+        &[b"claim", vamp_state_pda.as_ref(), &claim_data.eth_address, &epoch_seed],
         &program_id,
     );
 
+    // Find VestingState PDA, an absent optional account is passed as the program id
+    let vesting_enabled = vamp_state.vesting_enabled;
+    let vesting_state_pda = if vesting_enabled {
+        Pubkey::find_program_address(
+            &[b"vesting", vamp_state_pda.as_ref(), &claim_data.eth_address, &epoch_seed],
+            &program_id,
+        )
+        .0
+    } else {
+        program_id
+    };
+
     // Find SOL vault PDA
     let (sol_vault_pda, _bump) =
         Pubkey::find_program_address(&[b"sol_vault", mint_pubkey.as_ref()], &program_id);
@@ -630,16 +830,26 @@ fn execute_claim_transaction(
     println!("   Mint: {}", mint_pubkey);
 
     // Create the instruction data for buy_claim_tokens
-    let instruction_data = create_claim_instruction_data(claim_data, ownership_signature)?;
+    let instruction_data =
+        create_claim_instruction_data(claim_data, max_total_cost)?;
 
     // Create the transaction
     let recent_blockhash = client.get_latest_blockhash()?;
 
+    // The mint is either a classic SPL Token or a Token-2022 one, its owner tells which
+    let token_program = client.get_account(mint_pubkey)?.owner;
+
     // Create claimer token account
-    let claimer_token_account = get_associated_token_address(&solana_keypair.pubkey(), mint_pubkey);
+    let claimer_token_account = get_associated_token_address_with_program_id(
+        &solana_keypair.pubkey(),
+        mint_pubkey,
+        &token_program,
+    );
+
+    // The Secp256k1 program verifies the signatures, it goes first so its index is 0
+    let mut instructions = vec![create_secp256k1_instruction(0, signatures)?];
 
     // Check if claimer token account exists, if not create it
-    let mut instructions = Vec::new();
 
     if client.get_account(&claimer_token_account).is_err() {
         println!(
@@ -651,7 +861,7 @@ fn execute_claim_transaction(
                 &solana_keypair.pubkey(),
                 &solana_keypair.pubkey(),
                 mint_pubkey,
-                &spl_token::id(),
+                &token_program,
             );
         instructions.push(create_ata_ix);
     }
@@ -682,16 +892,40 @@ fn execute_claim_transaction(
             // Context accounts
             solana_sdk::instruction::AccountMeta::new(solana_keypair.pubkey(), true), // authority
             solana_sdk::instruction::AccountMeta::new(vamp_state_pda, false),         // vamp_state
+            solana_sdk::instruction::AccountMeta::new_readonly(global_config_pda, false), // global_config
+            // The IPFS data is signed for the current intent, archived epochs aren't claimed here
+            solana_sdk::instruction::AccountMeta::new_readonly(program_id, false), // epoch_snapshot
             solana_sdk::instruction::AccountMeta::new(claim_state_pda, false),        // claim_state
             solana_sdk::instruction::AccountMeta::new(vault_pda, false),              // vault
             solana_sdk::instruction::AccountMeta::new(sol_vault_pda, false),          // sol_vault
             solana_sdk::instruction::AccountMeta::new(claimer_token_account, false), // claimer_token_account
             solana_sdk::instruction::AccountMeta::new_readonly(mint_pubkey.clone(), false), // mint_account
-            solana_sdk::instruction::AccountMeta::new_readonly(spl_token::id(), false), // token_program
+            solana_sdk::instruction::AccountMeta::new_readonly(token_program, false), // token_program
             solana_sdk::instruction::AccountMeta::new_readonly(
                 solana_sdk::system_program::id(),
                 false,
             ), // system_program
+            solana_sdk::instruction::AccountMeta::new_readonly(
+                solana_sdk::sysvar::instructions::id(),
+                false,
+            ), // instructions_sysvar
+            if vesting_enabled {
+                solana_sdk::instruction::AccountMeta::new(vesting_state_pda, false)
+            } else {
+                solana_sdk::instruction::AccountMeta::new_readonly(vesting_state_pda, false)
+            }, // vesting_state
+            // Signed claims track the holder with the claim state, the program id stands for no bitmap
+            solana_sdk::instruction::AccountMeta::new_readonly(program_id, false), // claim_bitmap
+            match protocol_treasury {
+                Some(protocol_treasury) => {
+                    solana_sdk::instruction::AccountMeta::new(protocol_treasury, false)
+                }
+                None => solana_sdk::instruction::AccountMeta::new_readonly(program_id, false),
+            }, // protocol_treasury
+            match referrer {
+                Some(referrer) => solana_sdk::instruction::AccountMeta::new(referrer, false),
+                None => solana_sdk::instruction::AccountMeta::new_readonly(program_id, false),
+            }, // referrer
         ],
         data: instruction_data,
     };
@@ -735,7 +969,7 @@ fn execute_claim_transaction(
 
 fn create_claim_instruction_data(
     claim_data: &ClaimData,
-    ownership_signature: &[u8; 65],
+    max_total_cost: Option<u64>,
 ) -> Result<Vec<u8>> {
     // Anchor instruction discriminator for claim (from IDL)
     let discriminator = [62, 198, 214, 193, 213, 159, 108, 210];
@@ -744,69 +978,75 @@ fn create_claim_instruction_data(
     data.extend_from_slice(&discriminator);
     data.extend_from_slice(&claim_data.eth_address);
     data.extend_from_slice(&claim_data.balance.to_le_bytes());
-    data.extend_from_slice(&claim_data.solver_signature);
-    data.extend_from_slice(&claim_data.validator_signature);
-    data.extend_from_slice(ownership_signature);
+    // Option<u64>: 1-byte tag followed by the value when present
+    match max_total_cost {
+        Some(cost) => {
+            data.push(1);
+            data.extend_from_slice(&cost.to_le_bytes());
+        }
+        None => data.push(0),
+    }
 
     Ok(data)
 }
 
-fn calculate_expected_claim_cost(
-    token_amount: u64,
-    total_claimed: u64,
-    curve_slope: u64,
-    base_price: u64,
-    max_price: Option<u64>,
-    use_bonding_curve: bool,
-    flat_price_per_token: u64,
-    paid_claiming_enabled: bool,
+fn quote_claim_cost(
+    client: &RpcClient,
+    payer: &Pubkey,
+    mint_pubkey: &Pubkey,
+    balance: u64,
+    referred: bool,
 ) -> Result<u64> {
-    if !use_bonding_curve {
-        if !paid_claiming_enabled {
-            return Ok(0);
-        }
-        // Mirror on-chain safety cap: min(flat_price_per_token, 1) and total cap 0.1 SOL
-        let safe_flat = std::cmp::min(flat_price_per_token, 1);
-        let cost = (token_amount as u128)
-            .checked_mul(safe_flat as u128)
-            .ok_or_else(|| anyhow!("Arithmetic overflow in flat price calculation"))?;
-        let capped = std::cmp::min(cost, 100_000_000u128);
-        return Ok(capped as u64);
+    let program_id = Pubkey::from_str(VAMP_PROGRAM_ID)?;
+    let (vamp_state_pda, _bump) =
+        Pubkey::find_program_address(&[b"vamp", mint_pubkey.as_ref()], &program_id);
+    let (global_config_pda, _bump) = Pubkey::find_program_address(&[b"config"], &program_id);
+
+    // Anchor instruction discriminator for quote_claim (from IDL)
+    let mut data = vec![81, 239, 121, 241, 238, 235, 240, 194];
+    data.extend_from_slice(&balance.to_le_bytes());
+    data.push(referred as u8);
+
+    let quote_instruction = solana_sdk::instruction::Instruction {
+        program_id,
+        accounts: vec![
+            solana_sdk::instruction::AccountMeta::new_readonly(vamp_state_pda, false), // vamp_state
+            solana_sdk::instruction::AccountMeta::new_readonly(global_config_pda, false), // global_config
+            solana_sdk::instruction::AccountMeta::new_readonly(*mint_pubkey, false),   // mint_account
+        ],
+        data,
+    };
+
+    // The payer doesn't sign, the simulation only needs it to exist
+    let transaction = Transaction::new_with_payer(&[quote_instruction], Some(payer));
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        ..Default::default()
+    };
+    let result = client.simulate_transaction_with_config(&transaction, config)?.value;
+    if let Some(err) = result.err {
+        explain_client_error(&err);
+        return Err(anyhow!(
+            "Claim cost quote failed: {}, logs: {:?}",
+            err,
+            result.logs.unwrap_or_default()
+        ));
     }
 
-    let x1 = total_claimed as u128;
-    let x2 = x1
-        .checked_add(token_amount as u128)
-        .ok_or_else(|| anyhow!("Arithmetic overflow in token amount addition"))?;
-
-    let delta = x2
-        .checked_sub(x1)
-        .ok_or_else(|| anyhow!("Arithmetic overflow in delta calculation"))?;
-
-    let part1 = delta
-        .checked_mul(curve_slope as u128)
-        .ok_or_else(|| anyhow!("Overflow mul slope"))?
-        .checked_mul(delta)
-        .ok_or_else(|| anyhow!("Overflow delta^2"))?
-        .checked_div(100000u128)
-        .ok_or_else(|| anyhow!("Div by zero"))?;
-
-    let part2 = delta
-        .checked_mul(base_price as u128)
-        .ok_or_else(|| anyhow!("Overflow base mul"))?;
-
-    let total = part1
-        .checked_add(part2)
-        .ok_or_else(|| anyhow!("Overflow total"))?;
-
-    if let Some(_max_price_per_token) = max_price {
-        // Client does not pre-assert; on-chain will enforce PriceTooHigh.
-        let _avg = total
-            .checked_div(delta)
-            .ok_or_else(|| anyhow!("Div by zero avg"))?;
+    // The runtime trims trailing zero bytes, and omits the return data when nothing is left
+    let mut cost = [0u8; 8];
+    if let Some(return_data) = result.return_data {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(return_data.data.0)
+            .map_err(|e| anyhow!("Failed to decode quote: {}", e))?;
+        if bytes.len() > cost.len() {
+            return Err(anyhow!("Unexpected quote length {}", bytes.len()));
+        }
+        cost[..bytes.len()].copy_from_slice(&bytes);
     }
 
-    Ok(u64::try_from(total).map_err(|_| anyhow!("Total cost too large for u64"))?)
+    Ok(u64::from_le_bytes(cost))
 }
 
 fn explain_client_error<E: std::fmt::Display>(err: E) {
@@ -20,11 +20,35 @@ pub struct IpfsConfig {
     pub enable_mfs_copy: bool,
 }
 
+/// Deployed vamp program, the salt of the EIP-712 domain of the balance signatures
+const VAMP_PROGRAM_ID: &str = "FAyBECn6ppQgRwb5R4LryAzNic3XwsCuHakVpD1X7hFW";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SolanaConfig {
+    #[serde(default = "default_vamp_program_id")]
+    pub vamp_program_id: String,
+}
+
+impl Default for SolanaConfig {
+    fn default() -> Self {
+        Self {
+            vamp_program_id: default_vamp_program_id(),
+        }
+    }
+}
+
+fn default_vamp_program_id() -> String {
+    VAMP_PROGRAM_ID.to_string()
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub grpc: GrpcConfig,
     pub storage: StorageConfig,
     pub ipfs: IpfsConfig,
+    // Configs written before the section existed sign for the deployed program
+    #[serde(default)]
+    pub solana: SolanaConfig,
 }
 
 pub fn load_config(config_file_path: &str) -> Config {
@@ -1,11 +1,10 @@
 use crate::validator_vamp::config;
 use std::collections::HashMap;
 use std::fs;
-use ethers::core::k256::sha2::Digest;
+use balance_util::get_balance_claim_digest;
 use ethers::signers::{LocalWallet, Signer};
 use prost::Message;
 use serde_json::json;
-use sha3::Keccak256;
 use tonic::{Request, Response, Status};
 use tonic::transport::Server;
 use crate::proto::{SubmitSolutionForValidationRequestProto, SubmitSolutionForValidationResponseProto, validator_service_server::{ValidatorService}, AppChainResultProto, AppChainResultStatus};
@@ -19,6 +18,7 @@ pub struct ValidatorGrpcService {
     pub storage: Storage,
     pub ipfs_service: IpfsService,
     pub validator_wallet: LocalWallet,
+    pub vamp_program_id: [u8; 32],
 }
 
 #[tonic::async_trait]
@@ -60,46 +60,37 @@ impl ValidatorService for ValidatorGrpcService {
                     req.intent_id,
                 );
 
+                // Parse the intent_id from hex string to raw bytes
+                let intent_id_bytes = hex::decode(req.intent_id.strip_prefix("0x").unwrap_or(&req.intent_id))
+                    .map_err(|e| {
+                        log::warn!("Invalid intent_id format for intent_id: {} - {}", req.intent_id, e);
+                        Status::internal(format!("Invalid intent_id format: {e}"))
+                    })?;
+
                 // Sign each entry with validator key
                 for (addr, entry) in solution.individual_balance_entry_by_oth_address.iter_mut() {
-                    let mut hasher = Keccak256::new();
-                    
                     // Parse the Ethereum address from hex string to raw bytes
                     let eth_address = hex::decode(addr.strip_prefix("0x").unwrap_or(addr))
                         .map_err(|e| {
                             log::warn!("Invalid Ethereum address format for intent_id: {} - {}", req.intent_id, e);
                             Status::internal(format!("Invalid Ethereum address format: {e}"))
                         })?;
-                    
-                    // Parse the intent_id from hex string to raw bytes
-                    let intent_id_bytes = hex::decode(req.intent_id.strip_prefix("0x").unwrap_or(&req.intent_id))
-                        .map_err(|e| {
-                            log::warn!("Invalid intent_id format for intent_id: {} - {}", req.intent_id, e);
-                            Status::internal(format!("Invalid intent_id format: {e}"))
-                        })?;
-                    
-                    // Use the same message format as the Solana program
-                    hasher.update(&eth_address);
-                    hasher.update(&entry.balance.to_le_bytes());
-                    hasher.update(&intent_id_bytes);
-                    let message_hash = hasher.finalize();
-                    
-                    // Add Ethereum message prefix like the Solana program does during verification
-                    const PREFIX: &str = "\x19Ethereum Signed Message:\n";
-                    let len = message_hash.len();
-                    let len_string = len.to_string();
-                    
-                    let mut eth_message = Vec::with_capacity(PREFIX.len() + len_string.len() + message_hash.len());
-                    eth_message.extend_from_slice(PREFIX.as_bytes());
-                    eth_message.extend_from_slice(len_string.as_bytes());
-                    eth_message.extend_from_slice(&message_hash);
-                    
-                    // Hash the message with prefix - this is what the Solana program will hash during verification
-                    let mut final_hasher = sha3::Keccak256::new();
-                    final_hasher.update(&eth_message);
-                    let final_message_hash = final_hasher.finalize();
-                    
-                    let sig = self.validator_wallet.sign_hash(ethers::types::H256::from_slice(&final_message_hash))
+
+                    // EIP-712 BalanceClaim digest, the same typed data the Solana program verifies
+                    let balance_digest = get_balance_claim_digest(
+                        &eth_address,
+                        entry.balance,
+                        &intent_id_bytes,
+                        solution.signer_epoch,
+                        solution.chain_id,
+                        &self.vamp_program_id,
+                    )
+                    .map_err(|e| {
+                        log::warn!("Balance claim digest error for intent_id: {} - {}", req.intent_id, e);
+                        Status::internal(format!("Balance claim digest error: {e}"))
+                    })?;
+
+                    let sig = self.validator_wallet.sign_hash(ethers::types::H256::from(balance_digest))
                         .map_err(|e| {
                             log::warn!("Signing error for intent_id: {} - {}", req.intent_id, e);
                             Status::internal(format!("Signing error: {e}"))
@@ -212,10 +203,16 @@ pub async fn start_grpc_server(config: config::Config, storage: Storage, ipfs_se
 
     log::info!("Starting gRPC server on {}", addr);
 
+    let vamp_program_id = bs58::decode(&config.solana.vamp_program_id)
+        .into_vec()?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid vamp program ID: {}", config.solana.vamp_program_id))?;
+
     let validator_service = ValidatorGrpcService {
         storage,
         ipfs_service,
         validator_wallet,
+        vamp_program_id,
     };
 
     Server::builder()
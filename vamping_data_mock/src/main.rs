@@ -13,6 +13,10 @@ const SOLVER_PRIVATE_KEY: &str = "9aa4451744ed6f2e3eeee95923c8c5323d86a413151149
 const VALIDATOR_PRIVATE_KEY: &str = "c9927bc21d1c962ee9a4f0634b49868ab80cb1b3f3522881849a5e81ca21edb0";
 const BALANCE_ACCOUNT_PRIVATE_KEY: &str = "fc813315c55817d4fb1396dcf772e1dfa84b8d3328713a6647930ec1983a67cf";
 const SECOND_ACCOUNT_PRIVATE_KEY: &str = "a5b69751153da2de176da6dc42934799fe0403cf97c67efe223d51ef20c98950";
+// The program ID is the salt of the EIP-712 domain, so the signatures are valid for this deployment only
+const VAMP_PROGRAM_ID: &str = "FAyBECn6ppQgRwb5R4LryAzNic3XwsCuHakVpD1X7hFW";
+// The mocked vamp is a new one, its keys were never rotated
+const SIGNER_EPOCH: u32 = 0;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -61,47 +65,57 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Second Balance Address: 0x{}", second_balance_address);
     println!("Second Balance Amount: {}", second_balance_amount);
 
-    // Add signatures
-    let balance_hash = balance_util::get_balance_hash(
+    // Add signatures over the EIP-712 BalanceClaim
+    let program_id: [u8; 32] = bs58::decode(VAMP_PROGRAM_ID)
+        .into_vec()?
+        .try_into()
+        .map_err(|_| "Invalid program ID")?;
+
+    let balance_digest = balance_util::get_balance_claim_digest(
         &hex::decode(balance_address)?,
         balance_amount,
         &vamping_data.intent_id,
+        SIGNER_EPOCH,
+        vamping_data.chain_id,
+        &program_id,
     )?;
 
-    let second_balance_hash = balance_util::get_balance_hash(
+    let second_balance_digest = balance_util::get_balance_claim_digest(
         &hex::decode(second_balance_address)?,
         second_balance_amount,
         &vamping_data.intent_id,
+        SIGNER_EPOCH,
+        vamping_data.chain_id,
+        &program_id,
     )?;
 
-    println!("Balance hash: {:?}", balance_hash);
-    println!("Second balance hash: {:?}", second_balance_hash);
+    println!("Balance digest: {:?}", balance_digest);
+    println!("Second balance digest: {:?}", second_balance_digest);
 
     // First signatures
     let signer = LocalWallet::from_str(SOLVER_PRIVATE_KEY)?;
-    let solver_signature = signer.sign_message(&balance_hash).await?;
+    let solver_signature = signer.sign_hash(balance_digest.into())?;
     println!("Solver Signature: {:?}", solver_signature.to_vec());
 
     let signer = LocalWallet::from_str(VALIDATOR_PRIVATE_KEY)?;
-    let validator_signature = signer.sign_message(&balance_hash).await?;
+    let validator_signature = signer.sign_hash(balance_digest.into())?;
     println!("Validator Signature: {:?}", validator_signature.to_vec());
 
-    let signer = LocalWallet::from_str(BALANCE_ACCOUNT_PRIVATE_KEY)?;
-    let owner_signature = signer.sign_message(&balance_hash).await?;
-    println!("Owner Signature: {:?}", owner_signature.to_vec());
-
     // Second signatures
     let signer = LocalWallet::from_str(SOLVER_PRIVATE_KEY)?;
-    let second_solver_signature = signer.sign_message(&second_balance_hash).await?;
+    let second_solver_signature = signer.sign_hash(second_balance_digest.into())?;
     println!("Second Solver Signature: {:?}", second_solver_signature.to_vec());
 
     let signer = LocalWallet::from_str(VALIDATOR_PRIVATE_KEY)?;
-    let second_validator_signature = signer.sign_message(&second_balance_hash).await?;
+    let second_validator_signature = signer.sign_hash(second_balance_digest.into())?;
     println!("Second Validator Signature: {:?}", second_validator_signature.to_vec());
 
+    // Owner signatures commit to the destination Solana wallet (balance_util::get_ownership_typed_data),
+    // so they are produced at claim time with the holder keys below.
+    let signer = LocalWallet::from_str(BALANCE_ACCOUNT_PRIVATE_KEY)?;
+    println!("Owner: {:?}", signer.address());
     let signer = LocalWallet::from_str(SECOND_ACCOUNT_PRIVATE_KEY)?;
-    let second_owner_signature = signer.sign_message(&second_balance_hash).await?;
-    println!("Second Owner Signature: {:?}", second_owner_signature.to_vec());
+    println!("Second Owner: {:?}", signer.address());
 
     Ok(())
 }